@@ -0,0 +1,259 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{Controller, alarm::*};
+//! use std::collections::HashMap;
+//!
+//! let mut group = AlarmGroup::new(AlarmGroupConfig {
+//!     alarms: vec!["pressure_high".into(), "temperature_high".into()],
+//! });
+//!
+//! let mut alarms = HashMap::new();
+//! alarms.insert("temperature_high".to_string(), true);
+//! assert!(group.next(&alarms));
+//! alarms.insert("pressure_high".to_string(), true);
+//! assert!(group.next(&alarms));
+//! assert_eq!(group.first_out(), Some("temperature_high"));
+//! ```
+
+use super::{Controller, PureController};
+use std::collections::HashMap;
+
+/// A group of related alarms with first-out detection
+#[derive(Debug, Clone)]
+pub struct AlarmGroup {
+    cfg: AlarmGroupConfig,
+    /// Current group state
+    pub state: AlarmGroupState,
+}
+
+/// Alarm group configuration
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AlarmGroupConfig {
+    /// The IDs of the alarms that belong to the group
+    ///
+    /// If several alarms activate within the same cycle,
+    /// the first one of this list is recorded as first-out.
+    pub alarms: Vec<String>,
+}
+
+/// Internal alarm group state
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AlarmGroupState {
+    /// The IDs of the currently active alarms
+    pub active: Vec<String>,
+    /// The alarm that activated first
+    ///
+    /// It is latched until all alarms of the group are cleared.
+    pub first_out: Option<String>,
+}
+
+impl AlarmGroupState {
+    /// Check if any alarm of the group is active.
+    pub fn is_active(&self) -> bool {
+        !self.active.is_empty()
+    }
+}
+
+impl AlarmGroup {
+    /// Create a new alarm group.
+    pub fn new(cfg: AlarmGroupConfig) -> Self {
+        AlarmGroup {
+            cfg,
+            state: AlarmGroupState::default(),
+        }
+    }
+    /// The alarm that activated first.
+    pub fn first_out(&self) -> Option<&str> {
+        self.state.first_out.as_deref()
+    }
+    /// Reset the internal state.
+    pub fn reset(&mut self) {
+        self.state = AlarmGroupState::default();
+    }
+}
+
+impl Controller<&HashMap<String, bool>, bool> for AlarmGroup {
+    fn next(&mut self, alarms: &HashMap<String, bool>) -> bool {
+        self.state = self.cfg.next((&self.state, alarms));
+        self.state.is_active()
+    }
+}
+
+impl PureController<(&AlarmGroupState, &HashMap<String, bool>), AlarmGroupState>
+    for AlarmGroupConfig
+{
+    fn next(&self, input: (&AlarmGroupState, &HashMap<String, bool>)) -> AlarmGroupState {
+        let (state, alarms) = input;
+        let active: Vec<String> = self
+            .alarms
+            .iter()
+            .filter(|id| alarms.get(*id).cloned().unwrap_or(false))
+            .cloned()
+            .collect();
+        let first_out = if active.is_empty() {
+            None
+        } else {
+            state.first_out.clone().or_else(|| active.first().cloned())
+        };
+        AlarmGroupState { active, first_out }
+    }
+}
+
+/// The severity of a limit violation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Severity {
+    /// No limit is violated
+    #[default]
+    Normal,
+    /// A warning limit is violated
+    Warning,
+    /// An alarm limit is violated
+    Alarm,
+    /// A critical limit is violated
+    Critical,
+}
+
+/// A set of graded limits of an analog value
+///
+/// Each limit is optional. A value at or beyond a high limit
+/// (at or below a low limit) violates it and the most severe
+/// violated limit determines the [Severity].
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{PureController, alarm::*};
+///
+/// let limits = LimitSet {
+///     high_warning: Some(80.0),
+///     high_alarm: Some(90.0),
+///     ..Default::default()
+/// };
+/// assert_eq!(limits.next(85.0), Severity::Warning);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LimitSet {
+    /// Lower critical limit
+    pub low_critical: Option<f64>,
+    /// Lower alarm limit
+    pub low_alarm: Option<f64>,
+    /// Lower warning limit
+    pub low_warning: Option<f64>,
+    /// Upper warning limit
+    pub high_warning: Option<f64>,
+    /// Upper alarm limit
+    pub high_alarm: Option<f64>,
+    /// Upper critical limit
+    pub high_critical: Option<f64>,
+}
+
+impl PureController<f64, Severity> for LimitSet {
+    fn next(&self, value: f64) -> Severity {
+        let low = |limit: Option<f64>| limit.map(|l| value <= l).unwrap_or(false);
+        let high = |limit: Option<f64>| limit.map(|l| value >= l).unwrap_or(false);
+        if low(self.low_critical) || high(self.high_critical) {
+            Severity::Critical
+        } else if low(self.low_alarm) || high(self.high_alarm) {
+            Severity::Alarm
+        } else if low(self.low_warning) || high(self.high_warning) {
+            Severity::Warning
+        } else {
+            Severity::Normal
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn group() -> AlarmGroup {
+        AlarmGroup::new(AlarmGroupConfig {
+            alarms: vec!["a".into(), "b".into(), "c".into()],
+        })
+    }
+
+    #[test]
+    fn record_the_first_activated_alarm() {
+        let mut group = group();
+        let mut alarms = HashMap::new();
+        assert!(!group.next(&alarms));
+        assert_eq!(group.first_out(), None);
+
+        alarms.insert("b".to_string(), true);
+        assert!(group.next(&alarms));
+        alarms.insert("a".to_string(), true);
+        assert!(group.next(&alarms));
+        assert_eq!(group.state.active, vec!["a", "b"]);
+        assert_eq!(group.first_out(), Some("b"));
+
+        // the first-out stays latched while the group is active
+        alarms.insert("b".to_string(), false);
+        assert!(group.next(&alarms));
+        assert_eq!(group.first_out(), Some("b"));
+
+        // ... and is cleared together with the group
+        alarms.insert("a".to_string(), false);
+        assert!(!group.next(&alarms));
+        assert_eq!(group.first_out(), None);
+
+        alarms.insert("c".to_string(), true);
+        assert!(group.next(&alarms));
+        assert_eq!(group.first_out(), Some("c"));
+    }
+
+    #[test]
+    fn use_configured_order_for_simultaneous_alarms() {
+        let mut group = group();
+        let mut alarms = HashMap::new();
+        alarms.insert("c".to_string(), true);
+        alarms.insert("b".to_string(), true);
+        // alarms outside of the group are ignored
+        alarms.insert("x".to_string(), true);
+        assert!(group.next(&alarms));
+        assert_eq!(group.first_out(), Some("b"));
+        group.reset();
+        assert_eq!(group.first_out(), None);
+    }
+
+    #[test]
+    fn grade_limit_violations() {
+        let limits = LimitSet {
+            low_critical: Some(5.0),
+            low_alarm: Some(10.0),
+            low_warning: Some(20.0),
+            high_warning: Some(80.0),
+            high_alarm: Some(90.0),
+            high_critical: Some(95.0),
+        };
+        let bands = [
+            (50.0, Severity::Normal),
+            (20.1, Severity::Normal),
+            (20.0, Severity::Warning),
+            (10.0, Severity::Alarm),
+            (2.0, Severity::Critical),
+            (79.9, Severity::Normal),
+            (80.0, Severity::Warning),
+            (91.0, Severity::Alarm),
+            (95.0, Severity::Critical),
+            (120.0, Severity::Critical),
+        ];
+        for (value, severity) in bands {
+            assert_eq!(limits.next(value), severity);
+        }
+
+        // the deepest violated limit wins, even if others are missing
+        let limits = LimitSet {
+            high_warning: Some(80.0),
+            high_critical: Some(95.0),
+            ..Default::default()
+        };
+        assert_eq!(limits.next(90.0), Severity::Warning);
+        assert_eq!(limits.next(99.0), Severity::Critical);
+        assert_eq!(limits.next(-99.0), Severity::Normal);
+        assert!(Severity::Critical > Severity::Alarm);
+    }
+}
@@ -14,7 +14,7 @@
 //! assert_eq!(c.next(5.69),  false);
 //! ```
 
-use super::{Controller, PureController};
+use super::{Controller, Introspect, Introspection, PureController};
 
 /// A Bang-bang controller implementation
 #[derive(Debug, Clone)]
@@ -64,6 +64,25 @@ impl BangBang {
     }
 }
 
+impl Introspect for BangBang {
+    fn introspect(&self) -> Introspection {
+        Introspection::default()
+            .with_parameter("default_threshold", self.cfg.default_threshold)
+            .with_parameter("hysteresis", self.cfg.hysteresis)
+            .with_state("current", self.state.current)
+            .with_state("threshold", self.state.threshold)
+    }
+}
+
+/// Only contains state variables.
+impl Introspect for BangBangState {
+    fn introspect(&self) -> Introspection {
+        Introspection::default()
+            .with_state("current", self.current)
+            .with_state("threshold", self.threshold)
+    }
+}
+
 impl Controller<f64, bool> for BangBang {
     fn next(&mut self, actual: f64) -> bool {
         self.state = self.cfg.next((self.state, actual));
@@ -175,4 +194,19 @@ mod tests {
         assert_eq!(bb.next(-0.6), false);
         assert_eq!(bb.next(NAN), false);
     }
+
+    #[test]
+    fn introspect_bang_bang() {
+        use crate::Value;
+
+        let mut bb = BangBang::new(BangBangConfig {
+            default_threshold: 2.0,
+            hysteresis: 0.5,
+        });
+        bb.next(3.0);
+        let info = bb.introspect();
+        assert_eq!(info.parameter("hysteresis"), Some(&Value::Decimal(0.5)));
+        assert_eq!(info.state_variable("current"), Some(&Value::Bit(true)));
+        assert_eq!(info.state_variable("threshold"), Some(&Value::Decimal(2.0)));
+    }
 }
@@ -0,0 +1,978 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{PureController, command::*};
+//!
+//! // Close below 40 %, open from 60 % and stop in between.
+//! let mapping = CommandMapping::three_position(40.0, 60.0);
+//!
+//! assert_eq!(mapping.next(12.0), ValveCommand::Close);
+//! assert_eq!(mapping.next(50.0), ValveCommand::Stop);
+//! assert_eq!(mapping.next(75.0), ValveCommand::Open);
+//! ```
+
+use super::{Controller, PureController};
+use crate::util::{cap_delta_t, DurationInSeconds};
+use std::{cmp::Ordering, time::Duration};
+
+/// Command of a 3-position actuator (e.g. a motor operated valve)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ValveCommand {
+    /// Drive the actuator towards the open position
+    Open,
+    /// Keep the current position
+    Stop,
+    /// Drive the actuator towards the closed position
+    Close,
+}
+
+/// A band of controller output values that is mapped to a command.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CommandBand<C> {
+    /// The lower (inclusive) bound of the band
+    pub min: f64,
+    /// The command that is issued within the band
+    pub command: C,
+}
+
+/// Maps a continuous controller output to a set of discrete commands.
+///
+/// The bands are defined by their lower bounds. A value is mapped to
+/// the command of the band with the highest lower bound that is
+/// less than or equal to the value. Values below all bands
+/// (and `NaN`) are mapped to the `default` command.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CommandMapping<C> {
+    /// The command for values below all bands
+    pub default: C,
+    /// The bands
+    pub bands: Vec<CommandBand<C>>,
+}
+
+impl<C> CommandMapping<C> {
+    /// Create a new mapping without any bands.
+    pub fn new(default: C) -> Self {
+        CommandMapping {
+            default,
+            bands: vec![],
+        }
+    }
+    /// Add a band starting at `min`.
+    pub fn band(mut self, min: f64, command: C) -> Self {
+        self.bands.push(CommandBand { min, command });
+        self
+    }
+}
+
+impl CommandMapping<ValveCommand> {
+    /// Create a mapping for a 3-position actuator.
+    ///
+    /// Values below `close_below` close, values from `open_at` upwards
+    /// open and values in between stop the actuator.
+    pub fn three_position(close_below: f64, open_at: f64) -> Self {
+        CommandMapping::new(ValveCommand::Close)
+            .band(close_below, ValveCommand::Stop)
+            .band(open_at, ValveCommand::Open)
+    }
+}
+
+impl<C> PureController<f64, C> for CommandMapping<C>
+where
+    C: Clone,
+{
+    fn next(&self, value: f64) -> C {
+        self.bands
+            .iter()
+            .filter(|b| value >= b.min)
+            .max_by(|a, b| a.min.partial_cmp(&b.min).unwrap_or(Ordering::Equal))
+            .map(|b| b.command.clone())
+            .unwrap_or_else(|| self.default.clone())
+    }
+}
+
+/// Converts a position command (0–100 %) into open/close pulses.
+///
+/// The position of the actuator is estimated by integrating
+/// the pulses based on its full-stroke time.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, command::*};
+/// use std::time::Duration;
+///
+/// let mut valve = PulseOutput::new(PulseOutputConfig {
+///     stroke_time: Duration::from_secs(60),
+///     deadband: 1.0,
+/// });
+/// let dt = Duration::from_secs(1);
+/// assert_eq!(valve.next(30.0, &dt), ValveCommand::Open);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PulseOutput {
+    cfg: PulseOutputConfig,
+    /// Current pulse output state
+    pub state: PulseOutputState,
+}
+
+/// Pulse output configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PulseOutputConfig {
+    /// The time the actuator needs to travel from 0 % to 100 %
+    pub stroke_time: Duration,
+    /// Position deviations (in %) up to this value don't issue a pulse
+    pub deadband: f64,
+}
+
+/// Internal pulse output state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PulseOutputState {
+    /// The estimated position in %
+    pub position: f64,
+}
+
+impl Default for PulseOutputConfig {
+    fn default() -> Self {
+        PulseOutputConfig {
+            stroke_time: Duration::from_secs(60),
+            deadband: 0.0,
+        }
+    }
+}
+
+impl PulseOutput {
+    /// Create a new instance.
+    pub fn new(cfg: PulseOutputConfig) -> Self {
+        PulseOutput {
+            cfg,
+            state: PulseOutputState::default(),
+        }
+    }
+    /// The estimated position in %.
+    pub fn position(&self) -> f64 {
+        self.state.position
+    }
+    /// Synchronize the estimated position, e.g. with an end position switch.
+    pub fn set_position(&mut self, position: f64) {
+        self.state.position = position.clamp(0.0, 100.0);
+    }
+}
+
+impl Controller<(f64, &Duration), ValveCommand> for PulseOutput {
+    fn next(&mut self, input: (f64, &Duration)) -> ValveCommand {
+        let (target, duration) = input;
+        let (state, cmd) = self.cfg.next((self.state, target, duration));
+        self.state = state;
+        cmd
+    }
+}
+
+impl PureController<(PulseOutputState, f64, &Duration), (PulseOutputState, ValveCommand)>
+    for PulseOutputConfig
+{
+    fn next(&self, input: (PulseOutputState, f64, &Duration)) -> (PulseOutputState, ValveCommand) {
+        let (mut state, target, duration) = input;
+        let stroke_time = f64::from(DurationInSeconds::from(self.stroke_time));
+        let step = if stroke_time > 0.0 {
+            100.0 * f64::from(DurationInSeconds::from(*duration)) / stroke_time
+        } else {
+            100.0
+        };
+        let error = target.clamp(0.0, 100.0) - state.position;
+        // Pulses shorter than half a cycle would overshoot
+        // more than they would approach the target.
+        if error.is_nan() || error.abs() <= self.deadband || error.abs() < step / 2.0 {
+            return (state, ValveCommand::Stop);
+        }
+        let cmd = if error > 0.0 {
+            state.position += step;
+            ValveCommand::Open
+        } else {
+            state.position -= step;
+            ValveCommand::Close
+        };
+        state.position = state.position.clamp(0.0, 100.0);
+        (state, cmd)
+    }
+}
+
+/// Enforces a minimum change of a position command.
+///
+/// A new position is only forwarded if it differs from the last
+/// forwarded position by at least the threshold. Smaller changes are
+/// suppressed but accumulate, so a slow drift eventually results in
+/// a single move, while a 1-step jitter doesn't move the actuator.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{Controller, command::*};
+///
+/// let mut stepper = MinimumMove::new(MinimumMoveConfig { threshold: 5.0 });
+/// assert_eq!(stepper.next(100.0), 100.0);
+/// assert_eq!(stepper.next(101.0), 100.0);
+/// assert_eq!(stepper.next(106.0), 106.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MinimumMove {
+    cfg: MinimumMoveConfig,
+    /// Current state
+    pub state: MinimumMoveState,
+}
+
+/// Minimum move configuration
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MinimumMoveConfig {
+    /// The minimum (absolute) change of the position
+    pub threshold: f64,
+}
+
+/// Internal minimum move state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MinimumMoveState {
+    /// The last forwarded position
+    pub position: Option<f64>,
+}
+
+impl MinimumMove {
+    /// Create a new instance.
+    pub fn new(cfg: MinimumMoveConfig) -> Self {
+        MinimumMove {
+            cfg,
+            state: MinimumMoveState::default(),
+        }
+    }
+    /// Reset the internal state.
+    ///
+    /// The next position is forwarded in any case.
+    pub fn reset(&mut self) {
+        self.state = MinimumMoveState::default();
+    }
+}
+
+impl Controller<f64, f64> for MinimumMove {
+    fn next(&mut self, position: f64) -> f64 {
+        let (state, position) = self.cfg.next((self.state, position));
+        self.state = state;
+        position
+    }
+}
+
+impl PureController<(MinimumMoveState, f64), (MinimumMoveState, f64)> for MinimumMoveConfig {
+    fn next(&self, input: (MinimumMoveState, f64)) -> (MinimumMoveState, f64) {
+        let (mut state, position) = input;
+        match state.position {
+            Some(current) if (position - current).abs() < self.threshold => (state, current),
+            _ => {
+                state.position = Some(position);
+                (state, position)
+            }
+        }
+    }
+}
+
+/// Counts the travel of an actuator.
+///
+/// The total travel (the sum of all position changes)
+/// and the number of direction reversals are indicators
+/// for the wear of a valve and can be used to schedule maintenance.
+///
+/// The commands are passed through unchanged.
+/// Non-finite commands (e.g. `NaN`) are not counted.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{Controller, command::*};
+///
+/// let mut counter = TravelCounter::default();
+/// counter.next(10.0);
+/// counter.next(30.0);
+/// counter.next(25.0);
+/// assert_eq!(counter.travel(), 25.0);
+/// assert_eq!(counter.reversals(), 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TravelCounter {
+    /// Current state
+    pub state: TravelCounterState,
+}
+
+/// Internal travel counter state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TravelCounterState {
+    /// The last position
+    pub position: Option<f64>,
+    /// The direction of the last move (`true` if rising)
+    pub rising: Option<bool>,
+    /// The accumulated travel
+    pub travel: f64,
+    /// The number of direction reversals
+    pub reversals: u64,
+}
+
+impl TravelCounter {
+    /// The accumulated travel
+    pub fn travel(&self) -> f64 {
+        self.state.travel
+    }
+    /// The number of direction reversals
+    pub fn reversals(&self) -> u64 {
+        self.state.reversals
+    }
+    /// Reset the counters, e.g. after maintenance.
+    ///
+    /// The current position is kept, so the next
+    /// move is counted correctly.
+    pub fn reset(&mut self) {
+        self.state = TravelCounterState {
+            position: self.state.position,
+            ..Default::default()
+        };
+    }
+}
+
+impl Controller<f64, f64> for TravelCounter {
+    fn next(&mut self, position: f64) -> f64 {
+        if !position.is_finite() {
+            return position;
+        }
+        let state = &mut self.state;
+        if let Some(last) = state.position {
+            let delta = position - last;
+            if delta != 0.0 {
+                let rising = delta > 0.0;
+                if state.rising.map(|r| r != rising).unwrap_or(false) {
+                    state.reversals += 1;
+                }
+                state.rising = Some(rising);
+                state.travel += delta.abs();
+            }
+        }
+        state.position = Some(position);
+        position
+    }
+}
+
+/// Converts an absolute position into incremental step commands,
+/// e.g. for stepper drives
+///
+/// The commanded position is tracked internally and one step
+/// (`+1` or `-1`) is issued per cycle until the remaining distance
+/// is at most half a step (`0`).
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{Controller, command::*};
+///
+/// let mut stepper = StepOutput::new(StepOutputConfig { step_size: 0.5 });
+/// assert_eq!(stepper.next(1.0), 1);
+/// assert_eq!(stepper.next(1.0), 1);
+/// assert_eq!(stepper.next(1.0), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StepOutput {
+    cfg: StepOutputConfig,
+    /// Current state
+    pub state: StepOutputState,
+}
+
+/// Step output configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StepOutputConfig {
+    /// The change of the position per step
+    pub step_size: f64,
+}
+
+/// Internal step output state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StepOutputState {
+    /// The commanded position
+    pub position: f64,
+}
+
+impl Default for StepOutputConfig {
+    fn default() -> Self {
+        StepOutputConfig { step_size: 1.0 }
+    }
+}
+
+impl StepOutput {
+    /// Create a new instance.
+    pub fn new(cfg: StepOutputConfig) -> Self {
+        StepOutput {
+            cfg,
+            state: StepOutputState::default(),
+        }
+    }
+    /// The commanded position.
+    pub fn position(&self) -> f64 {
+        self.state.position
+    }
+    /// Set the position, e.g. after homing the drive.
+    pub fn set_position(&mut self, position: f64) {
+        self.state.position = position;
+    }
+}
+
+impl Controller<f64, i8> for StepOutput {
+    fn next(&mut self, target: f64) -> i8 {
+        let (state, step) = self.cfg.next((self.state, target));
+        self.state = state;
+        step
+    }
+}
+
+impl PureController<(StepOutputState, f64), (StepOutputState, i8)> for StepOutputConfig {
+    fn next(&self, input: (StepOutputState, f64)) -> (StepOutputState, i8) {
+        let (mut state, target) = input;
+        let distance = target - state.position;
+        if self.step_size <= 0.0 || distance.is_nan() || distance.abs() <= self.step_size / 2.0 {
+            return (state, 0);
+        }
+        let step = if distance > 0.0 { 1 } else { -1 };
+        state.position += f64::from(step) * self.step_size;
+        (state, step)
+    }
+}
+
+/// Lead/lag control of a duplex pump set
+///
+/// The lead pump starts if the demand reaches `start` and stops
+/// if it falls to `stop`. The lag pump assists if the demand stays
+/// at or above `lag_start` for `lag_delay` and stops at `lag_stop`.
+/// The lead role alternates between the pumps to equalize wear.
+/// Each pump has to run for `min_on` and rest for `min_off`
+/// before it can be switched again.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, command::*};
+/// use std::time::Duration;
+///
+/// let mut pumps = LeadLagPump::new(LeadLagPumpConfig {
+///     start: 60.0,
+///     stop: 20.0,
+///     lag_start: 90.0,
+///     lag_stop: 70.0,
+///     ..Default::default()
+/// });
+/// let dt = Duration::from_secs(1);
+/// let level = 65.0;
+/// let (pump_a, pump_b) = pumps.next(level, &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LeadLagPump {
+    cfg: LeadLagPumpConfig,
+    /// Current state
+    pub state: LeadLagPumpState,
+}
+
+/// Lead/lag pump configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LeadLagPumpConfig {
+    /// Demand to start the lead pump
+    pub start: f64,
+    /// Demand to stop the lead pump
+    pub stop: f64,
+    /// Demand to start the lag pump
+    pub lag_start: f64,
+    /// Demand to stop the lag pump
+    pub lag_stop: f64,
+    /// The time the demand has to stay high to start the lag pump
+    pub lag_delay: Duration,
+    /// The minimum run time of a pump
+    pub min_on: Duration,
+    /// The minimum rest time of a pump
+    pub min_off: Duration,
+    /// How the lead role alternates
+    pub alternation: Alternation,
+}
+
+/// Alternation of the lead role
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Alternation {
+    /// The pumps take turns on each start
+    #[default]
+    EachStart,
+    /// The pump with the lower run time leads
+    RunTime,
+}
+
+/// The state of a single pump
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PumpState {
+    /// The pump is running
+    pub running: bool,
+    /// The time since the last switch
+    pub since: Duration,
+    /// The total run time
+    pub run_time: Duration,
+}
+
+/// Internal lead/lag pump state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LeadLagPumpState {
+    /// The pumps
+    pub pumps: [PumpState; 2],
+    /// The index of the lead pump
+    pub lead: usize,
+    /// The number of starts
+    pub starts: u64,
+    /// The time the demand is high
+    pub high_demand: Duration,
+}
+
+impl Default for LeadLagPumpConfig {
+    fn default() -> Self {
+        LeadLagPumpConfig {
+            start: 1.0,
+            stop: 0.0,
+            lag_start: 2.0,
+            lag_stop: 1.0,
+            lag_delay: Duration::from_secs(10),
+            min_on: Duration::new(0, 0),
+            min_off: Duration::new(0, 0),
+            alternation: Alternation::default(),
+        }
+    }
+}
+
+impl LeadLagPump {
+    /// Create a new instance.
+    pub fn new(cfg: LeadLagPumpConfig) -> Self {
+        LeadLagPump {
+            cfg,
+            state: LeadLagPumpState::default(),
+        }
+    }
+    /// The index of the current lead pump.
+    pub fn lead(&self) -> usize {
+        self.state.lead
+    }
+}
+
+impl PumpState {
+    fn switch(&mut self, running: bool) {
+        self.running = running;
+        self.since = Duration::new(0, 0);
+    }
+}
+
+impl Controller<(f64, &Duration), (bool, bool)> for LeadLagPump {
+    fn next(&mut self, input: (f64, &Duration)) -> (bool, bool) {
+        let (demand, duration) = input;
+        self.state = self.cfg.next((self.state, demand, duration));
+        let [a, b] = self.state.pumps;
+        (a.running, b.running)
+    }
+}
+
+impl PureController<(LeadLagPumpState, f64, &Duration), LeadLagPumpState> for LeadLagPumpConfig {
+    fn next(&self, input: (LeadLagPumpState, f64, &Duration)) -> LeadLagPumpState {
+        let (mut state, demand, duration) = input;
+        for p in &mut state.pumps {
+            p.since += *duration;
+            if p.running {
+                p.run_time += *duration;
+            }
+        }
+        if demand >= self.lag_start {
+            state.high_demand += *duration;
+        } else {
+            state.high_demand = Duration::new(0, 0);
+        }
+        let can_start = |p: &PumpState| !p.running && p.since >= self.min_off;
+        let can_stop = |p: &PumpState| p.running && p.since >= self.min_on;
+
+        let idle = state.pumps.iter().all(|p| !p.running);
+        if idle && demand >= self.start {
+            let lead = match self.alternation {
+                Alternation::EachStart if state.starts > 0 => 1 - state.lead,
+                Alternation::EachStart => state.lead,
+                Alternation::RunTime => {
+                    let [a, b] = state.pumps;
+                    if b.run_time < a.run_time {
+                        1
+                    } else {
+                        0
+                    }
+                }
+            };
+            // fall back to the other pump if the lead has to rest
+            let lead = if can_start(&state.pumps[lead]) {
+                Some(lead)
+            } else if can_start(&state.pumps[1 - lead]) {
+                Some(1 - lead)
+            } else {
+                None
+            };
+            if let Some(lead) = lead {
+                state.lead = lead;
+                state.starts += 1;
+                state.pumps[lead].switch(true);
+            }
+        } else if !idle {
+            let (lead, lag) = (state.lead, 1 - state.lead);
+            if demand <= self.stop {
+                for p in &mut state.pumps {
+                    if can_stop(p) {
+                        p.switch(false);
+                    }
+                }
+            } else if can_start(&state.pumps[lag]) && state.high_demand >= self.lag_delay {
+                state.pumps[lag].switch(true);
+            } else if demand <= self.lag_stop
+                && state.pumps[lead].running
+                && can_stop(&state.pumps[lag])
+            {
+                state.pumps[lag].switch(false);
+            }
+        }
+        state
+    }
+}
+
+/// Correction pulses on an accumulated control error
+///
+/// The control error is integrated over time. The output stays at `0.0`
+/// until the magnitude of the integral reaches `threshold`, then a single
+/// correction pulse of `±pulse` is issued and the integral restarts
+/// from zero. This minimizes actuator movements for slow processes
+/// where continuous adjustment is unnecessary.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, command::*};
+/// use std::time::Duration;
+///
+/// let mut ctl = IntegralPulse::new(IntegralPulseConfig {
+///     threshold: 10.0,
+///     pulse: 2.0,
+///     ..Default::default()
+/// });
+/// ctl.set_target(7.2);
+/// let dt = Duration::from_secs(1);
+/// let ph = 7.0;
+/// let dosing = ctl.next(ph, &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IntegralPulse {
+    cfg: IntegralPulseConfig,
+    /// Current state
+    pub state: IntegralPulseState,
+}
+
+/// Integral pulse configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IntegralPulseConfig {
+    /// The error integral (error × seconds) that triggers a pulse
+    pub threshold: f64,
+    /// The magnitude of a correction pulse
+    pub pulse: f64,
+    /// Maximum plausible time step (see [crate::util::cap_delta_t])
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_delta_t: Option<Duration>,
+}
+
+/// Internal integral pulse state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IntegralPulseState {
+    /// Current target
+    pub target: f64,
+    /// The accumulated error since the last pulse
+    pub integral: f64,
+}
+
+impl Default for IntegralPulseConfig {
+    fn default() -> Self {
+        IntegralPulseConfig {
+            threshold: 1.0,
+            pulse: 1.0,
+            max_delta_t: None,
+        }
+    }
+}
+
+impl IntegralPulse {
+    /// Create a new instance.
+    pub fn new(cfg: IntegralPulseConfig) -> Self {
+        IntegralPulse {
+            cfg,
+            state: IntegralPulseState::default(),
+        }
+    }
+    /// Set target value.
+    pub fn set_target(&mut self, target: f64) {
+        self.state.target = target;
+    }
+    /// Clear the accumulated error.
+    pub fn reset(&mut self) {
+        self.state.integral = 0.0;
+    }
+}
+
+impl Controller<(f64, &Duration), f64> for IntegralPulse {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (actual, duration) = input;
+        let (state, output) = self.cfg.next((self.state, actual, duration));
+        self.state = state;
+        output
+    }
+}
+
+impl PureController<(IntegralPulseState, f64, &Duration), (IntegralPulseState, f64)>
+    for IntegralPulseConfig
+{
+    fn next(&self, input: (IntegralPulseState, f64, &Duration)) -> (IntegralPulseState, f64) {
+        let (mut state, actual, duration) = input;
+        let delta_t = f64::from(DurationInSeconds::from(cap_delta_t(
+            duration,
+            self.max_delta_t,
+        )));
+        let error = state.target - actual;
+        if !error.is_nan() {
+            state.integral += error * delta_t;
+        }
+        if state.integral.abs() < self.threshold {
+            return (state, 0.0);
+        }
+        let pulse = self.pulse.copysign(state.integral);
+        state.integral = 0.0;
+        (state, pulse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn map_three_position_valve() {
+        let mapping = CommandMapping::three_position(40.0, 60.0);
+        let bands = vec![
+            (-5.0, ValveCommand::Close),
+            (0.0, ValveCommand::Close),
+            (39.9, ValveCommand::Close),
+            (40.0, ValveCommand::Stop),
+            (50.0, ValveCommand::Stop),
+            (59.9, ValveCommand::Stop),
+            (60.0, ValveCommand::Open),
+            (100.0, ValveCommand::Open),
+        ];
+        for (input, cmd) in bands {
+            assert_eq!(mapping.next(input), cmd);
+        }
+    }
+
+    #[test]
+    fn map_named_commands() {
+        let mapping = CommandMapping::new("off")
+            .band(70.0, "high")
+            .band(10.0, "low")
+            .band(40.0, "medium");
+        assert_eq!(mapping.next(5.0), "off");
+        assert_eq!(mapping.next(10.0), "low");
+        assert_eq!(mapping.next(45.0), "medium");
+        assert_eq!(mapping.next(90.0), "high");
+        assert_eq!(mapping.next(f64::NAN), "off");
+    }
+
+    #[test]
+    fn pulse_to_half_open() {
+        let mut valve = PulseOutput::new(PulseOutputConfig {
+            stroke_time: Duration::from_secs(20),
+            deadband: 0.2,
+        });
+        let dt = Duration::from_millis(100);
+        let mut open_time = Duration::new(0, 0);
+        for _ in 0..200 {
+            match valve.next((50.0, &dt)) {
+                ValveCommand::Open => open_time += dt,
+                ValveCommand::Stop => {}
+                ValveCommand::Close => panic!("unexpected close pulse"),
+            }
+        }
+        assert_eq!(open_time, Duration::from_secs(10));
+        assert!((valve.position() - 50.0).abs() < 1e-9);
+
+        assert_eq!(valve.next((50.2, &dt)), ValveCommand::Stop);
+        assert_eq!(valve.next((40.0, &dt)), ValveCommand::Close);
+        assert!((valve.position() - 49.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn limit_estimated_position() {
+        let mut valve = PulseOutput::new(PulseOutputConfig {
+            stroke_time: Duration::from_secs(1),
+            deadband: 0.0,
+        });
+        let dt = Duration::from_millis(600);
+        assert_eq!(valve.next((120.0, &dt)), ValveCommand::Open);
+        assert_eq!(valve.next((120.0, &dt)), ValveCommand::Open);
+        assert_eq!(valve.position(), 100.0);
+        assert_eq!(valve.next((120.0, &dt)), ValveCommand::Stop);
+        valve.set_position(0.0);
+        assert_eq!(valve.next((f64::NAN, &dt)), ValveCommand::Stop);
+    }
+
+    #[test]
+    fn accumulate_small_changes_to_one_move() {
+        let mut stepper = MinimumMove::new(MinimumMoveConfig { threshold: 3.0 });
+        assert_eq!(stepper.next(10.0), 10.0);
+        let mut moves = 0;
+        for position in &[11.0, 10.0, 11.0, 12.0, 12.0, 13.0, 14.0] {
+            let out = stepper.next(*position);
+            if out != 10.0 {
+                moves += 1;
+                assert_eq!(out, 13.0);
+                assert_eq!(*position, 13.0);
+                break;
+            }
+        }
+        assert_eq!(moves, 1);
+        assert_eq!(stepper.next(14.0), 13.0);
+        assert_eq!(stepper.next(10.0), 10.0);
+        stepper.reset();
+        assert_eq!(stepper.next(10.5), 10.5);
+    }
+
+    #[test]
+    fn count_travel_and_reversals() {
+        let mut counter = TravelCounter::default();
+        for pos in [10.0, 20.0, 20.0, 50.0, 40.0, 40.0, 30.0, 35.0] {
+            assert_eq!(counter.next(pos), pos);
+        }
+        assert_eq!(counter.travel(), 65.0);
+        assert_eq!(counter.reversals(), 2);
+
+        counter.reset();
+        assert_eq!(counter.travel(), 0.0);
+        assert_eq!(counter.reversals(), 0);
+        // the direction before the reset is not a reversal
+        counter.next(30.0);
+        assert_eq!(counter.travel(), 5.0);
+        assert_eq!(counter.reversals(), 0);
+
+        // invalid commands are passed through but not counted
+        assert!(counter.next(f64::NAN).is_nan());
+        assert_eq!(counter.next(f64::INFINITY), f64::INFINITY);
+        counter.next(28.0);
+        assert_eq!(counter.travel(), 7.0);
+        assert_eq!(counter.reversals(), 0);
+    }
+
+    #[test]
+    fn alternate_lead_pump_and_engage_lag() {
+        let mut pumps = LeadLagPump::new(LeadLagPumpConfig {
+            start: 60.0,
+            stop: 20.0,
+            lag_start: 90.0,
+            lag_stop: 70.0,
+            lag_delay: Duration::from_secs(3),
+            min_on: Duration::from_secs(3),
+            min_off: Duration::from_secs(1),
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        let mut next = |demand| pumps.next((demand, &dt));
+
+        // several start cycles
+        for lead in [(true, false), (false, true), (true, false)] {
+            assert_eq!(next(50.0), (false, false));
+            assert_eq!(next(65.0), lead);
+            assert_eq!(next(40.0), lead);
+            // minimum run time
+            assert_eq!(next(10.0), lead);
+            assert_eq!(next(10.0), (false, false));
+        }
+
+        // sustained high demand
+        assert_eq!(next(65.0), (false, true));
+        assert_eq!(next(95.0), (false, true));
+        assert_eq!(next(95.0), (false, true));
+        assert_eq!(next(95.0), (true, true));
+        assert_eq!(next(80.0), (true, true));
+        assert_eq!(next(80.0), (true, true));
+        assert_eq!(next(70.0), (false, true));
+        assert_eq!(next(10.0), (false, false));
+
+        // a short peak doesn't start the lag pump
+        assert_eq!(next(65.0), (true, false));
+        assert_eq!(next(95.0), (true, false));
+        assert_eq!(next(85.0), (true, false));
+        assert_eq!(next(95.0), (true, false));
+    }
+
+    #[test]
+    fn alternate_by_run_time() {
+        let mut pumps = LeadLagPump::new(LeadLagPumpConfig {
+            alternation: Alternation::RunTime,
+            ..Default::default()
+        });
+        pumps.state.pumps[0].run_time = Duration::from_secs(3600);
+        let dt = Duration::from_secs(1);
+        assert_eq!(pumps.next((1.0, &dt)), (false, true));
+        assert_eq!(pumps.lead(), 1);
+    }
+
+    #[test]
+    fn issue_one_step_per_cycle() {
+        let mut stepper = StepOutput::new(StepOutputConfig { step_size: 0.25 });
+        let steps: Vec<_> = (0..6).map(|_| stepper.next(1.0)).collect();
+        assert_eq!(steps, vec![1, 1, 1, 1, 0, 0]);
+        assert_eq!(stepper.position(), 1.0);
+
+        // the nearest step is approached
+        let steps: Vec<_> = (0..4).map(|_| stepper.next(0.4)).collect();
+        assert_eq!(steps, vec![-1, -1, 0, 0]);
+        assert_eq!(stepper.position(), 0.5);
+
+        stepper.set_position(0.0);
+        assert_eq!(stepper.next(0.1), 0);
+        assert_eq!(stepper.next(-0.2), -1);
+        assert_eq!(stepper.next(f64::NAN), 0);
+    }
+
+    #[test]
+    fn pulse_on_integral_threshold() {
+        let mut ctl = IntegralPulse::new(IntegralPulseConfig {
+            threshold: 3.0,
+            pulse: 5.0,
+            ..Default::default()
+        });
+        ctl.set_target(10.0);
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next((9.0, &dt)), 0.0);
+        assert_eq!(ctl.next((9.0, &dt)), 0.0);
+        assert_eq!(ctl.state.integral, 2.0);
+        assert_eq!(ctl.next((9.0, &dt)), 5.0);
+        // the integral restarts after a pulse
+        assert_eq!(ctl.state.integral, 0.0);
+        assert_eq!(ctl.next((9.0, &dt)), 0.0);
+        // opposing errors cancel out
+        assert_eq!(ctl.next((11.0, &dt)), 0.0);
+        assert_eq!(ctl.state.integral, 0.0);
+        // negative pulses
+        assert_eq!(ctl.next((12.0, &Duration::from_secs(2))), -5.0);
+        assert_eq!(ctl.next((10.0, &dt)), 0.0);
+    }
+
+    #[test]
+    fn settle_at_half_a_step() {
+        let mut stepper = StepOutput::new(StepOutputConfig { step_size: 0.25 });
+        let steps: Vec<_> = (0..4).map(|_| stepper.next(0.125)).collect();
+        assert_eq!(steps, vec![0, 0, 0, 0]);
+        assert_eq!(stepper.position(), 0.0);
+
+        let steps: Vec<_> = (0..4).map(|_| stepper.next(-0.375)).collect();
+        assert_eq!(steps, vec![-1, 0, 0, 0]);
+        assert_eq!(stepper.position(), -0.25);
+    }
+}
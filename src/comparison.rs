@@ -1,5 +1,7 @@
 use super::*;
 use std::{
+    borrow::Cow,
+    collections::VecDeque,
     f64::EPSILON,
     io::{Error, Result},
 };
@@ -19,6 +21,40 @@ pub enum Comparator {
     Equal,
     /// `!=` or `NE` (Not Equal)
     NotEqual,
+    /// `~=` (Approximately Equal)
+    ///
+    /// The tolerance of the [Comparison] follows the right hand side,
+    /// e.g. `in.x ~= 5.0 +/- 0.5` or `in.x ~= 5.0 +/- 2%`.
+    /// Only numbers can be compared approximately.
+    ApproxEqual,
+}
+
+/// The tolerance of an approximate comparison
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    /// An absolute deviation
+    Absolute(f64),
+    /// A deviation in percent of the reading (the left hand side)
+    ///
+    /// This is useful for sensors whose accuracy scales with magnitude.
+    Percent(f64),
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance::Absolute(0.0)
+    }
+}
+
+impl Tolerance {
+    /// Check if two numbers are equal within the tolerance.
+    pub fn approx_eq(self, reading: f64, other: f64) -> bool {
+        let max = match self {
+            Tolerance::Absolute(t) => t.abs(),
+            Tolerance::Percent(p) => (reading * p / 100.0).abs(),
+        };
+        (reading - other).abs() <= max
+    }
 }
 
 /// A comparison between two data sources
@@ -27,16 +63,54 @@ pub struct Comparison {
     pub(crate) left: Source,
     pub(crate) cmp: Comparator,
     pub(crate) right: Source,
+    pub(crate) encoding: BitEncoding,
+    /// Only used by [Comparator::ApproxEqual]
+    pub(crate) tolerance: Tolerance,
 }
 
 impl Evaluation<SystemState> for Comparison {
     type Output = bool;
     fn eval(&self, state: &SystemState) -> Result<bool> {
+        let left = get_val(&self.left, state)?;
+        let right = get_val(&self.right, state)?;
+        self.compare(&left, &right)
+    }
+}
+
+impl Evaluation<IoState> for Comparison {
+    type Output = bool;
+    fn eval(&self, state: &IoState) -> Result<bool> {
+        let left = get_io_val(&self.left, state)?;
+        let right = get_io_val(&self.right, state)?;
+        self.compare(left, right)
+    }
+}
+
+impl Comparison {
+    /// Compare bits as numbers with the given encoding,
+    /// e.g. to compare a digital input with a numeric threshold.
+    ///
+    /// By default bits can only be compared with bits (and timeouts).
+    /// The textual representation marks the encoding
+    /// with a suffix like `[bits: 1.0/0.0]`.
+    pub fn with_bit_encoding(mut self, encoding: BitEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+    fn compare(&self, left: &Value, right: &Value) -> Result<bool> {
         use crate::Comparator::*;
         use crate::ErrorKind::*;
         use crate::Value::*;
-        let left = get_val(&self.left, state)?;
-        let right = get_val(&self.right, state)?;
+        if let BitEncoding::Numeric { .. } = self.encoding {
+            if matches!(left, Bit(_)) || matches!(right, Bit(_)) {
+                if let (Ok(a), Ok(b)) = (
+                    left.to_decimal(self.encoding),
+                    right.to_decimal(self.encoding),
+                ) {
+                    return self.compare(&Decimal(a), &Decimal(b));
+                }
+            }
+        }
         let res = match left {
             Bit(a) => match right {
                 Bit(b) => match self.cmp {
@@ -72,21 +146,38 @@ impl Evaluation<SystemState> for Comparison {
                     ));
                 }
             },
-            Decimal(a) => {
-                if let Decimal(b) = right {
-                    match self.cmp {
-                        Less => a < b,
-                        LessOrEqual => a <= b,
-                        Greater => a > b,
-                        GreaterOrEqual => a >= b,
-                        Equal => (a - b).abs() < EPSILON,
-                        NotEqual => (a - b).abs() > EPSILON,
+            // Quantities can be compared with quantities of the same unit
+            // and with plain decimals (of the same unit).
+            Decimal(a) | Quantity(a, _) => {
+                let b = match (left, right) {
+                    (Quantity(_, unit_a), Quantity(b, unit_b)) => {
+                        if unit_a != unit_b {
+                            return Err(Error::new(
+                                InvalidInput,
+                                format!(
+                                    "Quantities of different units ('{}' and '{}') can't be compared",
+                                    unit_a, unit_b
+                                ),
+                            ));
+                        }
+                        b
                     }
-                } else {
-                    return Err(Error::new(
-                        InvalidInput,
-                        "Decimal values can only compared with other decimals",
-                    ));
+                    (_, Decimal(b)) | (Decimal(_), Quantity(b, _)) => b,
+                    _ => {
+                        return Err(Error::new(
+                            InvalidInput,
+                            "Decimal values can only compared with other decimals",
+                        ));
+                    }
+                };
+                match self.cmp {
+                    Less => a < b,
+                    LessOrEqual => a <= b,
+                    Greater => a > b,
+                    GreaterOrEqual => a >= b,
+                    Equal => (a - b).abs() < EPSILON,
+                    NotEqual => (a - b).abs() > EPSILON,
+                    ApproxEqual => self.tolerance.approx_eq(*a, *b),
                 }
             }
             Integer(a) => {
@@ -98,6 +189,7 @@ impl Evaluation<SystemState> for Comparison {
                         GreaterOrEqual => a >= b,
                         Equal => a == b,
                         NotEqual => a != b,
+                        ApproxEqual => self.tolerance.approx_eq(*a as f64, *b as f64),
                     }
                 } else {
                     return Err(Error::new(
@@ -160,6 +252,12 @@ impl Evaluation<SystemState> for Comparison {
                     GreaterOrEqual => a >= b,
                     Equal => a == b,
                     NotEqual => a != b,
+                    ApproxEqual => {
+                        return Err(Error::new(
+                            InvalidInput,
+                            "Timeouts can't be compared approximately",
+                        ));
+                    }
                 },
                 Bit(b) => {
                     let timed_out = *a == Duration::new(0, 0);
@@ -189,40 +287,307 @@ impl Evaluation<SystemState> for Comparison {
     }
 }
 
-fn get_val<'a>(src: &'a Source, state: &'a SystemState) -> Result<&'a Value> {
+/// What a stale input means for a [GuardedComparison]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnStale {
+    /// The result is unknown (`None`)
+    #[default]
+    Unknown,
+    /// The comparison fails (`Some(false)`)
+    False,
+}
+
+/// A comparison that doesn't act on stale sensor data
+///
+/// If an input of the comparison wasn't updated within `max_age`
+/// (or never), the result is defined by `on_stale`.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+/// use std::time::Duration;
+///
+/// let high_temp = GuardedComparison {
+///     comparison: Source::In("tcr001".into()).cmp_gt(90.0.into()),
+///     max_age: Duration::from_secs(5),
+///     on_stale: OnStale::Unknown,
+/// };
+/// let state = TimestampedIoState::default();
+/// assert_eq!(high_temp.eval(&state).unwrap(), None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuardedComparison {
+    /// The comparison
+    pub comparison: Comparison,
+    /// The maximum age of the inputs
+    pub max_age: Duration,
+    /// The result in case of stale inputs
+    pub on_stale: OnStale,
+}
+
+impl GuardedComparison {
+    fn is_stale(&self, src: &Source, state: &TimestampedIoState) -> bool {
+        match src {
+            Source::In(id) => state.age(id).map(|a| a > self.max_age).unwrap_or(true),
+            _ => false,
+        }
+    }
+}
+
+impl Evaluation<TimestampedIoState> for GuardedComparison {
+    type Output = Option<bool>;
+    fn eval(&self, state: &TimestampedIoState) -> Result<Option<bool>> {
+        let c = &self.comparison;
+        if self.is_stale(&c.left, state) || self.is_stale(&c.right, state) {
+            return Ok(match self.on_stale {
+                OnStale::Unknown => None,
+                OnStale::False => Some(false),
+            });
+        }
+        c.eval(&state.io).map(Some)
+    }
+}
+
+/// The allowed deviation from the mean for a [HistoricalComparison]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deviation {
+    /// A multiple of the standard deviation
+    StdDevs(f64),
+    /// An absolute difference
+    Absolute(f64),
+}
+
+/// Compares a value against a statistic of its own recent history
+///
+/// The comparison is `true` if the current value deviates from the
+/// mean of the window by more than the configured [Deviation],
+/// e.g. for anomaly detection. The current value is added to the
+/// history after the comparison, outliers included.
+/// Use a [HistoricalCondition] to refer to the result within rules.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{Controller, Deviation, HistoricalComparison, HistoricalComparisonConfig};
+/// use msr::{Source, SystemState};
+/// use std::time::Duration;
+///
+/// let mut outlier = HistoricalComparison::new(HistoricalComparisonConfig {
+///     source: Source::In("pressure".into()),
+///     window: Duration::from_secs(60),
+///     deviation: Deviation::StdDevs(2.0),
+///     min_samples: 10,
+/// });
+/// let state = SystemState::default();
+/// let dt = Duration::from_millis(100);
+/// let is_anomaly = outlier.next((&state, &dt));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HistoricalComparison {
+    cfg: HistoricalComparisonConfig,
+    /// The samples of the window with their age
+    samples: VecDeque<(Duration, f64)>,
+}
+
+/// Configuration of a [HistoricalComparison]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalComparisonConfig {
+    /// The compared value
+    pub source: Source,
+    /// The duration of the history
+    pub window: Duration,
+    /// The allowed deviation from the mean
+    pub deviation: Deviation,
+    /// The number of samples required before
+    /// the comparison can become `true`
+    pub min_samples: usize,
+}
+
+impl HistoricalComparison {
+    /// Create a comparison with an empty history.
+    pub fn new(cfg: HistoricalComparisonConfig) -> Self {
+        HistoricalComparison {
+            cfg,
+            samples: VecDeque::new(),
+        }
+    }
+    /// The mean and the standard deviation of the history.
+    pub fn statistics(&self) -> Option<(f64, f64)> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let n = self.samples.len() as f64;
+        let mean = self.samples.iter().map(|(_, x)| x).sum::<f64>() / n;
+        let var = self
+            .samples
+            .iter()
+            .map(|(_, x)| (x - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        Some((mean, var.sqrt()))
+    }
+    /// Clear the history.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+impl Controller<(&SystemState, &Duration), Result<bool>> for HistoricalComparison {
+    fn next(&mut self, input: (&SystemState, &Duration)) -> Result<bool> {
+        let (state, duration) = input;
+        let x = get_val(&self.cfg.source, state)?.to_decimal(BitEncoding::Strict)?;
+        for (age, _) in &mut self.samples {
+            *age += *duration;
+        }
+        let window = self.cfg.window;
+        while self.samples.front().map(|(age, _)| *age > window) == Some(true) {
+            self.samples.pop_front();
+        }
+        let result = match self.statistics() {
+            Some((mean, std_dev)) if self.samples.len() >= self.cfg.min_samples => {
+                let limit = match self.cfg.deviation {
+                    Deviation::StdDevs(n) => n * std_dev,
+                    Deviation::Absolute(d) => d,
+                };
+                (x - mean).abs() > limit
+            }
+            _ => false,
+        };
+        self.samples.push_back((Duration::new(0, 0), x));
+        Ok(result)
+    }
+}
+
+/// Publishes the result of a [HistoricalComparison] as a memory point
+///
+/// The comparison is stepped once per cycle with the actual time step
+/// (e.g. before the rules of a [SyncRuntime] are evaluated) and its
+/// result is written to the memory point. This way rules can refer to it
+/// like to any other value (e.g. `mem.pressure.anomaly == true`)
+/// without skipping a step if an expression is evaluated lazily.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+/// use std::time::Duration;
+///
+/// let outlier = HistoricalComparison::new(HistoricalComparisonConfig {
+///     source: Source::In("pressure".into()),
+///     window: Duration::from_secs(60),
+///     deviation: Deviation::StdDevs(2.0),
+///     min_samples: 10,
+/// });
+/// let mut anomaly = HistoricalCondition::new(outlier, "pressure.anomaly");
+/// let alarm = Source::Mem("pressure.anomaly".into()).cmp_eq(true.into());
+///
+/// let mut state = SystemState::default();
+/// let dt = Duration::from_millis(100);
+/// TimeStepController::next(&mut anomaly, &mut state, &dt).unwrap();
+/// let is_anomaly = alarm.eval(&state).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct HistoricalCondition {
+    comparison: HistoricalComparison,
+    point: String,
+}
+
+impl HistoricalCondition {
+    /// Write the results of the comparison to the memory point `point`.
+    pub fn new<S: Into<String>>(comparison: HistoricalComparison, point: S) -> Self {
+        HistoricalCondition {
+            comparison,
+            point: point.into(),
+        }
+    }
+    /// The memory point of the result.
+    pub fn point(&self) -> &str {
+        &self.point
+    }
+    /// The wrapped comparison.
+    pub fn comparison(&self) -> &HistoricalComparison {
+        &self.comparison
+    }
+    /// Unwrap the comparison.
+    pub fn into_inner(self) -> HistoricalComparison {
+        self.comparison
+    }
+}
+
+/// A failed step removes the memory point,
+/// so conditions that refer to it fail as well.
+impl Controller<(&mut SystemState, &Duration), Result<bool>> for HistoricalCondition {
+    fn next(&mut self, input: (&mut SystemState, &Duration)) -> Result<bool> {
+        let (state, duration) = input;
+        match Controller::next(&mut self.comparison, (&*state, duration)) {
+            Ok(result) => {
+                state.io.mem.insert(self.point.clone(), Value::Bit(result));
+                Ok(result)
+            }
+            Err(err) => {
+                state.io.mem.remove(&self.point);
+                Err(err)
+            }
+        }
+    }
+}
+
+pub(crate) fn get_val<'a>(src: &'a Source, state: &'a SystemState) -> Result<Cow<'a, Value>> {
     use crate::ErrorKind::*;
     use crate::Source::*;
     match src {
-        In(ref id) => state.io.inputs.get(id).ok_or_else(|| {
+        In(_) | Out(_) | Mem(_) | Const(_) => get_io_val(src, &state.io).map(Cow::Borrowed),
+        Setpoint(ref id) => state.setpoints.get(id).map(Cow::Borrowed).ok_or_else(|| {
             Error::new(
                 NotFound,
-                format!("The state of input '{}' does not exist", id),
+                format!("The state of setpoint '{}' does not exist", id),
             )
         }),
-        Out(ref id) => state.io.outputs.get(id).ok_or_else(|| {
+        Timeout(ref id) => state.timeouts.get(id).map(Cow::Borrowed).ok_or_else(|| {
             Error::new(
                 NotFound,
-                format!("The state of output '{}' does not exist", id),
+                format!("The state of timeout '{}' does not exist", id),
             )
         }),
-        Mem(ref id) => state.io.mem.get(id).ok_or_else(|| {
+        ControllerVar(ref id, ref var) => state
+            .get_controller_var(id, var)
+            .map(Cow::Owned)
+            .ok_or_else(|| {
+                Error::new(
+                    NotFound,
+                    format!(
+                        "The state variable '{}' of controller '{}' does not exist",
+                        var, id
+                    ),
+                )
+            }),
+    }
+}
+
+pub(crate) fn get_io_val<'a>(src: &'a Source, state: &'a IoState) -> Result<&'a Value> {
+    use crate::ErrorKind::*;
+    use crate::Source::*;
+    match src {
+        In(ref id) => state.inputs.get(id).ok_or_else(|| {
             Error::new(
                 NotFound,
-                format!("The state of memory '{}' does not exist", id),
+                format!("The state of input '{}' does not exist", id),
             )
         }),
-        Setpoint(ref id) => state.setpoints.get(id).ok_or_else(|| {
+        Out(ref id) => state.outputs.get(id).ok_or_else(|| {
             Error::new(
                 NotFound,
-                format!("The state of setpoint '{}' does not exist", id),
+                format!("The state of output '{}' does not exist", id),
             )
         }),
-        Timeout(ref id) => state.timeouts.get(id).ok_or_else(|| {
+        Mem(ref id) => state.mem.get(id).ok_or_else(|| {
             Error::new(
                 NotFound,
-                format!("The state of timeout '{}' does not exist", id),
+                format!("The state of memory '{}' does not exist", id),
             )
         }),
+        Setpoint(_) | Timeout(_) | ControllerVar(_, _) => Err(Error::new(
+            InvalidInput,
+            "Setpoints, timeouts and controllers are not part of the I/O state",
+        )),
         Const(ref v) => Ok(v),
     }
 }
@@ -285,6 +650,42 @@ mod tests {
         assert!(cmp.eval(&mut state).is_ok());
     }
 
+    #[test]
+    fn evaluate_comparison_against_io_state() {
+        use std::{sync::Arc, thread};
+
+        let mut io = IoState::default();
+        io.inputs.insert("x".into(), 5.4.into());
+        io.outputs.insert("y".into(), 3.0.into());
+        let io = Arc::new(io);
+
+        let x_gt_y = In("x".into()).cmp_gt(Out("y".into()));
+        let x_lt_y = In("x".into()).cmp_lt(Out("y".into()));
+        let expr = BoolExpr::And(
+            Box::new(BoolExpr::Eval(x_gt_y.clone())),
+            Box::new(BoolExpr::Eval(x_lt_y.clone())),
+        );
+
+        let handles = (0..2)
+            .map(|_| {
+                let io = Arc::clone(&io);
+                let x_gt_y = x_gt_y.clone();
+                thread::spawn(move || x_gt_y.eval(&*io).unwrap())
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            assert!(h.join().unwrap());
+        }
+        assert!(!x_lt_y.eval(&*io).unwrap());
+        assert!(!expr.eval(&*io).unwrap());
+
+        assert!(In("z".into()).cmp_eq(In("x".into())).eval(&*io).is_err());
+        assert!(Setpoint("x".into())
+            .cmp_eq(In("x".into()))
+            .eval(&*io)
+            .is_err());
+    }
+
     #[test]
     fn evaluate_decimal_comparison() {
         let ok_tests: Vec<(Value, Comparator, Value, bool)> = vec![
@@ -459,6 +860,62 @@ mod tests {
         run_cmp_err_tests(err_tests);
     }
 
+    #[test]
+    fn evaluate_approximate_comparison() {
+        let ok_tests = vec![
+            (5.0.into(), Tolerance::Absolute(0.5), 5.5.into(), true),
+            (5.0.into(), Tolerance::Absolute(0.5), 4.4.into(), false),
+            (100.into(), Tolerance::Absolute(2.0), 98.into(), true),
+            (100.into(), Tolerance::Percent(1.0), 102.into(), false),
+        ];
+        let err_tests = vec![
+            (5.0.into(), Tolerance::Absolute(1.0), 5.into()),
+            (true.into(), Tolerance::Absolute(1.0), true.into()),
+            (
+                Duration::from_secs(1).into(),
+                Tolerance::Absolute(1.0),
+                Duration::from_secs(1).into(),
+            ),
+        ];
+        let mut state = SystemState::default();
+        let approx_eq = |tolerance| Comparison {
+            left: In("x".into()),
+            cmp: ApproxEqual,
+            right: In("y".into()),
+            encoding: BitEncoding::Strict,
+            tolerance,
+        };
+        for (a, tolerance, b, res) in ok_tests {
+            state.io.inputs.insert("x".into(), a);
+            state.io.inputs.insert("y".into(), b);
+            assert_eq!(approx_eq(tolerance).eval(&state).unwrap(), res);
+        }
+        for (a, tolerance, b) in err_tests {
+            state.io.inputs.insert("x".into(), a);
+            state.io.inputs.insert("y".into(), b);
+            assert!(approx_eq(tolerance).eval(&state).is_err());
+        }
+    }
+
+    #[test]
+    fn widen_percent_tolerance_with_the_reading() {
+        let cmp = In("x".into()).approx_eq_pct(In("y".into()), 2.0);
+        let mut io = IoState::default();
+        let mut check = |x: f64, y: f64| {
+            io.inputs.insert("x".into(), x.into());
+            io.inputs.insert("y".into(), y.into());
+            cmp.eval(&io).unwrap()
+        };
+        assert!(check(10.0, 10.15));
+        assert!(!check(10.0, 10.5));
+        assert!(check(100.0, 101.5));
+        assert!(check(1000.0, 985.0));
+        assert!(!check(1000.0, 1025.0));
+        assert!(check(-50.0, -50.9));
+        assert!(check(0.0, 0.0));
+        assert!(!check(0.0, 0.001));
+    }
+
     fn run_cmp_ok_tests(ok_tests: Vec<(Value, Comparator, Value, bool)>) {
         let mut state = SystemState::default();
         let left = In("x".into());
@@ -468,6 +925,8 @@ mod tests {
                 left: left.clone(),
                 cmp,
                 right: right.clone(),
+                encoding: BitEncoding::Strict,
+                tolerance: Tolerance::default(),
             };
             state.io.inputs.insert("x".into(), a);
             state.io.inputs.insert("y".into(), b);
@@ -475,6 +934,130 @@ mod tests {
         }
     }
 
+    #[test]
+    fn compare_quantities() {
+        run_cmp_ok_tests(vec![
+            (
+                Value::quantity(5.0, "bar"),
+                Comparator::Greater,
+                Value::quantity(4.0, "bar"),
+                true,
+            ),
+            (
+                Value::quantity(5.0, "bar"),
+                Comparator::Equal,
+                Value::quantity(5.0, "bar"),
+                true,
+            ),
+            (
+                Value::quantity(5.0, "bar"),
+                Comparator::Less,
+                Value::Decimal(6.0),
+                true,
+            ),
+            (
+                Value::Decimal(5.0),
+                Comparator::Less,
+                Value::quantity(4.0, "bar"),
+                false,
+            ),
+        ]);
+        run_cmp_err_tests(vec![
+            (
+                Value::quantity(5.0, "bar"),
+                Comparator::Equal,
+                Value::quantity(5.0, "psi"),
+            ),
+            (
+                Value::quantity(5.0, "bar"),
+                Comparator::Equal,
+                Value::Integer(5),
+            ),
+            (
+                Value::Integer(5),
+                Comparator::Equal,
+                Value::quantity(5.0, "bar"),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn guard_against_stale_inputs() {
+        let mut guarded = GuardedComparison {
+            comparison: In("x".into()).cmp_gt(5.0.into()),
+            max_age: Duration::from_secs(2),
+            on_stale: OnStale::Unknown,
+        };
+        let mut state = TimestampedIoState::default();
+        // never updated
+        assert_eq!(guarded.eval(&state).unwrap(), None);
+
+        state.update_input("x", Value::Decimal(6.0));
+        assert_eq!(guarded.eval(&state).unwrap(), Some(true));
+        state.tick(&Duration::from_secs(2));
+        assert_eq!(guarded.eval(&state).unwrap(), Some(true));
+        state.tick(&Duration::from_secs(1));
+        assert_eq!(guarded.eval(&state).unwrap(), None);
+        guarded.on_stale = OnStale::False;
+        assert_eq!(guarded.eval(&state).unwrap(), Some(false));
+
+        state.update_input("x", Value::Decimal(4.0));
+        assert_eq!(guarded.eval(&state).unwrap(), Some(false));
+        state.update_input("x", Value::Bit(true));
+        assert!(guarded.eval(&state).is_err());
+    }
+
+    #[test]
+    fn compare_against_history() {
+        let mut cmp = HistoricalComparison::new(HistoricalComparisonConfig {
+            source: In("x".into()),
+            window: Duration::from_secs(10),
+            deviation: Deviation::StdDevs(2.0),
+            min_samples: 5,
+        });
+        let mut state = SystemState::default();
+        let dt = Duration::from_secs(1);
+        let mut next = |x: f64| {
+            state.io.inputs.insert("x".into(), Value::Decimal(x));
+            Controller::next(&mut cmp, (&state, &dt)).unwrap()
+        };
+        // a stable signal with some noise
+        for x in [10.0, 10.2, 9.8, 10.1, 9.9, 10.0, 10.2, 9.8] {
+            assert!(!next(x));
+        }
+        // the outlier
+        assert!(next(15.0));
+        assert!(!next(10.1));
+        assert!(!next(9.9));
+    }
+
+    #[test]
+    fn require_enough_history() {
+        let mut cmp = HistoricalComparison::new(HistoricalComparisonConfig {
+            source: In("x".into()),
+            window: Duration::from_secs(2),
+            deviation: Deviation::Absolute(1.0),
+            min_samples: 2,
+        });
+        let mut state = SystemState::default();
+        let dt = Duration::from_secs(1);
+        assert!(Controller::next(&mut cmp, (&state, &dt)).is_err());
+        state.io.inputs.insert("x".into(), Value::Decimal(0.0));
+        assert!(!Controller::next(&mut cmp, (&state, &dt)).unwrap());
+        state.io.inputs.insert("x".into(), Value::Decimal(5.0));
+        // not enough samples yet
+        assert!(!Controller::next(&mut cmp, (&state, &dt)).unwrap());
+        assert_eq!(cmp.statistics(), Some((2.5, 2.5)));
+        state.io.inputs.insert("x".into(), Value::Decimal(0.0));
+        assert!(Controller::next(&mut cmp, (&state, &dt)).unwrap());
+        // the first sample leaves the window
+        state.io.inputs.insert("x".into(), Value::Decimal(2.5));
+        assert!(!Controller::next(&mut cmp, (&state, &dt)).unwrap());
+        assert_eq!(cmp.statistics().unwrap().0, 2.5);
+        cmp.reset();
+        assert_eq!(cmp.statistics(), None);
+    }
+
     fn run_cmp_err_tests(err_tests: Vec<(Value, Comparator, Value)>) {
         let mut state = SystemState::default();
         let left = In("x".into());
@@ -484,10 +1067,83 @@ mod tests {
                 left: left.clone(),
                 cmp,
                 right: right.clone(),
+                encoding: BitEncoding::Strict,
+                tolerance: Tolerance::default(),
             };
             state.io.inputs.insert("x".into(), a);
             state.io.inputs.insert("y".into(), b);
             assert!(cmp.eval(&mut state).is_err());
         }
     }
+
+    #[test]
+    fn compare_bits_with_numbers() {
+        let mut io = IoState::default();
+        io.inputs.insert("pump".into(), Value::Bit(true));
+        io.inputs.insert("level".into(), Value::Decimal(0.5));
+        let strict = In("pump".into()).cmp_gt(In("level".into()));
+        assert!(strict.eval(&io).is_err());
+
+        let permissive = strict.with_bit_encoding(BitEncoding::permissive());
+        assert!(permissive.eval(&io).unwrap());
+        let cmp = In("pump".into())
+            .cmp_eq(Const(1.into()))
+            .with_bit_encoding(BitEncoding::permissive());
+        assert!(cmp.eval(&io).unwrap());
+        let cmp = Const(0.0.into())
+            .cmp_lt(In("pump".into()))
+            .with_bit_encoding(BitEncoding::Numeric { on: -1.0, off: 0.0 });
+        assert!(!cmp.eval(&io).unwrap());
+        // bits are still compared with bits
+        let cmp = In("pump".into())
+            .cmp_ne(Const(false.into()))
+            .with_bit_encoding(BitEncoding::permissive());
+        assert!(cmp.eval(&io).unwrap());
+        // other values are not converted
+        io.inputs.insert("level".into(), Value::Text("high".into()));
+        let cmp = In("pump".into())
+            .cmp_eq(In("level".into()))
+            .with_bit_encoding(BitEncoding::permissive());
+        assert!(cmp.eval(&io).is_err());
+    }
+
+    #[test]
+    fn use_historical_comparisons_in_expressions() {
+        let outlier = HistoricalComparison::new(HistoricalComparisonConfig {
+            source: In("x".into()),
+            window: Duration::from_secs(10),
+            deviation: Deviation::Absolute(1.0),
+            min_samples: 2,
+        });
+        let mut anomaly = HistoricalCondition::new(outlier, "x.anomaly");
+        let expr = BoolExpr::And(
+            Box::new(BoolExpr::Eval(In("enabled".into()).cmp_eq(true.into()))),
+            Box::new(BoolExpr::Eval(Mem("x.anomaly".into()).cmp_eq(true.into()))),
+        );
+        let mut state = SystemState::default();
+        let dt = Duration::from_secs(1);
+        for (enabled, x, expected) in [
+            (true, 5.0, false),
+            // the history is kept up to date while the expression short-circuits
+            (false, 5.5, false),
+            (true, 5.2, false),
+            (true, 8.0, true),
+        ] {
+            state.io.inputs.insert("enabled".into(), enabled.into());
+            state.io.inputs.insert("x".into(), x.into());
+            TimeStepController::next(&mut anomaly, &mut state, &dt).unwrap();
+            assert_eq!(expr.eval(&state).unwrap(), expected);
+        }
+        assert_eq!(anomaly.comparison().statistics().unwrap().0, 23.7 / 4.0);
+
+        state.io.inputs.remove("x");
+        assert!(TimeStepController::next(&mut anomaly, &mut state, &dt).is_err());
+        assert!(expr.eval(&state).is_err());
+
+        // the history ages with the actual time steps
+        let mut anomaly = HistoricalCondition::new(anomaly.into_inner(), "x.anomaly");
+        state.io.inputs.insert("x".into(), 1.0.into());
+        TimeStepController::next(&mut anomaly, &mut state, &Duration::from_secs(11)).unwrap();
+        assert_eq!(anomaly.comparison().statistics(), Some((1.0, 0.0)));
+    }
 }
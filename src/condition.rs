@@ -0,0 +1,541 @@
+use super::*;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Error, ErrorKind, Result},
+    time::Duration,
+};
+
+/// A comparison with hysteresis.
+///
+/// Once the `set` comparison became true, the condition stays
+/// true until the value moved back past the `reset` threshold.
+/// The previous result has to be passed to each evaluation.
+/// Within the rule engine the rule state of the last cycle is used
+/// (see [SyncRuntime::add_rule_with_hysteresis]).
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// // active above 10.0 until the value drops below 8.0
+/// let hysteresis = Source::In("x".into())
+///     .cmp_gt(10.0.into())
+///     .with_hysteresis(8.0.into())
+///     .unwrap();
+///
+/// let mut state = SystemState::default();
+/// state.io.inputs.insert("x".into(), 9.0.into());
+/// assert!(hysteresis.next((true, &state)).unwrap());
+/// assert!(!hysteresis.next((false, &state)).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hysteresis {
+    pub(crate) set: Comparison,
+    pub(crate) reset: Comparison,
+}
+
+impl Hysteresis {
+    /// The comparison that activates the condition.
+    pub fn set(&self) -> &Comparison {
+        &self.set
+    }
+    /// The comparison that deactivates the condition.
+    pub fn reset(&self) -> &Comparison {
+        &self.reset
+    }
+}
+
+impl Comparison {
+    /// Wrap the comparison with a hysteresis.
+    ///
+    /// The condition becomes inactive as soon as the left hand side
+    /// moved back past the `reset` threshold.
+    /// Only ordering comparators are supported.
+    pub fn with_hysteresis(self, reset: Source) -> Result<Hysteresis> {
+        use crate::Comparator::*;
+        let reset_cmp = match self.cmp {
+            Greater | GreaterOrEqual => Less,
+            Less | LessOrEqual => Greater,
+            Equal | NotEqual | ApproxEqual => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "A '{:?}' comparison can't be used with a hysteresis",
+                        self.cmp
+                    ),
+                ));
+            }
+        };
+        let reset = Comparison {
+            left: self.left.clone(),
+            cmp: reset_cmp,
+            right: reset,
+            encoding: self.encoding,
+            tolerance: self.tolerance,
+        };
+        Ok(Hysteresis { set: self, reset })
+    }
+}
+
+impl<S> PureController<(bool, &S), Result<bool>> for Hysteresis
+where
+    Comparison: Evaluation<S, Output = bool>,
+{
+    fn next(&self, input: (bool, &S)) -> Result<bool> {
+        let (active, state) = input;
+        if active {
+            Ok(!self.reset.eval(state)?)
+        } else {
+            self.set.eval(state)
+        }
+    }
+}
+
+/// A condition that reports how long it has been continuously true.
+///
+/// The duration of each cycle accumulates as long as
+/// the condition is true. A false result resets it to zero.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+/// use std::time::Duration;
+///
+/// let mut condition = TimedCondition::new(Source::In("x".into()).cmp_gt(10.0.into()));
+///
+/// let mut state = SystemState::default();
+/// state.io.inputs.insert("x".into(), 11.0.into());
+/// let dt = Duration::from_millis(100);
+/// let elapsed = TimeStepController::next(&mut condition, &state, &dt).unwrap();
+/// if condition.held_for(Duration::from_secs(2)) {
+///     // act
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedCondition<T> {
+    pub(crate) condition: T,
+    elapsed: Duration,
+}
+
+impl<T> TimedCondition<T> {
+    /// Wrap a condition.
+    pub fn new(condition: T) -> Self {
+        TimedCondition {
+            condition,
+            elapsed: Duration::new(0, 0),
+        }
+    }
+    /// The wrapped condition.
+    pub fn condition(&self) -> &T {
+        &self.condition
+    }
+    /// The time the condition has been continuously true.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+    /// Check if the condition is true and has been for at least `min`.
+    pub fn held_for(&self, min: Duration) -> bool {
+        self.elapsed >= min && self.elapsed > Duration::new(0, 0)
+    }
+    /// Reset the accumulated time.
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::new(0, 0);
+    }
+}
+
+/// If the condition can't be evaluated, the accumulated time is kept.
+impl<S, T> Controller<(&S, &Duration), Result<Duration>> for TimedCondition<T>
+where
+    T: Evaluation<S, Output = bool>,
+{
+    fn next(&mut self, input: (&S, &Duration)) -> Result<Duration> {
+        let (state, delta_t) = input;
+        if self.condition.eval(state)? {
+            self.elapsed += *delta_t;
+        } else {
+            self.elapsed = Duration::new(0, 0);
+        }
+        Ok(self.elapsed)
+    }
+}
+
+/// Uses a [Controller] with a boolean output as a condition
+///
+/// The input of the controller is read from a [Source], so e.g. a
+/// bang-bang controller (Schmitt trigger) can be part of a [BoolExpr].
+/// Each evaluation is a step of the controller. Note that the operands of
+/// `And`/`Or` are evaluated lazily, so the controller might skip a step if
+/// its result isn't needed. To combine it with comparisons,
+/// box both as `dyn Evaluation`.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{bang_bang::*, *};
+///
+/// let trigger = BangBang::new(BangBangConfig {
+///     default_threshold: 80.0,
+///     hysteresis: 2.0,
+/// });
+/// let too_hot = ControllerCondition::new(trigger, Source::In("temp".into()));
+/// let alarm = BoolExpr::Eval(too_hot);
+///
+/// let mut io = IoState::default();
+/// io.inputs.insert("temp".into(), 83.0.into());
+/// assert!(alarm.eval(&io).unwrap());
+/// ```
+#[derive(Debug)]
+pub struct ControllerCondition<C> {
+    controller: RefCell<C>,
+    source: Source,
+    encoding: BitEncoding,
+}
+
+impl<C> ControllerCondition<C> {
+    /// Feed the value of `source` into the controller.
+    pub fn new(controller: C, source: Source) -> Self {
+        ControllerCondition {
+            controller: RefCell::new(controller),
+            source,
+            encoding: BitEncoding::default(),
+        }
+    }
+    /// Convert bits with the given encoding,
+    /// e.g. for a controller that detects edges.
+    pub fn with_encoding(mut self, encoding: BitEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+    /// The source of the controller input.
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+    /// Unwrap the controller.
+    pub fn into_inner(self) -> C {
+        self.controller.into_inner()
+    }
+}
+
+impl<C> ControllerCondition<C>
+where
+    C: Controller<f64, bool>,
+{
+    fn step(&self, value: &Value) -> Result<bool> {
+        let x = value.to_decimal(self.encoding)?;
+        Ok(self.controller.borrow_mut().next(x))
+    }
+}
+
+impl<C> Evaluation<IoState> for ControllerCondition<C>
+where
+    C: Controller<f64, bool>,
+{
+    type Output = bool;
+    fn eval(&self, state: &IoState) -> Result<bool> {
+        self.step(get_io_val(&self.source, state)?)
+    }
+}
+
+impl<C> Evaluation<SystemState> for ControllerCondition<C>
+where
+    C: Controller<f64, bool>,
+{
+    type Output = bool;
+    fn eval(&self, state: &SystemState) -> Result<bool> {
+        self.step(&*get_val(&self.source, state)?)
+    }
+}
+
+/// Evaluates rules and caches their results.
+///
+/// A rule is only re-evaluated if one of its referenced
+/// values is affected by the latest [IoStateDiff].
+/// Failed evaluations are not cached.
+/// If the rules themselves change, the cache has to be
+/// [invalidated](CachedEvaluator::invalidate).
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let rules = vec![Rule {
+///     id: "too-hot".into(),
+///     condition: Source::In("temp".into()).cmp_gt(80.0.into()).into(),
+///     actions: vec![],
+/// }];
+/// let mut evaluator = CachedEvaluator::default();
+/// let prev = IoState::default();
+/// let mut state = IoState::default();
+/// state.inputs.insert("temp".into(), 85.0.into());
+/// let updated = evaluator.update(&rules, &state, &state.diff(&prev));
+/// assert_eq!(updated.len(), 1);
+/// assert_eq!(evaluator.result("too-hot"), Some(true));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CachedEvaluator {
+    results: HashMap<String, bool>,
+}
+
+impl CachedEvaluator {
+    /// Re-evaluate all rules that might be affected by `diff`.
+    ///
+    /// Returns the results of the rules that were evaluated.
+    pub fn update(
+        &mut self,
+        rules: &[Rule],
+        state: &IoState,
+        diff: &IoStateDiff,
+    ) -> Vec<(String, Result<bool>)> {
+        let mut updated = vec![];
+        for r in rules {
+            let cached = self.results.contains_key(&r.id);
+            if cached && !r.condition.referenced_ids().iter().any(|s| diff.affects(s)) {
+                continue;
+            }
+            let res = r.condition.eval(state);
+            match res {
+                Ok(v) => {
+                    self.results.insert(r.id.clone(), v);
+                }
+                Err(_) => {
+                    self.results.remove(&r.id);
+                }
+            }
+            updated.push((r.id.clone(), res));
+        }
+        updated
+    }
+    /// The cached result of a rule.
+    pub fn result(&self, id: &str) -> Option<bool> {
+        self.results.get(id).copied()
+    }
+    /// Drop all cached results.
+    pub fn invalidate(&mut self) {
+        self.results.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::Source::*;
+
+    #[test]
+    fn latch_and_unlatch_at_thresholds() {
+        let hysteresis = In("x".into())
+            .cmp_gt(10.0.into())
+            .with_hysteresis(8.0.into())
+            .unwrap();
+        let mut state = SystemState::default();
+        let mut active = false;
+        for (x, expected) in vec![
+            (7.0, false),
+            (9.0, false),
+            (10.0, false),
+            (10.1, true),
+            (10.0, true),
+            (8.5, true),
+            (8.0, true),
+            (7.9, false),
+            (9.0, false),
+            (11.0, true),
+        ] {
+            state.io.inputs.insert("x".into(), x.into());
+            active = hysteresis.next((active, &state)).unwrap();
+            assert_eq!(active, expected);
+        }
+    }
+
+    #[test]
+    fn latch_with_falling_values() {
+        let hysteresis = In("x".into())
+            .cmp_le(2.0.into())
+            .with_hysteresis(5.0.into())
+            .unwrap();
+        let mut io = IoState::default();
+        io.inputs.insert("x".into(), 2.0.into());
+        assert!(hysteresis.next((false, &io)).unwrap());
+        io.inputs.insert("x".into(), 5.0.into());
+        assert!(hysteresis.next((true, &io)).unwrap());
+        io.inputs.insert("x".into(), 5.1.into());
+        assert!(!hysteresis.next((true, &io)).unwrap());
+    }
+
+    #[test]
+    fn reject_non_ordering_comparators() {
+        assert!(In("x".into())
+            .cmp_eq(10.0.into())
+            .with_hysteresis(8.0.into())
+            .is_err());
+        assert!(In("x".into())
+            .cmp_gt(10.0.into())
+            .with_hysteresis(8.0.into())
+            .is_ok());
+    }
+
+    #[test]
+    fn propagate_evaluation_errors() {
+        let hysteresis = In("x".into())
+            .cmp_gt(10.0.into())
+            .with_hysteresis(8.0.into())
+            .unwrap();
+        let state = SystemState::default();
+        assert!(hysteresis.next((false, &state)).is_err());
+        assert!(hysteresis.next((true, &state)).is_err());
+    }
+
+    #[test]
+    fn accumulate_duration_while_true() {
+        let mut condition = TimedCondition::new(In("x".into()).cmp_ge(1.0.into()));
+        let dt = Duration::from_millis(500);
+        let mut io = IoState::default();
+        let mut elapsed = Duration::new(0, 0);
+        for (x, expected) in [
+            (0.0, 0),
+            (1.0, 500),
+            (2.0, 1000),
+            (3.0, 1500),
+            (0.5, 0),
+            (1.5, 500),
+        ] {
+            io.inputs.insert("x".into(), x.into());
+            elapsed = TimeStepController::next(&mut condition, &io, &dt).unwrap();
+            assert_eq!(elapsed, Duration::from_millis(expected));
+            assert_eq!(condition.elapsed(), elapsed);
+        }
+        // a condition has to persist for a minimum time
+        assert!(elapsed < Duration::from_secs(1));
+        assert!(condition.held_for(dt));
+        assert!(!condition.held_for(2 * dt));
+        condition.reset();
+        assert!(!condition.held_for(Duration::new(0, 0)));
+    }
+
+    #[test]
+    fn timed_boolean_expression() {
+        let mut condition = TimedCondition::new(BoolExpr::And(
+            Box::new(BoolExpr::Eval(In("a".into()).cmp_eq(true.into()))),
+            Box::new(BoolExpr::Eval(In("b".into()).cmp_eq(true.into()))),
+        ));
+        let dt = Duration::from_secs(1);
+        let mut io = IoState::default();
+        let mut next = |io: &IoState| TimeStepController::next(&mut condition, io, &dt);
+        assert!(next(&io).is_err());
+        io.inputs.insert("a".into(), true.into());
+        io.inputs.insert("b".into(), true.into());
+        assert_eq!(next(&io).unwrap(), dt);
+        assert_eq!(next(&io).unwrap(), 2 * dt);
+        io.inputs.insert("b".into(), false.into());
+        assert_eq!(next(&io).unwrap(), Duration::new(0, 0));
+    }
+
+    #[test]
+    fn use_controllers_as_conditions() {
+        use crate::bang_bang::*;
+
+        let trigger = BangBang::new(BangBangConfig {
+            default_threshold: 10.0,
+            hysteresis: 1.0,
+        });
+        let enabled: Box<dyn Evaluation<IoState, Output = bool>> =
+            Box::new(In("enabled".into()).cmp_eq(true.into()));
+        let high: Box<dyn Evaluation<IoState, Output = bool>> =
+            Box::new(ControllerCondition::new(trigger, In("x".into())));
+        let expr = BoolExpr::And(
+            Box::new(BoolExpr::Eval(enabled)),
+            Box::new(BoolExpr::Eval(high)),
+        );
+        let mut io = IoState::default();
+        io.inputs.insert("enabled".into(), true.into());
+        for (x, expected) in [(9.0, false), (11.5, true), (9.5, true), (8.9, false)] {
+            io.inputs.insert("x".into(), x.into());
+            assert_eq!(expr.eval(&io).unwrap(), expected);
+        }
+        io.inputs.insert("enabled".into(), false.into());
+        io.inputs.insert("x".into(), 12.0.into());
+        assert!(!expr.eval(&io).unwrap());
+    }
+
+    #[test]
+    fn read_controller_conditions_from_system_state() {
+        struct RisingEdge(bool);
+
+        impl Controller<f64, bool> for RisingEdge {
+            fn next(&mut self, x: f64) -> bool {
+                let on = x > 0.0;
+                let edge = on && !self.0;
+                self.0 = on;
+                edge
+            }
+        }
+
+        let edge = ControllerCondition::new(RisingEdge(false), In("button".into()))
+            .with_encoding(BitEncoding::permissive());
+        let mut state = SystemState::default();
+        assert!(edge.eval(&state).is_err());
+        for (pressed, expected) in [(false, false), (true, true), (true, false), (false, false)] {
+            state.io.inputs.insert("button".into(), pressed.into());
+            assert_eq!(edge.eval(&state).unwrap(), expected);
+        }
+        assert!(!edge.into_inner().0);
+    }
+
+    #[test]
+    fn skip_rules_without_changed_ids() {
+        let rules = vec![
+            Rule {
+                id: "a".into(),
+                condition: In("x".into()).cmp_gt(5.0.into()).into(),
+                actions: vec![],
+            },
+            Rule {
+                id: "b".into(),
+                condition: BoolExpr::And(
+                    Box::new(In("y".into()).cmp_eq(true.into()).into()),
+                    Box::new(Mem("z".into()).cmp_lt(1.0.into()).into()),
+                ),
+                actions: vec![],
+            },
+        ];
+        let mut evaluator = CachedEvaluator::default();
+        let mut prev = IoState::default();
+        prev.inputs.insert("x".into(), 1.0.into());
+        prev.inputs.insert("y".into(), true.into());
+        prev.mem.insert("z".into(), 0.0.into());
+        let updated = evaluator.update(&rules, &prev, &IoStateDiff::default());
+        assert_eq!(updated.len(), 2);
+        assert_eq!(evaluator.result("a"), Some(false));
+        assert_eq!(evaluator.result("b"), Some(true));
+
+        let mut state = prev.clone();
+        state.inputs.insert("x".into(), 7.0.into());
+        let diff = state.diff(&prev);
+        assert!(diff.inputs.contains("x"));
+        assert_eq!(diff.inputs.len(), 1);
+        let updated = evaluator.update(&rules, &state, &diff);
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].0, "a");
+        assert!(*updated[0].1.as_ref().unwrap());
+        assert_eq!(evaluator.result("b"), Some(true));
+
+        let prev = state.clone();
+        state.mem.remove("z");
+        let diff = state.diff(&prev);
+        assert!(diff.mem.contains("z"));
+        let updated = evaluator.update(&rules, &state, &diff);
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].0, "b");
+        assert!(updated[0].1.is_err());
+        assert_eq!(evaluator.result("b"), None);
+
+        assert!(
+            evaluator
+                .update(&rules, &state, &IoStateDiff::default())
+                .len()
+                == 1
+        );
+        evaluator.invalidate();
+        assert_eq!(evaluator.result("a"), None);
+    }
+}
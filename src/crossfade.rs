@@ -0,0 +1,319 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{TimeStepController, crossfade::*};
+//! use std::time::Duration;
+//!
+//! let mut fade = CrossFade::new(CrossFadeConfig {
+//!     duration: Duration::from_secs(4),
+//!     ..Default::default()
+//! });
+//! let dt = Duration::from_secs(1);
+//!
+//! // e.g. switching from manual (30.0) to auto (70.0)
+//! fade.start(30.0);
+//! assert_eq!(fade.next(70.0, &dt), 40.0);
+//! assert_eq!(fade.next(70.0, &dt), 50.0);
+//! ```
+
+use super::{BitEncoding, Controller, PureController, SyncIoSystem};
+use crate::util::{cap_delta_t, DurationInSeconds};
+use std::{
+    io::{Error, ErrorKind, Result},
+    time::Duration,
+};
+
+/// Cross-fades an output from a previous value to a new one.
+#[derive(Debug, Clone)]
+pub struct CrossFade {
+    cfg: CrossFadeConfig,
+    /// Current cross-fade state
+    pub state: CrossFadeState,
+}
+
+/// Cross-fade configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CrossFadeConfig {
+    /// The duration of a transition
+    pub duration: Duration,
+    /// Maximum plausible time step (see [crate::util::cap_delta_t])
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_delta_t: Option<Duration>,
+}
+
+/// Internal cross-fade state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CrossFadeState {
+    /// The output value the transition started from
+    pub from: Option<f64>,
+    /// The elapsed time of the transition
+    pub elapsed: Duration,
+}
+
+impl Default for CrossFadeConfig {
+    fn default() -> Self {
+        CrossFadeConfig {
+            duration: Duration::from_secs(1),
+            max_delta_t: None,
+        }
+    }
+}
+
+impl CrossFadeState {
+    /// Start a transition from the given output value.
+    pub fn start(&mut self, from: f64) {
+        self.from = Some(from);
+        self.elapsed = Duration::new(0, 0);
+    }
+}
+
+impl CrossFade {
+    /// Create a new instance.
+    pub fn new(cfg: CrossFadeConfig) -> Self {
+        CrossFade {
+            cfg,
+            state: CrossFadeState::default(),
+        }
+    }
+    /// Start a transition from the given output value,
+    /// e.g. on a switch between manual and auto mode.
+    pub fn start(&mut self, from: f64) {
+        self.state.start(from);
+    }
+    /// Check if a transition is in progress.
+    pub fn is_active(&self) -> bool {
+        self.state.from.is_some()
+    }
+}
+
+impl Controller<(f64, &Duration), f64> for CrossFade {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (target, duration) = input;
+        let (state, output) = self.cfg.next((self.state, target, duration));
+        self.state = state;
+        output
+    }
+}
+
+impl PureController<(CrossFadeState, f64, &Duration), (CrossFadeState, f64)> for CrossFadeConfig {
+    fn next(&self, input: (CrossFadeState, f64, &Duration)) -> (CrossFadeState, f64) {
+        let (mut state, target, duration) = input;
+        match state.from {
+            Some(from) => {
+                state.elapsed += cap_delta_t(duration, self.max_delta_t);
+                if state.elapsed >= self.duration {
+                    state.from = None;
+                    (state, target)
+                } else {
+                    let ratio = f64::from(DurationInSeconds::from(state.elapsed))
+                        / f64::from(DurationInSeconds::from(self.duration));
+                    (state, from + (target - from) * ratio)
+                }
+            }
+            None => (state, target),
+        }
+    }
+}
+
+/// Ramps the output of a controller on enable.
+///
+/// When the controller is enabled, the current actuator value is read
+/// and the output cross-fades from it to the computed command
+/// to prevent a jump when re-enabling a loop.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, IoState, crossfade::*, pid::*};
+/// use std::time::Duration;
+///
+/// let pid = Pid::new(PidConfig::default());
+/// let mut ctl = EnableRamp::new(pid, "valve", Duration::from_secs(10));
+///
+/// let mut io = IoState::default();
+/// ctl.enable(&mut io).unwrap();
+///
+/// let dt = Duration::from_millis(100);
+/// let sensor_value = 5.3;
+/// if let Some(output) = ctl.next(sensor_value, &dt) {
+///     // write the output
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnableRamp<C> {
+    /// The wrapped controller
+    pub controller: C,
+    output: String,
+    fade: CrossFade,
+    enabled: bool,
+}
+
+impl<C> EnableRamp<C> {
+    /// Wrap a controller that writes to the given output.
+    pub fn new<S: Into<String>>(controller: C, output: S, duration: Duration) -> Self {
+        EnableRamp {
+            controller,
+            output: output.into(),
+            fade: CrossFade::new(CrossFadeConfig {
+                duration,
+                max_delta_t: None,
+            }),
+            enabled: false,
+        }
+    }
+    /// Enable the controller.
+    ///
+    /// If the output has a current (numeric) value
+    /// the output ramps from there.
+    pub fn enable<IO: SyncIoSystem>(&mut self, io: &mut IO) -> Result<()> {
+        if self.enabled {
+            return Ok(());
+        }
+        match io.read_output(&self.output)? {
+            Some(v) => {
+                let v = v.to_decimal(BitEncoding::Strict).map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Can't ramp from a non-numeric value {:?}", v),
+                    )
+                })?;
+                self.fade.start(v);
+            }
+            None => self.fade.state = CrossFadeState::default(),
+        }
+        self.enabled = true;
+        Ok(())
+    }
+    /// Disable the controller.
+    pub fn disable(&mut self) {
+        self.enabled = false;
+    }
+    /// Check if the controller is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Returns `None` while the controller is disabled.
+impl<C> Controller<(f64, &Duration), Option<f64>> for EnableRamp<C>
+where
+    C: for<'a> Controller<(f64, &'a Duration), f64>,
+{
+    fn next(&mut self, input: (f64, &Duration)) -> Option<f64> {
+        if !self.enabled {
+            return None;
+        }
+        let (actual, duration) = input;
+        let command = self.controller.next((actual, duration));
+        Some(self.fade.next((command, duration)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn pass_through_without_transition() {
+        let mut fade = CrossFade::new(CrossFadeConfig::default());
+        let dt = Duration::from_millis(100);
+        assert!(!fade.is_active());
+        assert_eq!(fade.next((5.0, &dt)), 5.0);
+        assert_eq!(fade.next((-3.0, &dt)), -3.0);
+    }
+
+    #[test]
+    fn blend_over_the_configured_time() {
+        let mut fade = CrossFade::new(CrossFadeConfig {
+            duration: Duration::from_secs(4),
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        fade.start(20.0);
+        assert!(fade.is_active());
+        assert_eq!(fade.next((60.0, &dt)), 30.0);
+        assert_eq!(fade.next((60.0, &dt)), 40.0);
+        // the target may change during the transition
+        assert_eq!(fade.next((40.0, &dt)), 35.0);
+        assert!(fade.is_active());
+        assert_eq!(fade.next((40.0, &dt)), 40.0);
+        assert!(!fade.is_active());
+        assert_eq!(fade.next((41.0, &dt)), 41.0);
+    }
+
+    #[test]
+    fn restart_transition() {
+        let mut fade = CrossFade::new(CrossFadeConfig {
+            duration: Duration::from_secs(2),
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        fade.start(0.0);
+        assert_eq!(fade.next((10.0, &dt)), 5.0);
+        fade.start(5.0);
+        assert_eq!(fade.next((-5.0, &dt)), 0.0);
+        assert_eq!(fade.next((-5.0, &dt)), -5.0);
+    }
+
+    #[test]
+    fn ramp_from_the_current_output_on_enable() {
+        use crate::{pid::*, IoState, Value};
+
+        let mut pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            ..Default::default()
+        });
+        pid.set_target(100.0);
+        let mut ctl = EnableRamp::new(pid, "valve", Duration::from_secs(4));
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next((0.0, &dt)), None);
+
+        let mut io = IoState::default();
+        io.outputs.insert("valve".into(), Value::Decimal(20.0));
+        ctl.enable(&mut io).unwrap();
+        assert!(ctl.is_enabled());
+        assert_eq!(ctl.next((0.0, &dt)), Some(40.0));
+        assert_eq!(ctl.next((0.0, &dt)), Some(60.0));
+        assert_eq!(ctl.next((0.0, &dt)), Some(80.0));
+        assert_eq!(ctl.next((0.0, &dt)), Some(100.0));
+        assert_eq!(ctl.next((10.0, &dt)), Some(90.0));
+
+        // enabling an enabled controller doesn't restart the ramp
+        ctl.enable(&mut io).unwrap();
+        assert_eq!(ctl.next((10.0, &dt)), Some(90.0));
+
+        ctl.disable();
+        assert_eq!(ctl.next((10.0, &dt)), None);
+
+        // without a current value there is no ramp
+        io.outputs.clear();
+        ctl.enable(&mut io).unwrap();
+        assert_eq!(ctl.next((10.0, &dt)), Some(90.0));
+
+        ctl.disable();
+        io.outputs.insert("valve".into(), Value::Bit(true));
+        assert!(ctl.enable(&mut io).is_err());
+        assert!(!ctl.is_enabled());
+    }
+
+    #[test]
+    fn ramp_from_numeric_outputs_on_enable() {
+        use crate::{pid::*, IoState, Value};
+
+        let pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            default_target: 100.0,
+            ..Default::default()
+        });
+        let mut ctl = EnableRamp::new(pid, "valve", Duration::from_secs(2));
+        let dt = Duration::from_secs(1);
+        let mut io = IoState::default();
+        for v in [Value::Quantity(20.0, "%".into()), Value::Integer(20)] {
+            io.outputs.insert("valve".into(), v);
+            ctl.enable(&mut io).unwrap();
+            assert_eq!(ctl.next((0.0, &dt)), Some(60.0));
+            assert_eq!(ctl.next((0.0, &dt)), Some(100.0));
+            ctl.disable();
+        }
+    }
+}
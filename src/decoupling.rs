@@ -0,0 +1,145 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{Controller, decoupling::*};
+//!
+//! // temperature and humidity loops of an air handling unit
+//! let mut decoupler = Decoupler::new(vec![
+//!     vec![1.0, -0.3], // heater
+//!     vec![-0.2, 1.0], // humidifier
+//! ])
+//! .unwrap();
+//!
+//! let (temp_output, humidity_output) = (40.0, 25.0);
+//! let actuators = decoupler.next(vec![temp_output, humidity_output]);
+//! ```
+
+use super::{Controller, PureController};
+use std::io::{Error, ErrorKind, Result};
+
+/// A static decoupling block for interacting loops
+///
+/// The raw controller outputs are multiplied with a decoupling
+/// matrix to compensate the cross-coupling of the process.
+/// Each row of the matrix calculates the value of one actuator
+/// from the outputs of all controllers.
+#[derive(Debug, Clone)]
+pub struct Decoupler {
+    cfg: DecouplerConfig,
+}
+
+/// Decoupling configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecouplerConfig {
+    /// The decoupling matrix (one row per actuator)
+    pub matrix: Vec<Vec<f64>>,
+}
+
+impl DecouplerConfig {
+    /// The number of controller outputs
+    pub fn inputs(&self) -> usize {
+        self.matrix.first().map(Vec::len).unwrap_or(0)
+    }
+    /// The number of actuators
+    pub fn outputs(&self) -> usize {
+        self.matrix.len()
+    }
+    fn validate(&self) -> Result<()> {
+        let inputs = self.inputs();
+        if inputs == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "The decoupling matrix is empty",
+            ));
+        }
+        if let Some(i) = self.matrix.iter().position(|row| row.len() != inputs) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Row {} of the decoupling matrix has {} instead of {} columns",
+                    i,
+                    self.matrix[i].len(),
+                    inputs
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Decoupler {
+    /// Create a new decoupling block.
+    ///
+    /// All rows of the matrix must have the same (non-zero) length.
+    pub fn new(matrix: Vec<Vec<f64>>) -> Result<Self> {
+        let cfg = DecouplerConfig { matrix };
+        cfg.validate()?;
+        Ok(Decoupler { cfg })
+    }
+    /// Calculate the actuator values and check the number of controller outputs.
+    ///
+    /// Fails if the number of outputs doesn't match the columns of the matrix.
+    pub fn try_next(&mut self, outputs: &[f64]) -> Result<Vec<f64>> {
+        if outputs.len() != self.cfg.inputs() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Expected {} instead of {} controller outputs",
+                    self.cfg.inputs(),
+                    outputs.len()
+                ),
+            ));
+        }
+        Ok(self.cfg.next(outputs))
+    }
+}
+
+/// Missing controller outputs are treated as `0.0`,
+/// additional ones are ignored
+/// (see [Decoupler::try_next] for a checked version).
+impl Controller<Vec<f64>, Vec<f64>> for Decoupler {
+    fn next(&mut self, outputs: Vec<f64>) -> Vec<f64> {
+        self.cfg.next(outputs.as_slice())
+    }
+}
+
+impl PureController<&[f64], Vec<f64>> for DecouplerConfig {
+    fn next(&self, outputs: &[f64]) -> Vec<f64> {
+        self.matrix
+            .iter()
+            .map(|row| row.iter().zip(outputs).map(|(k, x)| k * x).sum())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn decouple_two_loops() {
+        let mut decoupler = Decoupler::new(vec![vec![1.0, -0.5], vec![-0.25, 1.0]]).unwrap();
+        assert_eq!(decoupler.next(vec![40.0, 20.0]), vec![30.0, 10.0]);
+        assert_eq!(decoupler.next(vec![0.0, 20.0]), vec![-10.0, 20.0]);
+        assert_eq!(decoupler.next(vec![40.0]), vec![40.0, -10.0]);
+    }
+
+    #[test]
+    fn check_number_of_controller_outputs() {
+        let mut decoupler = Decoupler::new(vec![vec![1.0, -0.5], vec![-0.25, 1.0]]).unwrap();
+        assert_eq!(decoupler.try_next(&[40.0, 20.0]).unwrap(), vec![30.0, 10.0]);
+        assert!(decoupler.try_next(&[40.0]).is_err());
+        assert!(decoupler.try_next(&[40.0, 20.0, 1.0]).is_err());
+    }
+
+    #[test]
+    fn reject_invalid_matrices() {
+        assert!(Decoupler::new(vec![]).is_err());
+        assert!(Decoupler::new(vec![vec![]]).is_err());
+        assert!(Decoupler::new(vec![vec![1.0, 0.0], vec![1.0]]).is_err());
+        let d = Decoupler::new(vec![vec![1.0, 0.0, 0.5]]).unwrap();
+        assert_eq!(d.cfg.inputs(), 3);
+        assert_eq!(d.cfg.outputs(), 1);
+    }
+}
@@ -1,5 +1,5 @@
 use super::*;
-use std::time::Duration;
+use std::{fmt, time::Duration};
 
 /// I/O (sensor or actuator)
 ///
@@ -55,6 +55,73 @@ pub struct ValueBounds {
     pub high: f64,
 }
 
+impl ValueBounds {
+    /// Convert a value of this range into per-unit form,
+    /// i.e. `low` is mapped to `0.0` and `high` to `1.0`.
+    pub fn to_per_unit(&self, x: f64) -> f64 {
+        util::map_value(x, self.low, self.high, 0.0, 1.0)
+    }
+    /// Convert a per-unit value into this range.
+    pub fn from_per_unit(&self, x: f64) -> f64 {
+        util::map_value(x, 0.0, 1.0, self.low, self.high)
+    }
+}
+
+/// A controller that operates in per-unit (normalized) form
+///
+/// The actual value is normalized by the input range before
+/// it is passed to the controller and the output of the controller
+/// is scaled to the output range. So the controller (including its
+/// target and limits) only deals with values between `0.0` and `1.0`,
+/// which simplifies tuning across differently-ranged loops.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{PerUnit, TimeStepController, ValueBounds, pid::*};
+/// use std::time::Duration;
+///
+/// let temperature = ValueBounds { low: 0.0, high: 200.0 };
+/// let heater = ValueBounds { low: 0.0, high: 4500.0 };
+/// let mut ctl = PerUnit::new(Pid::new(PidConfig::default()), temperature, heater);
+/// let target = ctl.input.to_per_unit(120.0);
+/// ctl.controller.set_target(target);
+///
+/// let dt = Duration::from_secs(1);
+/// let power = ctl.next(95.0, &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PerUnit<C> {
+    /// The wrapped controller
+    pub controller: C,
+    /// The range of the actual value
+    pub input: ValueBounds,
+    /// The range of the output value
+    pub output: ValueBounds,
+}
+
+impl<C> PerUnit<C> {
+    /// Wrap a controller.
+    pub fn new(controller: C, input: ValueBounds, output: ValueBounds) -> Self {
+        PerUnit {
+            controller,
+            input,
+            output,
+        }
+    }
+}
+
+impl<C> Controller<(f64, &Duration), f64> for PerUnit<C>
+where
+    C: for<'a> Controller<(f64, &'a Duration), f64>,
+{
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (actual, duration) = input;
+        let actual = self.input.to_per_unit(actual);
+        let output = self.controller.next((actual, duration));
+        self.output.from_per_unit(output)
+    }
+}
+
 /// Cropping value
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -80,6 +147,135 @@ impl Cropping {
     }
 }
 
+/// Reports whether a value was limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LimitStatus {
+    /// The value is within its range
+    #[default]
+    WithinRange,
+    /// The value was limited to the lower threshold
+    LimitedLow,
+    /// The value was limited to the upper threshold
+    LimitedHigh,
+}
+
+impl fmt::Display for LimitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LimitStatus::WithinRange => "within-range",
+            LimitStatus::LimitedLow => "limited-low",
+            LimitStatus::LimitedHigh => "limited-high",
+        })
+    }
+}
+
+impl Cropping {
+    /// Crop a value and report whether it was limited.
+    pub fn crop_with_status(&self, x: f64) -> (f64, LimitStatus) {
+        match (self.low, self.high) {
+            (Some(low), _) if x < low => (low, LimitStatus::LimitedLow),
+            (_, Some(high)) if x > high => (high, LimitStatus::LimitedHigh),
+            _ => (x, LimitStatus::WithinRange),
+        }
+    }
+}
+
+/// Limits the output of a controller to the range of the actuator
+/// and reports whether the command was limited.
+///
+/// This lets downstream logic (or an HMI) know that the controller
+/// asks for more than the actuator can deliver.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{Cropping, LimitedOutput, TimeStepController, pid::*};
+/// use std::time::Duration;
+///
+/// let pid = Pid::new(PidConfig::default());
+/// let range = Cropping { low: Some(0.0), high: Some(100.0) };
+/// let mut ctl = LimitedOutput::new(pid, range);
+///
+/// let dt = Duration::from_millis(100);
+/// let (value, status) = ctl.next(5.3, &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LimitedOutput<C> {
+    /// The wrapped controller
+    pub controller: C,
+    /// The range of the actuator
+    pub range: Cropping,
+}
+
+impl<C> LimitedOutput<C> {
+    /// Wrap a controller.
+    pub fn new(controller: C, range: Cropping) -> Self {
+        LimitedOutput { controller, range }
+    }
+}
+
+impl<C> Controller<(f64, &Duration), (Value, LimitStatus)> for LimitedOutput<C>
+where
+    C: for<'a> Controller<(f64, &'a Duration), f64>,
+{
+    fn next(&mut self, input: (f64, &Duration)) -> (Value, LimitStatus) {
+        let command = self.controller.next(input);
+        let (output, status) = self.range.crop_with_status(command);
+        (Value::Decimal(output), status)
+    }
+}
+
+/// Signal conditioning in a single block
+///
+/// The operations are applied in the following order:
+///
+/// 1. deadband: raw values within `±deadband` are mapped to `0.0`,
+///    e.g. to suppress the noise of a flow transmitter at rest
+/// 2. scaling: the result is mapped by `mapping`
+/// 3. clamping: the scaled value is cropped by `cropping`
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let conditioner = Conditioner {
+///     deadband: Some(0.05),
+///     mapping: Some(ValueMapping {
+///         from: ValueBounds { low: 0.0, high: 10.0 },
+///         to: ValueBounds { low: 0.0, high: 100.0 },
+///     }),
+///     cropping: Some(Cropping { low: Some(0.0), high: Some(100.0) }),
+/// };
+/// assert_eq!(conditioner.next(0.03), 0.0);
+/// assert_eq!(conditioner.next(11.0), 100.0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Conditioner {
+    /// Input deadband around zero
+    pub deadband: Option<f64>,
+    /// Linear scaling
+    pub mapping: Option<ValueMapping>,
+    /// Output clamping
+    pub cropping: Option<Cropping>,
+}
+
+impl PureController<f64, f64> for Conditioner {
+    fn next(&self, raw: f64) -> f64 {
+        let mut x = match self.deadband {
+            Some(deadband) if raw.abs() <= deadband => 0.0,
+            _ => raw,
+        };
+        if let Some(ref mapping) = self.mapping {
+            x = mapping.map(x);
+        }
+        if let Some(ref cropping) = self.cropping {
+            x = cropping.crop(x);
+        }
+        x
+    }
+}
+
 /// Calibration coefficients
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -119,6 +315,18 @@ pub struct Loop {
     pub controller: ControllerConfig,
 }
 
+/// A wire connects the output of a loop with the input of another loop.
+///
+/// The runtime copies the value of the output to the input
+/// within each cycle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wire {
+    /// The ID of the output
+    pub from: String,
+    /// The ID of the input
+    pub to: String,
+}
+
 /// A periodic interval with a fixed duration
 #[derive(Debug, Clone)]
 pub struct Interval {
@@ -139,6 +347,19 @@ pub struct Rule {
     pub actions: Vec<String>,
 }
 
+/// A named group of rules with a master enable.
+///
+/// It allows to toggle a whole subsystem of logic at once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleSet {
+    /// The unique ID of the rule set
+    pub id: String,
+    /// Master enable of all rules of the set
+    pub enabled: bool,
+    /// The rules of the set
+    pub rules: Vec<Rule>,
+}
+
 /// An action can modify outputs and setpoints.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Action {
@@ -200,4 +421,91 @@ mod tests {
         assert_eq!(cropping.crop(3.0), 3.0);
         assert_eq!(cropping.crop(3.1), 3.0);
     }
+
+    #[test]
+    fn condition_raw_signal() {
+        let conditioner = Conditioner {
+            deadband: Some(0.5),
+            mapping: Some(ValueMapping {
+                from: ValueBounds {
+                    low: 0.0,
+                    high: 10.0,
+                },
+                to: ValueBounds {
+                    low: 20.0,
+                    high: 120.0,
+                },
+            }),
+            cropping: Some(Cropping {
+                low: Some(25.0),
+                high: Some(100.0),
+            }),
+        };
+        // deadbanded before scaling, so 0.0 maps to 20.0 and gets clamped
+        assert_eq!(conditioner.next(0.4), 25.0);
+        assert_eq!(conditioner.next(-0.5), 25.0);
+        assert_eq!(conditioner.next(0.6), 26.0);
+        assert_eq!(conditioner.next(5.0), 70.0);
+        assert_eq!(conditioner.next(9.0), 100.0);
+
+        assert_eq!(Conditioner::default().next(0.4), 0.4);
+    }
+
+    #[test]
+    fn control_in_per_unit() {
+        use crate::pid::*;
+
+        let input = ValueBounds {
+            low: 0.0,
+            high: 200.0,
+        };
+        let output = ValueBounds {
+            low: 4.0,
+            high: 20.0,
+        };
+        assert_eq!(input.to_per_unit(50.0), 0.25);
+        assert_eq!(output.from_per_unit(0.25), 8.0);
+
+        let pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            min: Some(0.0),
+            max: Some(1.0),
+            ..Default::default()
+        });
+        let mut ctl = PerUnit::new(pid, input, output);
+        ctl.controller.set_target(0.5);
+        let dt = Duration::from_secs(1);
+        let mut next = |actual| TimeStepController::next(&mut ctl, actual, &dt);
+        // 0.5 - 0.25 = 0.25 p.u.
+        assert_eq!(next(50.0), 8.0);
+        assert_eq!(next(100.0), 4.0);
+        // limited to 1.0 p.u.
+        assert_eq!(next(-200.0), 20.0);
+    }
+
+    #[test]
+    fn report_limited_outputs() {
+        use crate::pid::*;
+
+        let mut pid = Pid::new(PidConfig {
+            k_p: 10.0,
+            ..Default::default()
+        });
+        pid.set_target(5.0);
+        let range = Cropping {
+            low: Some(0.0),
+            high: Some(100.0),
+        };
+        let mut ctl = LimitedOutput::new(pid, range);
+        let dt = Duration::from_secs(1);
+        let mut next = |actual| TimeStepController::next(&mut ctl, actual, &dt);
+        assert_eq!(next(1.0), (Value::Decimal(40.0), LimitStatus::WithinRange));
+        let (value, status) = next(-20.0);
+        assert_eq!(value, Value::Decimal(100.0));
+        assert_eq!(status, LimitStatus::LimitedHigh);
+        assert_eq!(status.to_string(), "limited-high");
+        assert_eq!(next(20.0), (Value::Decimal(0.0), LimitStatus::LimitedLow));
+        // a value at the limit is not limited
+        assert_eq!(next(5.0), (Value::Decimal(0.0), LimitStatus::WithinRange));
+    }
 }
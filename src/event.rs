@@ -0,0 +1,236 @@
+use super::*;
+use std::fmt;
+
+/// An event of the runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A rule that is registered as alarm became active.
+    AlarmRaised(String),
+    /// A rule that is registered as alarm became inactive.
+    AlarmCleared(String),
+    /// A rule became active.
+    RuleFired(String),
+    /// An output changed its value.
+    OutputWritten(OutputWrite),
+    /// An input changed its value significantly.
+    InputChanged {
+        /// The ID of the input
+        id: String,
+        /// The previously reported value
+        old: Option<Value>,
+        /// The new value
+        new: Value,
+    },
+    /// A state machine changed its state.
+    ModeChanged {
+        /// The ID of the state machine
+        machine: String,
+        /// The previous state
+        from: Option<String>,
+        /// The new state
+        to: String,
+    },
+}
+
+/// A subscriber of runtime [Event]s (e.g. a logger).
+pub trait EventSink {
+    /// Handle a single event.
+    fn handle(&mut self, event: &Event);
+}
+
+/// Distributes [Event]s to the registered sinks.
+///
+/// The events are derived from the changes between
+/// two consecutive system states.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// struct Logger;
+///
+/// impl EventSink for Logger {
+///     fn handle(&mut self, event: &Event) {
+///         println!("{:?}", event);
+///     }
+/// }
+///
+/// let mut bus = EventBus::default()
+///     .with_sink(Logger)
+///     .with_alarm("temperature_high");
+/// ```
+#[derive(Default)]
+pub struct EventBus {
+    sinks: Vec<Box<dyn EventSink>>,
+    alarms: Vec<String>,
+}
+
+impl EventBus {
+    /// Register a sink.
+    pub fn with_sink<S>(mut self, sink: S) -> Self
+    where
+        S: EventSink + 'static,
+    {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Register a rule as alarm.
+    ///
+    /// Changes of its state are emitted as
+    /// [Event::AlarmRaised] and [Event::AlarmCleared].
+    pub fn with_alarm<S: Into<String>>(mut self, rule_id: S) -> Self {
+        self.alarms.push(rule_id.into());
+        self
+    }
+
+    /// Emit an event to all sinks.
+    pub fn emit(&mut self, event: &Event) {
+        for s in &mut self.sinks {
+            s.handle(event);
+        }
+    }
+
+    /// Emit the events that lead from the `old` to the `new` state.
+    ///
+    /// Mode changes are emitted first, followed by rule and output events.
+    /// Events of the same kind are ordered by their IDs.
+    pub fn publish_changes(&mut self, old: &SystemState, new: &SystemState) {
+        for event in self.changes(old, new) {
+            self.emit(&event);
+        }
+    }
+
+    fn changes(&self, old: &SystemState, new: &SystemState) -> Vec<Event> {
+        let mut events = vec![];
+
+        let mut machines: Vec<_> = new.state_machines.iter().collect();
+        machines.sort_by(|a, b| a.0.cmp(b.0));
+        for (id, to) in machines {
+            let from = old.state_machines.get(id);
+            if from != Some(to) {
+                events.push(Event::ModeChanged {
+                    machine: id.clone(),
+                    from: from.cloned(),
+                    to: to.clone(),
+                });
+            }
+        }
+
+        let mut rules: Vec<_> = new.rules.keys().chain(old.rules.keys()).collect();
+        rules.sort();
+        rules.dedup();
+        for id in rules {
+            let was_active = old.rules.get(id).cloned().unwrap_or(false);
+            let is_active = new.rules.get(id).cloned().unwrap_or(false);
+            let alarm = self.alarms.contains(id);
+            match (was_active, is_active) {
+                (false, true) if alarm => events.push(Event::AlarmRaised(id.clone())),
+                (false, true) => events.push(Event::RuleFired(id.clone())),
+                (true, false) if alarm => events.push(Event::AlarmCleared(id.clone())),
+                _ => {}
+            }
+        }
+
+        let mut outputs: Vec<_> = new.io.outputs.iter().collect();
+        outputs.sort_by(|a, b| a.0.cmp(b.0));
+        for (id, v) in outputs {
+            let old = old.io.outputs.get(id);
+            if old != Some(v) {
+                events.push(Event::OutputWritten(OutputWrite {
+                    id: id.clone(),
+                    old: old.cloned(),
+                    new: v.clone(),
+                }));
+            }
+        }
+        events
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("sinks", &self.sinks.len())
+            .field("alarms", &self.alarms)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[derive(Default, Clone)]
+    struct Recorder(Rc<RefCell<Vec<Event>>>);
+
+    impl EventSink for Recorder {
+        fn handle(&mut self, event: &Event) {
+            self.0.borrow_mut().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn emit_to_all_sinks() {
+        let a = Recorder::default();
+        let b = Recorder::default();
+        let mut bus = EventBus::default()
+            .with_sink(a.clone())
+            .with_sink(b.clone());
+        bus.emit(&Event::RuleFired("x".into()));
+        assert_eq!(*a.0.borrow(), vec![Event::RuleFired("x".into())]);
+        assert_eq!(*b.0.borrow(), vec![Event::RuleFired("x".into())]);
+    }
+
+    #[test]
+    fn publish_state_changes() {
+        let recorder = Recorder::default();
+        let mut bus = EventBus::default()
+            .with_sink(recorder.clone())
+            .with_alarm("too_hot");
+
+        let mut old = SystemState::default();
+        old.rules.insert("too_hot".into(), false);
+        old.rules.insert("heating".into(), true);
+        old.io.outputs.insert("heater".into(), Value::Bit(true));
+
+        let mut new = old.clone();
+        new.rules.insert("too_hot".into(), true);
+        new.rules.insert("cooling".into(), true);
+        new.rules.insert("heating".into(), false);
+        new.io.outputs.insert("heater".into(), Value::Bit(false));
+        new.state_machines.insert("mode".into(), "cooling".into());
+        bus.publish_changes(&old, &new);
+
+        // nothing changed
+        bus.publish_changes(&new, &new);
+
+        assert_eq!(
+            *recorder.0.borrow(),
+            vec![
+                Event::ModeChanged {
+                    machine: "mode".into(),
+                    from: None,
+                    to: "cooling".into(),
+                },
+                Event::RuleFired("cooling".into()),
+                Event::AlarmRaised("too_hot".into()),
+                Event::OutputWritten(OutputWrite {
+                    id: "heater".into(),
+                    old: Some(Value::Bit(true)),
+                    new: Value::Bit(false),
+                }),
+            ]
+        );
+
+        recorder.0.borrow_mut().clear();
+        bus.publish_changes(&new, &old);
+        assert_eq!(recorder.0.borrow()[0], Event::RuleFired("heating".into()));
+        assert_eq!(
+            recorder.0.borrow()[1],
+            Event::AlarmCleared("too_hot".into())
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{TimeStepController, filter::*};
+//! use std::time::Duration;
+//!
+//! let mut chain = FilterChain::default()
+//!     .with(Filter::median(5))
+//!     .with(Filter::low_pass(Duration::from_secs(2)));
+//!
+//! let dt = Duration::from_millis(100);
+//! let raw_value = 4.2;
+//! let filtered = chain.next(raw_value, &dt);
+//!
+//! // try a different order during commissioning
+//! chain.reorder(1, 0);
+//! ```
+
+use super::{Controller, PureController, Timer, TimerState};
+use crate::util::DurationInSeconds;
+use std::{collections::VecDeque, time::Duration};
+
+/// A first-order low-pass filter
+#[derive(Debug, Clone)]
+pub struct LowPass {
+    /// The time constant of the filter
+    pub time_constant: Duration,
+    /// The current output
+    pub state: Option<f64>,
+}
+
+impl Controller<(f64, &Duration), f64> for LowPass {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (x, duration) = input;
+        let y = match self.state {
+            Some(y) => {
+                let dt = f64::from(DurationInSeconds::from(*duration));
+                let tau = f64::from(DurationInSeconds::from(self.time_constant));
+                if tau + dt > 0.0 {
+                    y + (x - y) * dt / (tau + dt)
+                } else {
+                    x
+                }
+            }
+            None => x,
+        };
+        self.state = Some(y);
+        y
+    }
+}
+
+/// A median filter over the last samples, e.g. to suppress spikes
+#[derive(Debug, Clone)]
+pub struct Median {
+    /// The number of samples
+    pub len: usize,
+    /// The last samples
+    pub samples: VecDeque<f64>,
+}
+
+impl Controller<(f64, &Duration), f64> for Median {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (x, _) = input;
+        push_sample(&mut self.samples, self.len, x);
+        let mut sorted: Vec<_> = self.samples.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let mid = sorted.len() / 2;
+        if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        }
+    }
+}
+
+/// A moving average over the last samples
+#[derive(Debug, Clone)]
+pub struct MovingAverage {
+    /// The number of samples
+    pub len: usize,
+    /// The last samples
+    pub samples: VecDeque<f64>,
+}
+
+impl Controller<(f64, &Duration), f64> for MovingAverage {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (x, _) = input;
+        push_sample(&mut self.samples, self.len, x);
+        self.samples.iter().sum::<f64>() / self.samples.len() as f64
+    }
+}
+
+fn push_sample(samples: &mut VecDeque<f64>, len: usize, x: f64) {
+    samples.push_back(x);
+    while samples.len() > len.max(1) {
+        samples.pop_front();
+    }
+}
+
+/// A filter of a [FilterChain]
+#[derive(Debug, Clone)]
+pub enum Filter {
+    LowPass(LowPass),
+    Median(Median),
+    MovingAverage(MovingAverage),
+}
+
+impl Filter {
+    /// Create a low-pass filter.
+    pub fn low_pass(time_constant: Duration) -> Self {
+        Filter::LowPass(LowPass {
+            time_constant,
+            state: None,
+        })
+    }
+    /// Create a median filter over `len` samples.
+    pub fn median(len: usize) -> Self {
+        Filter::Median(Median {
+            len,
+            samples: VecDeque::new(),
+        })
+    }
+    /// Create a moving average over `len` samples.
+    pub fn moving_average(len: usize) -> Self {
+        Filter::MovingAverage(MovingAverage {
+            len,
+            samples: VecDeque::new(),
+        })
+    }
+    /// Reset the internal state.
+    pub fn reset(&mut self) {
+        match self {
+            Filter::LowPass(f) => f.state = None,
+            Filter::Median(f) => f.samples.clear(),
+            Filter::MovingAverage(f) => f.samples.clear(),
+        }
+    }
+}
+
+impl Controller<(f64, &Duration), f64> for Filter {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        match self {
+            Filter::LowPass(f) => f.next(input),
+            Filter::Median(f) => f.next(input),
+            Filter::MovingAverage(f) => f.next(input),
+        }
+    }
+}
+
+/// An ordered list of filters that are applied in sequence
+///
+/// The filters can be inserted, removed and reordered at runtime.
+/// Each filter keeps its state when the chain is modified.
+#[derive(Debug, Clone, Default)]
+pub struct FilterChain {
+    filters: Vec<Filter>,
+}
+
+impl FilterChain {
+    /// Append a filter.
+    pub fn with(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+    /// The filters in the order of their application.
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+    /// Insert a filter at the given position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, filter: Filter) {
+        self.filters.insert(index, filter);
+    }
+    /// Remove the filter at the given position.
+    pub fn remove(&mut self, index: usize) -> Option<Filter> {
+        (index < self.filters.len()).then(|| self.filters.remove(index))
+    }
+    /// Move a filter to another position.
+    ///
+    /// Returns `false` if one of the positions is out of range.
+    pub fn reorder(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.filters.len() || to >= self.filters.len() {
+            return false;
+        }
+        let filter = self.filters.remove(from);
+        self.filters.insert(to, filter);
+        true
+    }
+    /// Reset the state of all filters.
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(Filter::reset);
+    }
+}
+
+impl Controller<(f64, &Duration), f64> for FilterChain {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (x, duration) = input;
+        self.filters
+            .iter_mut()
+            .fold(x, |x, filter| filter.next((x, duration)))
+    }
+}
+
+/// Debounces a binary signal
+///
+/// A change of the input propagates to the output only after it was
+/// stable for `rise` (`false` → `true`) or `fall` (`true` → `false`),
+/// e.g. quick to detect a fault but slow to clear it.
+/// The first input is adopted immediately.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, filter::*};
+/// use std::time::Duration;
+///
+/// let mut fault = Debounce::new(DebounceConfig {
+///     rise: Duration::from_millis(200),
+///     fall: Duration::from_secs(5),
+/// });
+/// let dt = Duration::from_millis(100);
+/// let fault_contact = true;
+/// let active = fault.next(fault_contact, &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Debounce {
+    cfg: DebounceConfig,
+    /// Current state
+    pub state: DebounceState,
+}
+
+/// Debounce configuration
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DebounceConfig {
+    /// The time a rising change has to be stable
+    pub rise: Duration,
+    /// The time a falling change has to be stable
+    pub fall: Duration,
+}
+
+/// Internal debounce state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DebounceState {
+    /// The debounced output
+    pub output: Option<bool>,
+    /// Measures the time the input differs from the output
+    pub timer: TimerState,
+}
+
+impl Debounce {
+    /// Create a new instance.
+    pub fn new(cfg: DebounceConfig) -> Self {
+        Debounce {
+            cfg,
+            state: DebounceState::default(),
+        }
+    }
+    /// Reset the internal state.
+    pub fn reset(&mut self) {
+        self.state = DebounceState::default();
+    }
+}
+
+impl Controller<(bool, &Duration), bool> for Debounce {
+    fn next(&mut self, input: (bool, &Duration)) -> bool {
+        let (x, duration) = input;
+        let (state, output) = self.cfg.next((self.state, x, duration));
+        self.state = state;
+        output
+    }
+}
+
+impl PureController<(DebounceState, bool, &Duration), (DebounceState, bool)> for DebounceConfig {
+    fn next(&self, input: (DebounceState, bool, &Duration)) -> (DebounceState, bool) {
+        let (mut state, x, duration) = input;
+        match state.output {
+            Some(y) if y != x => {
+                let mut timer = Timer::new(if x { self.rise } else { self.fall });
+                timer.state = state.timer;
+                timer.start();
+                if timer.next(duration) {
+                    state.output = Some(x);
+                    timer.reset();
+                }
+                state.timer = timer.state;
+            }
+            _ => {
+                state.output = Some(x);
+                state.timer = TimerState::default();
+            }
+        }
+        (state, state.output.unwrap_or(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn apply_filters() {
+        let dt = Duration::from_secs(1);
+        let mut lp = Filter::low_pass(Duration::from_secs(1));
+        assert_eq!(lp.next((4.0, &dt)), 4.0);
+        assert_eq!(lp.next((0.0, &dt)), 2.0);
+        assert_eq!(lp.next((0.0, &dt)), 1.0);
+
+        let mut median = Filter::median(3);
+        assert_eq!(median.next((1.0, &dt)), 1.0);
+        assert_eq!(median.next((3.0, &dt)), 2.0);
+        assert_eq!(median.next((100.0, &dt)), 3.0);
+        assert_eq!(median.next((2.0, &dt)), 3.0);
+
+        let mut avg = Filter::moving_average(2);
+        assert_eq!(avg.next((1.0, &dt)), 1.0);
+        assert_eq!(avg.next((3.0, &dt)), 2.0);
+        assert_eq!(avg.next((7.0, &dt)), 5.0);
+        avg.reset();
+        assert_eq!(avg.next((7.0, &dt)), 7.0);
+    }
+
+    #[test]
+    fn reorder_filter_chain() {
+        let dt = Duration::from_secs(1);
+        let inputs = [1.0, 1.0, 10.0, 1.0];
+        let mut chain = FilterChain::default()
+            .with(Filter::median(3))
+            .with(Filter::moving_average(2));
+        let out: Vec<_> = inputs.iter().map(|x| chain.next((*x, &dt))).collect();
+        // the spike is removed before averaging
+        assert_eq!(out, vec![1.0, 1.0, 1.0, 1.0]);
+
+        assert!(chain.reorder(1, 0));
+        assert!(matches!(chain.filters()[0], Filter::MovingAverage(_)));
+        chain.reset();
+        let out: Vec<_> = inputs.iter().map(|x| chain.next((*x, &dt))).collect();
+        // the averaged spike passes the median filter
+        assert_eq!(out, vec![1.0, 1.0, 1.0, 5.5]);
+
+        assert!(!chain.reorder(0, 2));
+        assert!(chain.remove(2).is_none());
+        assert!(matches!(chain.remove(1), Some(Filter::Median(_))));
+        chain.insert(0, Filter::low_pass(Duration::from_secs(1)));
+        assert_eq!(chain.filters().len(), 2);
+    }
+
+    #[test]
+    fn debounce_with_separate_rise_and_fall_times() {
+        let mut debounce = Debounce::new(DebounceConfig {
+            rise: Duration::from_millis(200),
+            fall: Duration::from_millis(500),
+        });
+        let dt = Duration::from_millis(100);
+        let mut next = |x| debounce.next((x, &dt));
+        assert!(!next(false));
+        // rising
+        assert!(!next(true));
+        assert!(next(true));
+        // a short drop is suppressed
+        assert!(next(false));
+        assert!(next(true));
+        // falling
+        for _ in 0..4 {
+            assert!(next(false));
+        }
+        assert!(!next(false));
+        // a short spike is suppressed
+        assert!(!next(true));
+        assert!(!next(false));
+        assert!(!next(true));
+        assert!(next(true));
+    }
+}
@@ -0,0 +1,211 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{TimeStepController, flow::*};
+//! use std::time::Duration;
+//!
+//! let mut ctl = PressureCompensatedFlow::new(PressureCompensatedFlowConfig {
+//!     max_flow: 12.0,
+//!     design_dp: 1.5,
+//!     ..Default::default()
+//! });
+//! let dt = Duration::from_millis(100);
+//! let flow_setpoint = 6.0;
+//! let measured_dp = 1.2;
+//! let valve_command = ctl.next((flow_setpoint, measured_dp), &dt);
+//! ```
+
+use super::{Controller, PureController};
+use std::time::Duration;
+
+/// Square root extraction with low-flow cut-off
+///
+/// Inputs below the cut-off are mapped to `0.0`,
+/// e.g. to suppress noise of differential pressure transmitters.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SqrtExtract {
+    /// Inputs below this value are mapped to `0.0`
+    pub cutoff: f64,
+}
+
+impl PureController<f64, f64> for SqrtExtract {
+    fn next(&self, input: f64) -> f64 {
+        if input < self.cutoff || input <= 0.0 {
+            0.0
+        } else {
+            input.sqrt()
+        }
+    }
+}
+
+/// The inherent flow characteristic of a valve
+///
+/// Used to map a linear controller output (0–100%)
+/// to a valve position, so that the resulting flow is
+/// proportional to the controller output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ValveCharacteristic {
+    /// The flow is proportional to the position
+    #[default]
+    Linear,
+    /// Equal position changes result in equal percentage
+    /// changes of the flow, i.e. `flow = R^(position - 1)`
+    /// with the rangeability `R`.
+    EqualPercentage(f64),
+    /// Most of the flow is reached at low positions,
+    /// i.e. `flow = sqrt(position)`.
+    QuickOpening,
+}
+
+impl PureController<f64, f64> for ValveCharacteristic {
+    /// Map a command (0–100%) to a valve position (0–100%).
+    fn next(&self, command: f64) -> f64 {
+        let flow = command.clamp(0.0, 100.0) / 100.0;
+        let position = match *self {
+            Self::Linear => flow,
+            Self::EqualPercentage(rangeability) => {
+                if flow > 0.0 && rangeability > 1.0 {
+                    1.0 + flow.ln() / rangeability.ln()
+                } else {
+                    flow
+                }
+            }
+            Self::QuickOpening => flow * flow,
+        };
+        100.0 * position.clamp(0.0, 1.0)
+    }
+}
+
+/// A controller for pressure-independent flow
+///
+/// The flow through a valve is proportional to the opening
+/// and the square root of the differential pressure.
+/// The valve command for a flow setpoint is therefore corrected
+/// by the factor `sqrt(design_dp / dp)`.
+#[derive(Debug, Clone)]
+pub struct PressureCompensatedFlow {
+    cfg: PressureCompensatedFlowConfig,
+}
+
+/// Flow controller configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PressureCompensatedFlowConfig {
+    /// The flow of the fully opened valve at the design pressure
+    pub max_flow: f64,
+    /// The differential pressure the valve is designed for
+    pub design_dp: f64,
+    /// Square root extraction of the differential pressure
+    pub sqrt: SqrtExtract,
+    /// Lower limit of the valve command
+    pub min: f64,
+    /// Upper limit of the valve command
+    pub max: f64,
+    /// The characteristic of the valve
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub characteristic: ValveCharacteristic,
+}
+
+impl Default for PressureCompensatedFlowConfig {
+    fn default() -> Self {
+        PressureCompensatedFlowConfig {
+            max_flow: 1.0,
+            design_dp: 1.0,
+            sqrt: SqrtExtract::default(),
+            min: 0.0,
+            max: 100.0,
+            characteristic: ValveCharacteristic::default(),
+        }
+    }
+}
+
+impl PressureCompensatedFlow {
+    /// Create a new controller instance.
+    pub fn new(cfg: PressureCompensatedFlowConfig) -> Self {
+        PressureCompensatedFlow { cfg }
+    }
+}
+
+impl Controller<((f64, f64), &Duration), f64> for PressureCompensatedFlow {
+    fn next(&mut self, input: ((f64, f64), &Duration)) -> f64 {
+        let (setpoint_and_dp, _) = input;
+        self.cfg.next(setpoint_and_dp)
+    }
+}
+
+impl PureController<(f64, f64), f64> for PressureCompensatedFlowConfig {
+    fn next(&self, input: (f64, f64)) -> f64 {
+        let (setpoint, dp) = input;
+        let ratio = self.sqrt.next(dp) / self.sqrt.next(self.design_dp);
+        let command = if ratio > 0.0 && self.max_flow > 0.0 {
+            100.0 * setpoint / (self.max_flow * ratio)
+        } else if setpoint > 0.0 {
+            // there is no (measurable) pressure
+            self.max
+        } else {
+            self.min
+        };
+        self.characteristic
+            .next(command.max(self.min).min(self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn extract_square_root() {
+        let sqrt = SqrtExtract { cutoff: 0.1 };
+        assert_eq!(sqrt.next(4.0), 2.0);
+        assert_eq!(sqrt.next(0.1), 0.1_f64.sqrt());
+        assert_eq!(sqrt.next(0.09), 0.0);
+        assert_eq!(SqrtExtract::default().next(-1.0), 0.0);
+    }
+
+    #[test]
+    fn linearize_valve_characteristics() {
+        let equal_percentage = ValveCharacteristic::EqualPercentage(50.0);
+        // 50% of the flow: 50^(x - 1) = 0.5
+        let position = equal_percentage.next(50.0);
+        assert!((position - 82.281).abs() < 0.001);
+        assert!((50_f64.powf(position / 100.0 - 1.0) - 0.5).abs() < 1e-9);
+        assert_eq!(equal_percentage.next(100.0), 100.0);
+        assert_eq!(equal_percentage.next(1.0), 0.0);
+        assert_eq!(equal_percentage.next(0.0), 0.0);
+
+        assert_eq!(ValveCharacteristic::Linear.next(50.0), 50.0);
+        assert_eq!(ValveCharacteristic::Linear.next(120.0), 100.0);
+        assert_eq!(ValveCharacteristic::QuickOpening.next(50.0), 25.0);
+        assert_eq!(ValveCharacteristic::QuickOpening.next(-5.0), 0.0);
+
+        let mut ctl = PressureCompensatedFlow::new(PressureCompensatedFlowConfig {
+            max_flow: 10.0,
+            characteristic: ValveCharacteristic::QuickOpening,
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next(((5.0, 1.0), &dt)), 25.0);
+    }
+
+    #[test]
+    fn compensate_pressure_changes() {
+        let mut ctl = PressureCompensatedFlow::new(PressureCompensatedFlowConfig {
+            max_flow: 10.0,
+            design_dp: 1.0,
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next(((5.0, 1.0), &dt)), 50.0);
+        // four times the pressure results in twice the flow
+        // at the same opening, so the valve has to close by half.
+        assert_eq!(ctl.next(((5.0, 4.0), &dt)), 25.0);
+        assert_eq!(ctl.next(((5.0, 0.25), &dt)), 100.0);
+        // limited
+        assert_eq!(ctl.next(((5.0, 0.01), &dt)), 100.0);
+        assert_eq!(ctl.next(((5.0, 0.0), &dt)), 100.0);
+        assert_eq!(ctl.next(((0.0, 0.0), &dt)), 0.0);
+    }
+}
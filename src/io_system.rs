@@ -0,0 +1,1250 @@
+use super::*;
+use std::{fmt, time::Duration};
+
+/// Separates the namespace from the local ID within a [CompositeIo].
+pub const NAMESPACE_SEPARATOR: char = ':';
+
+/// An I/O system that is composed of multiple backends.
+///
+/// Each backend is registered with a namespace.
+/// An ID like `modbus:tcr001` is routed to the backend
+/// with the namespace `modbus` that receives the local ID `tcr001`.
+/// IDs without a registered namespace result in an error.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let mut io = CompositeIo::default()
+///     .with_backend("sim", IoState::default())
+///     .with_backend("modbus", IoState::default());
+///
+/// io.write("sim:h1", &Value::Decimal(1.7)).unwrap();
+/// ```
+#[derive(Default)]
+pub struct CompositeIo {
+    backends: Vec<(String, Box<dyn SyncIoSystem>)>,
+}
+
+impl CompositeIo {
+    /// Register a backend for the given namespace.
+    ///
+    /// An already registered backend with the same namespace is replaced.
+    pub fn with_backend<S, B>(mut self, namespace: S, backend: B) -> Self
+    where
+        S: Into<String>,
+        B: SyncIoSystem + 'static,
+    {
+        let namespace = namespace.into();
+        self.backends.retain(|(ns, _)| *ns != namespace);
+        self.backends.push((namespace, Box::new(backend)));
+        self
+    }
+
+    /// List the registered namespaces.
+    pub fn namespaces(&self) -> Vec<&str> {
+        self.backends.iter().map(|(ns, _)| ns.as_str()).collect()
+    }
+
+    fn route<'a>(&mut self, id: &'a str) -> Result<(&mut dyn SyncIoSystem, &'a str)> {
+        let mut parts = id.splitn(2, NAMESPACE_SEPARATOR);
+        let namespace = parts.next().unwrap_or_default();
+        let local_id = parts.next().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("The ID '{}' has no namespace", id),
+            )
+        })?;
+        let backend = self
+            .backends
+            .iter_mut()
+            .find(|(ns, _)| ns == namespace)
+            .map(|(_, b)| b)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("There is no backend for the namespace '{}'", namespace),
+                )
+            })?;
+        Ok((backend.as_mut(), local_id))
+    }
+}
+
+impl fmt::Debug for CompositeIo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompositeIo")
+            .field("namespaces", &self.namespaces())
+            .finish()
+    }
+}
+
+impl SyncIoSystem for CompositeIo {
+    fn read(&mut self, id: &str) -> Result<Value> {
+        let (backend, id) = self.route(id)?;
+        backend.read(id)
+    }
+
+    fn read_output(&mut self, id: &str) -> Result<Option<Value>> {
+        let (backend, id) = self.route(id)?;
+        backend.read_output(id)
+    }
+
+    fn write(&mut self, id: &str, value: &Value) -> Result<()> {
+        let (backend, id) = self.route(id)?;
+        backend.write(id, value)
+    }
+}
+
+/// A controller wrapper that writes each output to an I/O system.
+///
+/// Numeric outputs are written as [Value::Decimal]
+/// (or as [Value::Quantity] if a unit is given),
+/// boolean outputs as [Value::Bit].
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{*, pid::*};
+/// use std::time::Duration;
+///
+/// let mut pid = OutputWriter::new(Pid::new(PidConfig::default()), "heater");
+/// let mut io = IoState::default();
+/// let dt = Duration::from_millis(100);
+///
+/// let output = pid.next(((20.0, &dt), &mut io)).unwrap();
+/// assert_eq!(io.outputs["heater"], Value::Decimal(output));
+/// ```
+#[derive(Debug, Clone)]
+pub struct OutputWriter<C> {
+    /// The wrapped controller
+    pub controller: C,
+    /// The ID of the output to write to
+    pub output: String,
+    /// The engineering unit of numeric outputs
+    pub unit: Option<String>,
+}
+
+impl<C> OutputWriter<C> {
+    /// Wrap a controller.
+    pub fn new<S: Into<String>>(controller: C, output: S) -> Self {
+        OutputWriter {
+            controller,
+            output: output.into(),
+            unit: None,
+        }
+    }
+    /// Tag numeric outputs with an engineering unit.
+    pub fn with_unit<S: Into<String>>(mut self, unit: S) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+}
+
+impl<C, I, O, IO> Controller<(I, &mut IO), Result<O>> for OutputWriter<C>
+where
+    C: Controller<I, O>,
+    O: Into<Value> + Clone,
+    IO: SyncIoSystem,
+{
+    fn next(&mut self, input: (I, &mut IO)) -> Result<O> {
+        let (input, io) = input;
+        let output = self.controller.next(input);
+        let value = match (output.clone().into(), &self.unit) {
+            (Value::Decimal(v), Some(unit)) => Value::Quantity(v, unit.clone()),
+            (Value::Integer(v), Some(unit)) => Value::Quantity(v as f64, unit.clone()),
+            (value, _) => value,
+        };
+        io.write(&self.output, &value)?;
+        Ok(output)
+    }
+}
+
+/// An I/O system wrapper with transactional writes.
+///
+/// Writes within a transaction are buffered and written
+/// to the underlying system on [commit](TransactionalIo::commit).
+/// If a write fails during the commit, the outputs that were
+/// already written are restored to their previous values.
+///
+/// Most backends can't write multiple outputs atomically,
+/// so the rollback is best-effort: Outputs without a readable
+/// previous value can't be restored and the restore itself might fail.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let mut io = TransactionalIo::new(IoState::default());
+/// io.begin();
+/// io.write("pump", &Value::Bit(true)).unwrap();
+/// io.write("valve", &Value::Decimal(40.0)).unwrap();
+/// io.commit().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TransactionalIo<IO> {
+    /// The underlying I/O system
+    pub io: IO,
+    pending: Option<Vec<(String, Value)>>,
+}
+
+impl<IO> TransactionalIo<IO> {
+    /// Wrap an I/O system.
+    pub fn new(io: IO) -> Self {
+        TransactionalIo { io, pending: None }
+    }
+    /// Start a transaction.
+    ///
+    /// Pending writes of a previous transaction are discarded.
+    pub fn begin(&mut self) {
+        self.pending = Some(vec![]);
+    }
+    /// Discard all pending writes and end the transaction.
+    pub fn rollback(&mut self) {
+        self.pending = None;
+    }
+    /// Check if a transaction is in progress.
+    pub fn in_transaction(&self) -> bool {
+        self.pending.is_some()
+    }
+}
+
+impl<IO: SyncIoSystem> TransactionalIo<IO> {
+    /// Write all pending values and end the transaction.
+    ///
+    /// On failure the already written outputs are restored
+    /// and the error of the failed write is returned.
+    pub fn commit(&mut self) -> Result<()> {
+        let pending = self.pending.take().unwrap_or_default();
+        let mut written = vec![];
+        for (id, value) in pending {
+            let res = self
+                .io
+                .read_output(&id)
+                .and_then(|prev| self.io.write(&id, &value).map(|_| prev));
+            match res {
+                Ok(prev) => written.push((id, prev)),
+                Err(err) => {
+                    for (id, prev) in written.into_iter().rev() {
+                        if let Some(prev) = prev {
+                            // best-effort
+                            let _ = self.io.write(&id, &prev);
+                        }
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<IO: SyncIoSystem> SyncIoSystem for TransactionalIo<IO> {
+    fn read(&mut self, id: &str) -> Result<Value> {
+        self.io.read(id)
+    }
+
+    /// Pending writes of the current transaction are visible.
+    fn read_output(&mut self, id: &str) -> Result<Option<Value>> {
+        if let Some(pending) = &self.pending {
+            if let Some((_, v)) = pending.iter().rev().find(|(x, _)| x == id) {
+                return Ok(Some(v.clone()));
+            }
+        }
+        self.io.read_output(id)
+    }
+
+    fn write(&mut self, id: &str, value: &Value) -> Result<()> {
+        match &mut self.pending {
+            Some(pending) => {
+                pending.push((id.into(), value.clone()));
+                Ok(())
+            }
+            None => self.io.write(id, value),
+        }
+    }
+}
+
+/// An I/O system wrapper that applies the writes
+/// of a cycle in a safe sequence.
+///
+/// The writes of a cycle are collected and applied in the declared
+/// order on [flush](SequencedIo::flush), e.g. to close an inlet
+/// before opening a drain. Writes without an ordering constraint
+/// keep their original order. If a write fails, the following
+/// writes of the cycle are not applied.
+///
+/// In [strict](SequencedIo::strict) mode the writes are not reordered,
+/// instead a write that violates the declared order is rejected.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let mut io = SequencedIo::new(IoState::default())
+///     .with_order("inlet", "drain")
+///     .unwrap();
+///
+/// io.write("drain", &Value::Bit(true)).unwrap();
+/// io.write("inlet", &Value::Bit(false)).unwrap();
+/// io.flush().unwrap(); // writes "inlet" first
+/// ```
+#[derive(Debug, Clone)]
+pub struct SequencedIo<IO> {
+    /// The underlying I/O system
+    pub io: IO,
+    order: Vec<(String, String)>,
+    pending: Vec<(String, Value)>,
+    strict: bool,
+}
+
+impl<IO> SequencedIo<IO> {
+    /// Wrap an I/O system.
+    pub fn new(io: IO) -> Self {
+        SequencedIo {
+            io,
+            order: vec![],
+            pending: vec![],
+            strict: false,
+        }
+    }
+    /// Reject writes that violate the declared order
+    /// instead of reordering them.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+    /// Declare that the output `before` must be written before `after`
+    /// if both are written within the same cycle.
+    ///
+    /// Constraints that contradict each other result in an error.
+    pub fn with_order<S: Into<String>>(mut self, before: S, after: S) -> Result<Self> {
+        let (before, after) = (before.into(), after.into());
+        if before == after || self.precedes(&after, &before) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Writing '{}' before '{}' contradicts the declared order",
+                    before, after
+                ),
+            ));
+        }
+        self.order.push((before, after));
+        Ok(self)
+    }
+    /// Check if `a` has to be written before `b` (transitively).
+    fn precedes(&self, a: &str, b: &str) -> bool {
+        let mut visited = vec![];
+        let mut stack = vec![a];
+        while let Some(x) = stack.pop() {
+            if x == b {
+                return true;
+            }
+            if visited.contains(&x) {
+                continue;
+            }
+            visited.push(x);
+            stack.extend(
+                self.order
+                    .iter()
+                    .filter(|(before, _)| before == x)
+                    .map(|(_, after)| after.as_str()),
+            );
+        }
+        false
+    }
+    /// The pending writes in the order they would be applied.
+    pub fn sequence(&self) -> Vec<&str> {
+        let mut remaining: Vec<&str> = self.pending.iter().map(|(id, _)| id.as_str()).collect();
+        let mut sequence = vec![];
+        while !remaining.is_empty() {
+            let next = remaining
+                .iter()
+                .position(|x| !remaining.iter().any(|y| y != x && self.precedes(y, x)))
+                // contradicting constraints are rejected, so this can't happen
+                .unwrap_or(0);
+            sequence.push(remaining.remove(next));
+        }
+        sequence
+    }
+}
+
+impl<IO: SyncIoSystem> SequencedIo<IO> {
+    /// Apply the pending writes in the declared order.
+    pub fn flush(&mut self) -> Result<()> {
+        let sequence: Vec<String> = self.sequence().into_iter().map(String::from).collect();
+        let mut pending = std::mem::take(&mut self.pending);
+        for id in sequence {
+            if let Some(i) = pending.iter().position(|(x, _)| *x == id) {
+                let (id, value) = pending.remove(i);
+                self.io.write(&id, &value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<IO: SyncIoSystem> SyncIoSystem for SequencedIo<IO> {
+    fn read(&mut self, id: &str) -> Result<Value> {
+        self.io.read(id)
+    }
+
+    /// Pending writes are visible.
+    fn read_output(&mut self, id: &str) -> Result<Option<Value>> {
+        if let Some((_, v)) = self.pending.iter().find(|(x, _)| x == id) {
+            return Ok(Some(v.clone()));
+        }
+        self.io.read_output(id)
+    }
+
+    /// A repeated write of an output within a cycle replaces the pending value.
+    fn write(&mut self, id: &str, value: &Value) -> Result<()> {
+        if self.strict {
+            if let Some((x, _)) = self
+                .pending
+                .iter()
+                .find(|(x, _)| x != id && self.precedes(id, x))
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Writing '{}' after '{}' violates the declared order", id, x),
+                ));
+            }
+        }
+        match self.pending.iter_mut().find(|(x, _)| x == id) {
+            Some((_, v)) => *v = value.clone(),
+            None => self.pending.push((id.into(), value.clone())),
+        }
+        Ok(())
+    }
+}
+
+/// An [IoState] that tracks the age of its inputs
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+/// use std::time::Duration;
+///
+/// let mut state = TimestampedIoState::default();
+/// state.update_input("tcr001", Value::Decimal(8.9));
+/// state.tick(&Duration::from_secs(1));
+/// assert_eq!(state.age("tcr001"), Some(Duration::from_secs(1)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TimestampedIoState {
+    /// The I/O state
+    pub io: IoState,
+    /// The time since the last update of each input
+    pub ages: HashMap<String, Duration>,
+}
+
+impl TimestampedIoState {
+    /// Set the value of an input that was just received.
+    pub fn update_input<S: Into<String>>(&mut self, id: S, value: Value) {
+        let id = id.into();
+        self.ages.insert(id.clone(), Duration::new(0, 0));
+        self.io.inputs.insert(id, value);
+    }
+    /// Let the time pass.
+    pub fn tick(&mut self, duration: &Duration) {
+        for age in self.ages.values_mut() {
+            *age += *duration;
+        }
+    }
+    /// The time since the last update of an input.
+    pub fn age(&self, id: &str) -> Option<Duration> {
+        self.ages.get(id).copied()
+    }
+}
+
+/// The safe values of outputs, e.g. for an emergency stop.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SafeState {
+    /// The safe value of each output
+    pub outputs: HashMap<String, Value>,
+}
+
+impl SafeState {
+    /// Add the safe value of an output.
+    pub fn with_output<S: Into<String>, V: Into<Value>>(mut self, id: S, value: V) -> Self {
+        self.outputs.insert(id.into(), value.into());
+        self
+    }
+}
+
+/// An I/O system wrapper that can be tripped into a [SafeState].
+///
+/// On [trip](TrippableIo::trip) all outputs are driven to their
+/// safe values and further writes are rejected until the
+/// wrapper is explicitly [reset](TrippableIo::reset).
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let safe = SafeState::default()
+///     .with_output("heater", false)
+///     .with_output("valve", 0.0);
+/// let mut io = TrippableIo::new(IoState::default(), safe);
+///
+/// let emergency_stop = true;
+/// if emergency_stop {
+///     io.trip().unwrap();
+/// }
+/// assert!(io.write("heater", &Value::Bit(true)).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct TrippableIo<IO> {
+    /// The underlying I/O system
+    pub io: IO,
+    safe_state: SafeState,
+    tripped: bool,
+}
+
+impl<IO> TrippableIo<IO> {
+    /// Wrap an I/O system.
+    pub fn new(io: IO, safe_state: SafeState) -> Self {
+        TrippableIo {
+            io,
+            safe_state,
+            tripped: false,
+        }
+    }
+    /// Check if the system is tripped.
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+    /// Release the trip, so normal writes are accepted again.
+    pub fn reset(&mut self) {
+        self.tripped = false;
+    }
+}
+
+impl<IO: SyncIoSystem> TrippableIo<IO> {
+    /// Drive all outputs to their safe values
+    /// and block further writes.
+    ///
+    /// The system is tripped even if a write fails.
+    /// All safe values are written anyway and
+    /// the first error is returned.
+    pub fn trip(&mut self) -> Result<()> {
+        self.tripped = true;
+        let mut res = Ok(());
+        for (id, value) in &self.safe_state.outputs {
+            if let Err(err) = self.io.write(id, value) {
+                if res.is_ok() {
+                    res = Err(err);
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<IO: SyncIoSystem> SyncIoSystem for TrippableIo<IO> {
+    fn read(&mut self, id: &str) -> Result<Value> {
+        self.io.read(id)
+    }
+
+    fn read_output(&mut self, id: &str) -> Result<Option<Value>> {
+        self.io.read_output(id)
+    }
+
+    fn write(&mut self, id: &str, value: &Value) -> Result<()> {
+        if self.tripped {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Can't write '{}': the system is tripped", id),
+            ));
+        }
+        self.io.write(id, value)
+    }
+}
+
+/// A fixed binary layout of numeric I/O points.
+///
+/// Each field is encoded as a little-endian `f32`
+/// in the declared order, so a frame of `n` fields has
+/// exactly `4 * n` bytes and needs no further framing,
+/// e.g. for the transmission to an embedded display.
+/// Missing values are encoded as `NaN` and skipped on decoding.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let layout = FrameLayout::new(vec![
+///     Source::In("temp".into()),
+///     Source::Out("heater".into()),
+/// ])
+/// .unwrap();
+/// let mut state = IoState::default();
+/// state.inputs.insert("temp".into(), 21.5.into());
+/// state.outputs.insert("heater".into(), 40.0.into());
+/// let frame = layout.encode(&state).unwrap();
+/// assert_eq!(frame.len(), layout.frame_len());
+/// let decoded = layout.decode(&frame).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameLayout {
+    fields: Vec<Source>,
+}
+
+impl FrameLayout {
+    /// Create a new layout with the given field order.
+    ///
+    /// Only inputs, outputs and memory values can be used as fields.
+    pub fn new(fields: Vec<Source>) -> Result<Self> {
+        for f in &fields {
+            if !matches!(f, Source::In(_) | Source::Out(_) | Source::Mem(_)) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("'{}' is not an I/O point", f),
+                ));
+            }
+        }
+        Ok(FrameLayout { fields })
+    }
+    /// The fields in the order of the frame.
+    pub fn fields(&self) -> &[Source] {
+        &self.fields
+    }
+    /// The size of a frame in bytes.
+    pub fn frame_len(&self) -> usize {
+        self.fields.len() * 4
+    }
+    /// Encode the fields of a state.
+    ///
+    /// Non-numeric values can't be encoded.
+    pub fn encode(&self, state: &IoState) -> Result<Vec<u8>> {
+        let mut frame = Vec::with_capacity(self.frame_len());
+        for f in &self.fields {
+            let v = match get_io_val(f, state) {
+                Ok(v) => v.to_decimal(BitEncoding::Strict)? as f32,
+                Err(err) if err.kind() == ErrorKind::NotFound => f32::NAN,
+                Err(err) => return Err(err),
+            };
+            frame.extend_from_slice(&v.to_le_bytes());
+        }
+        Ok(frame)
+    }
+    /// Decode a frame into a state.
+    ///
+    /// All values are decoded as decimals.
+    pub fn decode(&self, frame: &[u8]) -> Result<IoState> {
+        if frame.len() != self.frame_len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Invalid frame length: expected {} bytes, got {}",
+                    self.frame_len(),
+                    frame.len()
+                ),
+            ));
+        }
+        let mut state = IoState::default();
+        for (f, bytes) in self.fields.iter().zip(frame.chunks_exact(4)) {
+            let v = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            if v.is_nan() {
+                continue;
+            }
+            let v = Value::Decimal(f64::from(v));
+            match f {
+                Source::In(id) => state.inputs.insert(id.clone(), v),
+                Source::Out(id) => state.outputs.insert(id.clone(), v),
+                Source::Mem(id) => state.mem.insert(id.clone(), v),
+                _ => unreachable!(),
+            };
+        }
+        Ok(state)
+    }
+}
+
+/// The scan configuration of an input
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanConfig {
+    /// The time between two reads
+    pub interval: Duration,
+    /// Numeric changes up to this amount are not reported
+    pub deadband: f64,
+}
+
+/// The scan state of an input
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScanState {
+    /// The time since the last read
+    pub elapsed: Option<Duration>,
+    /// The last reported value
+    pub reported: Option<Value>,
+}
+
+/// Reads inputs at individual rates and reports significant changes.
+///
+/// Each input is read when its scan interval elapsed.
+/// A read value is only reported as [Event::InputChanged]
+/// if it differs from the previously reported value by more
+/// than the deadband of the input (non-numeric values are
+/// reported on any change).
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+/// use std::time::Duration;
+///
+/// let mut scanner = Scanner::default()
+///     .with_input("pressure", ScanConfig {
+///         interval: Duration::from_millis(100),
+///         deadband: 0.05,
+///     })
+///     .with_input("ambient_temp", ScanConfig {
+///         interval: Duration::from_secs(10),
+///         deadband: 0.5,
+///     });
+/// let mut io = IoState::default();
+/// let report = scanner.scan(&mut io, &Duration::from_millis(100));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Scanner {
+    inputs: Vec<(String, ScanConfig)>,
+    /// The scan state of each input
+    pub state: HashMap<String, ScanState>,
+}
+
+/// A report of a [Scanner::scan]
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    /// The significant changes
+    pub events: Vec<Event>,
+    /// The inputs that couldn't be read (by their IDs)
+    pub errors: Vec<(String, Error)>,
+}
+
+impl Scanner {
+    /// Register an input.
+    pub fn with_input<S: Into<String>>(mut self, id: S, cfg: ScanConfig) -> Self {
+        let id = id.into();
+        self.inputs.retain(|(x, _)| *x != id);
+        self.inputs.push((id, cfg));
+        self
+    }
+    /// The last reported value of an input.
+    pub fn reported(&self, id: &str) -> Option<&Value> {
+        self.state.get(id).and_then(|s| s.reported.as_ref())
+    }
+    /// Reset the scan state, so all inputs are read
+    /// and reported with the next scan.
+    pub fn reset(&mut self) {
+        self.state.clear();
+    }
+    /// Let the time pass and read all inputs that are due.
+    ///
+    /// Reports the significant changes in the order
+    /// the inputs were registered.
+    /// An input that can't be read doesn't stop the scan,
+    /// instead its error is recorded in the report
+    /// and it is read again with the next scan.
+    pub fn scan<IO: SyncIoSystem>(&mut self, io: &mut IO, duration: &Duration) -> ScanReport {
+        let mut report = ScanReport::default();
+        for (id, cfg) in &self.inputs {
+            let state = self.state.entry(id.clone()).or_default();
+            let elapsed = state.elapsed.map(|e| e + *duration);
+            if let Some(e) = elapsed {
+                if e < cfg.interval {
+                    state.elapsed = Some(e);
+                    continue;
+                }
+            }
+            let value = match io.read(id) {
+                Ok(value) => value,
+                Err(err) => {
+                    state.elapsed = elapsed;
+                    report.errors.push((id.clone(), err));
+                    continue;
+                }
+            };
+            // Keep the overshoot, so the average rate matches the interval
+            // even if it isn't a multiple of the scan cycle,
+            // but don't catch up after a longer break (e.g. read errors).
+            let overshoot = elapsed.map_or(Duration::new(0, 0), |e| e - cfg.interval);
+            state.elapsed = Some(if overshoot < cfg.interval {
+                overshoot
+            } else {
+                Duration::new(0, 0)
+            });
+            let significant = match &state.reported {
+                None => true,
+                Some(old) => match (
+                    old.to_decimal(BitEncoding::Strict),
+                    value.to_decimal(BitEncoding::Strict),
+                ) {
+                    (Ok(old), Ok(new)) => (new - old).abs() > cfg.deadband,
+                    _ => *old != value,
+                },
+            };
+            if significant {
+                report.events.push(Event::InputChanged {
+                    id: id.clone(),
+                    old: state.reported.replace(value.clone()),
+                    new: value,
+                });
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn route_to_backends_by_namespace() {
+        let mut sim = IoState::default();
+        sim.inputs.insert("x".into(), Value::Decimal(1.0));
+        let mut modbus = IoState::default();
+        modbus.inputs.insert("x".into(), Value::Decimal(2.0));
+
+        let mut io = CompositeIo::default()
+            .with_backend("sim", sim)
+            .with_backend("modbus", modbus);
+
+        assert_eq!(io.namespaces(), vec!["sim", "modbus"]);
+        assert_eq!(io.read("sim:x").unwrap(), Value::Decimal(1.0));
+        assert_eq!(io.read("modbus:x").unwrap(), Value::Decimal(2.0));
+
+        io.write("sim:y", &Value::Bit(true)).unwrap();
+        assert_eq!(io.read_output("sim:y").unwrap(), Some(Value::Bit(true)));
+        assert_eq!(io.read_output("modbus:y").unwrap(), None);
+
+        io.write("modbus:y", &Value::Bit(false)).unwrap();
+        assert_eq!(io.read_output("sim:y").unwrap(), Some(Value::Bit(true)));
+        assert_eq!(io.read_output("modbus:y").unwrap(), Some(Value::Bit(false)));
+    }
+
+    #[test]
+    fn reject_unknown_namespaces() {
+        let mut io = CompositeIo::default().with_backend("sim", IoState::default());
+        assert_eq!(io.read("foo:x").unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(
+            io.write("x", &Value::Bit(true)).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert!(io.read_output("sim:x").unwrap().is_none());
+    }
+
+    #[test]
+    fn write_controller_outputs() {
+        use crate::{bang_bang::*, pid::*};
+        use std::time::Duration;
+
+        let dt = Duration::from_secs(1);
+        let mut io = IoState::default();
+
+        let mut pid = Pid::new(PidConfig {
+            k_p: 2.0,
+            ..Default::default()
+        });
+        pid.set_target(10.0);
+        let mut pid = OutputWriter::new(pid, "y");
+        assert_eq!(pid.next(((4.0, &dt), &mut io)).unwrap(), 12.0);
+        assert_eq!(io.outputs["y"], Value::Decimal(12.0));
+        assert_eq!(pid.next(((7.0, &dt), &mut io)).unwrap(), 6.0);
+        assert_eq!(io.outputs["y"], Value::Decimal(6.0));
+
+        let mut bb = OutputWriter::new(BangBang::new(BangBangConfig::default()), "switch");
+        assert!(bb.next((1.0, &mut io)).unwrap());
+        assert_eq!(io.outputs["switch"], Value::Bit(true));
+    }
+
+    #[test]
+    fn write_outputs_with_units() {
+        use crate::{bang_bang::*, pid::*};
+        use std::time::Duration;
+
+        let dt = Duration::from_secs(1);
+        let mut io = IoState::default();
+        let mut pid = Pid::new(PidConfig {
+            k_p: 2.0,
+            ..Default::default()
+        });
+        pid.set_target(10.0);
+        let mut pid = OutputWriter::new(pid, "valve").with_unit("%");
+        assert_eq!(pid.next(((4.0, &dt), &mut io)).unwrap(), 12.0);
+        let value = io.read_output("valve").unwrap().unwrap();
+        assert_eq!(value, Value::quantity(12.0, "%"));
+        assert_eq!(value.unit(), Some("%"));
+        assert_eq!(value.to_decimal(BitEncoding::Strict).unwrap(), 12.0);
+
+        // bits have no unit
+        let mut bb =
+            OutputWriter::new(BangBang::new(BangBangConfig::default()), "switch").with_unit("%");
+        assert!(bb.next((1.0, &mut io)).unwrap());
+        assert_eq!(io.outputs["switch"], Value::Bit(true));
+    }
+
+    #[test]
+    fn propagate_write_errors() {
+        use crate::bang_bang::*;
+        let mut io = CompositeIo::default();
+        let mut w = OutputWriter::new(BangBang::new(BangBangConfig::default()), "sim:x");
+        assert!(w.next((1.0, &mut io)).is_err());
+    }
+
+    #[test]
+    fn commit_transaction() {
+        let mut io = TransactionalIo::new(IoState::default());
+        io.write("a", &Value::Decimal(1.0)).unwrap();
+        assert_eq!(io.io.outputs["a"], Value::Decimal(1.0));
+
+        io.begin();
+        assert!(io.in_transaction());
+        io.write("a", &Value::Decimal(2.0)).unwrap();
+        io.write("b", &Value::Bit(true)).unwrap();
+        assert_eq!(io.read_output("a").unwrap(), Some(Value::Decimal(2.0)));
+        assert_eq!(io.io.outputs["a"], Value::Decimal(1.0));
+        assert!(!io.io.outputs.contains_key("b"));
+
+        io.commit().unwrap();
+        assert!(!io.in_transaction());
+        assert_eq!(io.io.outputs["a"], Value::Decimal(2.0));
+        assert_eq!(io.io.outputs["b"], Value::Bit(true));
+
+        io.begin();
+        io.write("a", &Value::Decimal(3.0)).unwrap();
+        io.rollback();
+        assert_eq!(io.read_output("a").unwrap(), Some(Value::Decimal(2.0)));
+    }
+
+    #[test]
+    fn restore_outputs_if_commit_fails() {
+        struct FailingIo {
+            state: IoState,
+            fail: &'static str,
+        }
+
+        impl SyncIoSystem for FailingIo {
+            fn read(&mut self, id: &str) -> Result<Value> {
+                self.state.read(id)
+            }
+            fn read_output(&mut self, id: &str) -> Result<Option<Value>> {
+                self.state.read_output(id)
+            }
+            fn write(&mut self, id: &str, value: &Value) -> Result<()> {
+                if id == self.fail {
+                    return Err(Error::new(ErrorKind::BrokenPipe, "connection lost"));
+                }
+                self.state.write(id, value)
+            }
+        }
+
+        let mut state = IoState::default();
+        state.outputs.insert("a".into(), Value::Decimal(1.0));
+        state.outputs.insert("b".into(), Value::Decimal(2.0));
+        let mut io = TransactionalIo::new(FailingIo { state, fail: "c" });
+        io.begin();
+        io.write("a", &Value::Decimal(10.0)).unwrap();
+        io.write("b", &Value::Decimal(20.0)).unwrap();
+        io.write("c", &Value::Decimal(30.0)).unwrap();
+        assert_eq!(io.commit().unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert!(!io.in_transaction());
+        assert_eq!(io.io.state.outputs["a"], Value::Decimal(1.0));
+        assert_eq!(io.io.state.outputs["b"], Value::Decimal(2.0));
+        assert!(!io.io.state.outputs.contains_key("c"));
+    }
+
+    #[test]
+    fn apply_writes_in_declared_order() {
+        #[derive(Default)]
+        struct RecordingIo {
+            writes: Vec<String>,
+        }
+
+        impl SyncIoSystem for RecordingIo {
+            fn read(&mut self, id: &str) -> Result<Value> {
+                Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("There is no input '{}'", id),
+                ))
+            }
+            fn read_output(&mut self, _: &str) -> Result<Option<Value>> {
+                Ok(None)
+            }
+            fn write(&mut self, id: &str, _: &Value) -> Result<()> {
+                if id == "broken" {
+                    return Err(Error::new(ErrorKind::BrokenPipe, "connection lost"));
+                }
+                self.writes.push(id.into());
+                Ok(())
+            }
+        }
+
+        let mut io = SequencedIo::new(RecordingIo::default())
+            .with_order("inlet", "drain")
+            .unwrap();
+        io.write("drain", &Value::Bit(true)).unwrap();
+        io.write("pump", &Value::Bit(true)).unwrap();
+        io.write("inlet", &Value::Bit(false)).unwrap();
+        assert_eq!(io.read_output("drain").unwrap(), Some(Value::Bit(true)));
+        assert_eq!(io.sequence(), vec!["pump", "inlet", "drain"]);
+        io.flush().unwrap();
+        assert_eq!(io.io.writes, vec!["pump", "inlet", "drain"]);
+
+        // only the drain
+        io.io.writes.clear();
+        io.write("drain", &Value::Bit(false)).unwrap();
+        io.flush().unwrap();
+        assert_eq!(io.io.writes, vec!["drain"]);
+
+        // a failed write stops the sequence
+        let mut io = io.with_order("broken", "inlet").unwrap();
+        io.io.writes.clear();
+        io.write("drain", &Value::Bit(true)).unwrap();
+        io.write("inlet", &Value::Bit(false)).unwrap();
+        io.write("broken", &Value::Bit(false)).unwrap();
+        assert!(io.flush().is_err());
+        assert!(io.io.writes.is_empty());
+        assert!(io.read("drain").is_err());
+    }
+
+    #[test]
+    fn reject_writes_in_wrong_order() {
+        let mut io = SequencedIo::new(IoState::default())
+            .with_order("inlet", "drain")
+            .unwrap()
+            .strict();
+        io.write("inlet", &Value::Bit(false)).unwrap();
+        io.write("drain", &Value::Bit(true)).unwrap();
+        io.write("drain", &Value::Bit(false)).unwrap();
+        io.flush().unwrap();
+        assert_eq!(io.io.outputs["drain"], Value::Bit(false));
+
+        io.write("drain", &Value::Bit(true)).unwrap();
+        assert!(io.write("inlet", &Value::Bit(true)).is_err());
+        assert_eq!(io.read_output("inlet").unwrap(), Some(Value::Bit(false)));
+    }
+
+    #[test]
+    fn reject_contradicting_write_orders() {
+        let io = SequencedIo::new(IoState::default())
+            .with_order("a", "b")
+            .unwrap()
+            .with_order("b", "c")
+            .unwrap();
+        assert!(io.clone().with_order("c", "a").is_err());
+        assert!(io.clone().with_order("a", "a").is_err());
+        assert!(io.with_order("a", "c").is_ok());
+    }
+
+    #[test]
+    fn trip_into_safe_state() {
+        let safe = SafeState::default()
+            .with_output("heater", false)
+            .with_output("valve", 0.0);
+        let mut io = TrippableIo::new(IoState::default(), safe);
+        io.write("heater", &Value::Bit(true)).unwrap();
+        io.write("valve", &Value::Decimal(75.0)).unwrap();
+        io.write("lamp", &Value::Bit(true)).unwrap();
+        assert!(!io.is_tripped());
+
+        io.trip().unwrap();
+        assert!(io.is_tripped());
+        assert_eq!(io.io.outputs["heater"], Value::Bit(false));
+        assert_eq!(io.io.outputs["valve"], Value::Decimal(0.0));
+        // outputs without a safe value are not touched
+        assert_eq!(io.io.outputs["lamp"], Value::Bit(true));
+
+        // the trip is latched
+        let err = io.write("valve", &Value::Decimal(50.0)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(io.write("lamp", &Value::Bit(false)).is_err());
+        assert_eq!(io.io.outputs["valve"], Value::Decimal(0.0));
+        assert_eq!(io.read_output("valve").unwrap(), Some(Value::Decimal(0.0)));
+
+        io.reset();
+        assert!(!io.is_tripped());
+        io.write("valve", &Value::Decimal(50.0)).unwrap();
+        assert_eq!(io.io.outputs["valve"], Value::Decimal(50.0));
+    }
+
+    #[test]
+    fn encode_and_decode_binary_frames() {
+        let layout = FrameLayout::new(vec![
+            Source::In("temp".into()),
+            Source::Out("valve".into()),
+            Source::Mem("count".into()),
+            Source::In("missing".into()),
+        ])
+        .unwrap();
+        assert_eq!(layout.frame_len(), 16);
+        let mut state = IoState::default();
+        state.inputs.insert("temp".into(), Value::Decimal(21.37));
+        state.inputs.insert("ignored".into(), Value::Decimal(1.0));
+        state
+            .outputs
+            .insert("valve".into(), Value::quantity(55.5, "%"));
+        state.mem.insert("count".into(), Value::Integer(42));
+        let frame = layout.encode(&state).unwrap();
+        assert_eq!(frame.len(), 16);
+        let decoded = layout.decode(&frame).unwrap();
+        let get = |id: &str, map: &HashMap<String, Value>| match map.get(id) {
+            Some(Value::Decimal(v)) => *v,
+            _ => panic!("missing {}", id),
+        };
+        assert!((get("temp", &decoded.inputs) - 21.37).abs() < 1e-5);
+        assert!((get("valve", &decoded.outputs) - 55.5).abs() < 1e-5);
+        assert!((get("count", &decoded.mem) - 42.0).abs() < 1e-5);
+        assert_eq!(decoded.inputs.len(), 1);
+
+        assert!(layout.decode(&frame[..12]).is_err());
+        state.inputs.insert("temp".into(), Value::Bit(true));
+        assert!(layout.encode(&state).is_err());
+        assert!(FrameLayout::new(vec![Source::Setpoint("x".into())]).is_err());
+    }
+
+    #[test]
+    fn scan_inputs_at_their_rates() {
+        #[derive(Default)]
+        struct CountingIo {
+            state: IoState,
+            reads: HashMap<String, usize>,
+        }
+
+        impl SyncIoSystem for CountingIo {
+            fn read(&mut self, id: &str) -> Result<Value> {
+                *self.reads.entry(id.into()).or_default() += 1;
+                self.state.read(id)
+            }
+            fn read_output(&mut self, id: &str) -> Result<Option<Value>> {
+                self.state.read_output(id)
+            }
+            fn write(&mut self, id: &str, value: &Value) -> Result<()> {
+                self.state.write(id, value)
+            }
+        }
+
+        let mut scanner = Scanner::default()
+            .with_input(
+                "fast",
+                ScanConfig {
+                    interval: Duration::from_secs(1),
+                    deadband: 0.0,
+                },
+            )
+            .with_input(
+                "slow",
+                ScanConfig {
+                    interval: Duration::from_secs(5),
+                    deadband: 1.0,
+                },
+            );
+        let mut io = CountingIo::default();
+        io.state.inputs.insert("fast".into(), Value::Decimal(1.0));
+        io.state.inputs.insert("slow".into(), Value::Decimal(20.0));
+        let dt = Duration::from_secs(1);
+
+        // everything is read and reported initially
+        let report = scanner.scan(&mut io, &dt);
+        assert!(report.errors.is_empty());
+        assert_eq!(
+            report.events,
+            vec![
+                Event::InputChanged {
+                    id: "fast".into(),
+                    old: None,
+                    new: Value::Decimal(1.0)
+                },
+                Event::InputChanged {
+                    id: "slow".into(),
+                    old: None,
+                    new: Value::Decimal(20.0)
+                },
+            ]
+        );
+
+        // the slow input is only read every 5 seconds
+        io.state.inputs.insert("slow".into(), Value::Decimal(20.5));
+        for _ in 0..4 {
+            assert!(scanner.scan(&mut io, &dt).events.is_empty());
+        }
+        assert_eq!(io.reads["fast"], 5);
+        assert_eq!(io.reads["slow"], 1);
+        // read but within the deadband
+        assert!(scanner.scan(&mut io, &dt).events.is_empty());
+        assert_eq!(io.reads["slow"], 2);
+        assert_eq!(scanner.reported("slow"), Some(&Value::Decimal(20.0)));
+
+        io.state.inputs.insert("slow".into(), Value::Decimal(21.5));
+        for _ in 0..4 {
+            assert!(scanner.scan(&mut io, &dt).events.is_empty());
+        }
+        // the change is compared to the reported value
+        assert_eq!(
+            scanner.scan(&mut io, &dt).events,
+            vec![Event::InputChanged {
+                id: "slow".into(),
+                old: Some(Value::Decimal(20.0)),
+                new: Value::Decimal(21.5)
+            }]
+        );
+        assert_eq!(io.reads["fast"], 11);
+        assert_eq!(io.reads["slow"], 3);
+
+        // a failed read doesn't hide the changes of other inputs
+        io.state.inputs.remove("fast");
+        io.state.inputs.insert("slow".into(), Value::Decimal(25.0));
+        for _ in 0..4 {
+            let report = scanner.scan(&mut io, &dt);
+            assert!(report.events.is_empty());
+            assert_eq!(report.errors.len(), 1);
+            assert_eq!(report.errors[0].0, "fast");
+        }
+        let report = scanner.scan(&mut io, &dt);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(
+            report.events,
+            vec![Event::InputChanged {
+                id: "slow".into(),
+                old: Some(Value::Decimal(21.5)),
+                new: Value::Decimal(25.0)
+            }]
+        );
+        assert_eq!(scanner.reported("slow"), Some(&Value::Decimal(25.0)));
+
+        // the input is read again with the next scan
+        io.state.inputs.insert("fast".into(), Value::Decimal(2.0));
+        let report = scanner.scan(&mut io, &dt);
+        assert!(report.errors.is_empty());
+        assert_eq!(
+            report.events,
+            vec![Event::InputChanged {
+                id: "fast".into(),
+                old: Some(Value::Decimal(1.0)),
+                new: Value::Decimal(2.0)
+            }]
+        );
+    }
+
+    #[test]
+    fn keep_the_scan_rate_independent_of_the_cycle_time() {
+        let mut scanner = Scanner::default().with_input(
+            "x",
+            ScanConfig {
+                interval: Duration::from_millis(100),
+                deadband: 0.0,
+            },
+        );
+        let mut io = IoState::default();
+        let dt = Duration::from_millis(30);
+        let mut reads = vec![];
+        for cycle in 0..15 {
+            io.inputs.insert("x".into(), Value::Integer(cycle));
+            if !scanner.scan(&mut io, &dt).events.is_empty() {
+                reads.push(cycle);
+            }
+        }
+        // at 0, 120, 210, 300 and 420 ms
+        assert_eq!(reads, vec![0, 4, 7, 10, 14]);
+    }
+}
@@ -6,21 +6,28 @@ extern crate serde;
 extern crate serde_json;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::{Error, ErrorKind, Result},
     ops::Not,
     time::Duration,
 };
 
 mod comparison;
+mod condition;
 mod entities;
+mod event;
 pub mod fsm;
+mod io_system;
 mod parser;
 mod runtime;
+mod store;
 pub mod util;
 mod value;
 
-pub use self::{comparison::*, entities::*, runtime::*, value::*};
+pub use self::{
+    comparison::*, condition::*, entities::*, event::*, io_system::*, runtime::*, store::*,
+    value::*,
+};
 
 /// PID controller
 pub mod pid;
@@ -28,6 +35,48 @@ pub mod pid;
 /// Bang-bang controller
 pub mod bang_bang;
 
+/// Discrete actuator commands
+pub mod command;
+
+/// Rate limiter
+pub mod rate_limiter;
+
+/// Process simulation
+pub mod sim;
+
+/// Sensor health monitoring
+pub mod sensor_health;
+
+/// Output cross-fading
+pub mod crossfade;
+
+/// Alarm handling
+pub mod alarm;
+
+/// Setpoint schedules and supervisory optimization
+pub mod schedule;
+
+/// Sliding window statistics
+pub mod window;
+
+/// Signal filters
+pub mod filter;
+
+/// Controller operating modes
+pub mod mode;
+
+/// Flow control
+pub mod flow;
+
+/// Multi-variable decoupling
+pub mod decoupling;
+
+/// Signal selection
+pub mod select;
+
+/// Regression testing of tuned loops
+pub mod testing;
+
 /// A generic stateful controller
 pub trait Controller<Input, Output> {
     /// Calculate the next state.
@@ -55,6 +104,57 @@ where
     }
 }
 
+/// A controller that can describe itself,
+/// e.g. for a generic HMI or a configuration dump.
+pub trait Introspect {
+    /// Describe the current parameters and the internal state.
+    fn introspect(&self) -> Introspection;
+}
+
+/// A controller that can resume from the current operating point,
+/// e.g. after a manual operation.
+pub trait WarmStart {
+    /// Initialize the internal state from the `actual` value
+    /// and the present `output`, so the next output doesn't bump.
+    fn warm_start(&mut self, actual: f64, output: f64);
+}
+
+/// A structured description of a controller
+///
+/// Optional parameters or state variables
+/// without a value are omitted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Introspection {
+    /// The tunable parameters
+    pub parameters: Vec<(String, Value)>,
+    /// The internal state
+    pub state: Vec<(String, Value)>,
+}
+
+impl Introspection {
+    /// Add a parameter.
+    pub fn with_parameter<S: Into<String>, V: Into<Value>>(mut self, name: S, value: V) -> Self {
+        self.parameters.push((name.into(), value.into()));
+        self
+    }
+    /// Add a state variable.
+    pub fn with_state<S: Into<String>, V: Into<Value>>(mut self, name: S, value: V) -> Self {
+        self.state.push((name.into(), value.into()));
+        self
+    }
+    /// Get the value of a parameter.
+    pub fn parameter(&self, name: &str) -> Option<&Value> {
+        self.parameters
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v)
+    }
+    /// Get the value of a state variable.
+    pub fn state_variable(&self, name: &str) -> Option<&Value> {
+        self.state.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+}
+
 /// An I/O system with synchronous fieldbus access
 pub trait SyncIoSystem {
     /// Read the current state of an input.
@@ -66,6 +166,8 @@ pub trait SyncIoSystem {
 }
 
 /// Controller type
+// Keep the variants unboxed, so the enum stays easy to construct and match.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum ControllerType {
     Pid(pid::Pid),
@@ -73,6 +175,8 @@ pub enum ControllerType {
 }
 
 /// Controller configuration
+// Configurations are created once, so their size doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ControllerConfig {
@@ -87,6 +191,15 @@ pub enum ControllerState {
     BangBang(bang_bang::BangBangState),
 }
 
+impl Introspect for ControllerState {
+    fn introspect(&self) -> Introspection {
+        match self {
+            ControllerState::Pid(s) => s.introspect(),
+            ControllerState::BangBang(s) => s.introspect(),
+        }
+    }
+}
+
 impl<'a>
     PureController<
         (&'a ControllerState, &'a IoState, &'a Duration),
@@ -107,14 +220,19 @@ impl<'a>
 
         let input_id = &self.inputs[0];
 
-        if let Some(Value::Decimal(v)) = io.inputs.get(input_id) {
+        // Quantities are accepted regardless of their unit.
+        let actual = io
+            .inputs
+            .get(input_id)
+            .and_then(|v| v.to_decimal(BitEncoding::Strict).ok());
+        if let Some(v) = actual {
             let mut io = io.clone();
             let output_id = self.outputs[0].clone();
 
             match self.controller {
                 ControllerConfig::Pid(ref cfg) => match controller {
                     ControllerState::Pid(s) => {
-                        let (pid_state, y) = cfg.next((*s, *v, dt));
+                        let (pid_state, y) = cfg.next((*s, v, dt));
                         io.outputs.insert(output_id, y.into());
                         let controller = ControllerState::Pid(pid_state);
                         Ok((controller, io))
@@ -126,7 +244,7 @@ impl<'a>
                 },
                 ControllerConfig::BangBang(ref cfg) => match controller {
                     ControllerState::BangBang(s) => {
-                        let bb_state = cfg.next((*s, *v));
+                        let bb_state = cfg.next((*s, v));
                         io.outputs.insert(output_id, bb_state.current.into());
                         let controller = ControllerState::BangBang(bb_state);
                         Ok((controller, io))
@@ -140,7 +258,7 @@ impl<'a>
         } else {
             Err(Error::new(
                 ErrorKind::InvalidData,
-                "Invalid input data type: a decimal value is required",
+                "Invalid input data type: a numeric value is required",
             ))
         }
     }
@@ -168,6 +286,7 @@ impl<'a>
 /// }
 /// ```
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct IoState {
     /// Input gates (sensors)
     pub inputs: HashMap<String, Value>,
@@ -198,6 +317,9 @@ pub struct SystemState {
 
 impl SystemState {
     /// Get a specific value defined by a [Source].
+    ///
+    /// The state variables of controllers are not stored as values
+    /// (see [SystemState::get_controller_var]).
     pub fn get<'a>(&'a self, src: &'a Source) -> Option<&'a Value> {
         use crate::Source::*;
         match src {
@@ -207,8 +329,17 @@ impl SystemState {
             Timeout(id) => self.timeouts.get(id),
             Const(v) => Some(v),
             Setpoint(id) => self.setpoints.get(id),
+            ControllerVar(_, _) => None,
         }
     }
+    /// Get a state variable of a controller (see [Introspect]).
+    pub fn get_controller_var(&self, id: &str, var: &str) -> Option<Value> {
+        self.controllers
+            .get(id)?
+            .introspect()
+            .state_variable(var)
+            .cloned()
+    }
 }
 
 impl Default for SystemState {
@@ -235,6 +366,252 @@ impl Default for IoState {
     }
 }
 
+impl IoState {
+    /// A read-only view of all values whose IDs start with `prefix`,
+    /// e.g. to present the state of a subsystem.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use msr::*;
+    ///
+    /// let state = IoState::default();
+    /// for (id, value) in state.view("boiler.").inputs() {
+    ///     println!("{}: {:?}", id, value);
+    /// }
+    /// ```
+    pub fn view<'a>(&'a self, prefix: &'a str) -> IoStateView<'a> {
+        IoStateView {
+            state: self,
+            prefix,
+        }
+    }
+    /// Compare all points with an `expected` state,
+    /// e.g. the state of a simulation.
+    pub fn deviations(&self, expected: &IoState) -> DeviationReport {
+        let mut points = vec![];
+        deviations(&self.inputs, &expected.inputs, Source::In, &mut points);
+        deviations(&self.outputs, &expected.outputs, Source::Out, &mut points);
+        deviations(&self.mem, &expected.mem, Source::Mem, &mut points);
+        DeviationReport { points }
+    }
+    /// The IDs of all values that differ from a `previous` state,
+    /// including added and removed values.
+    pub fn diff(&self, previous: &IoState) -> IoStateDiff {
+        IoStateDiff {
+            inputs: diff_ids(&self.inputs, &previous.inputs),
+            outputs: diff_ids(&self.outputs, &previous.outputs),
+            mem: diff_ids(&self.mem, &previous.mem),
+        }
+    }
+}
+
+/// The IDs of the values that changed between two [IoState]s
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IoStateDiff {
+    /// Changed inputs
+    pub inputs: HashSet<String>,
+    /// Changed outputs
+    pub outputs: HashSet<String>,
+    /// Changed memory values
+    pub mem: HashSet<String>,
+}
+
+impl IoStateDiff {
+    /// Check if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty() && self.outputs.is_empty() && self.mem.is_empty()
+    }
+    /// Check if the value of a source might have changed.
+    ///
+    /// Constants never change. Sources that are not part of
+    /// the I/O state (e.g. setpoints) are always considered as changed.
+    pub fn affects(&self, src: &Source) -> bool {
+        match src {
+            Source::In(id) => self.inputs.contains(id),
+            Source::Out(id) => self.outputs.contains(id),
+            Source::Mem(id) => self.mem.contains(id),
+            Source::Const(_) => false,
+            Source::Setpoint(_) | Source::Timeout(_) | Source::ControllerVar(_, _) => true,
+        }
+    }
+}
+
+fn diff_ids(
+    current: &HashMap<String, Value>,
+    previous: &HashMap<String, Value>,
+) -> HashSet<String> {
+    let changed = current
+        .iter()
+        .filter(|(id, v)| previous.get(*id) != Some(v));
+    let removed = previous.keys().filter(|id| !current.contains_key(*id));
+    changed.map(|(id, _)| id).chain(removed).cloned().collect()
+}
+
+/// The deviation of a single point between two [IoState]s
+#[derive(Debug, Clone, PartialEq)]
+pub enum PointDeviation {
+    /// Both values are numeric
+    Numeric {
+        /// The signed deviation (`actual - expected`)
+        deviation: f64,
+        /// The deviation in percent of the expected value
+        /// (`None` if the expected value is zero)
+        percent: Option<f64>,
+    },
+    /// Non-numeric values that are equal
+    Equal,
+    /// Non-numeric values (or quantities with different units)
+    /// that are not equal
+    Unequal,
+    /// The point only exists in one of the states
+    Missing,
+}
+
+impl PointDeviation {
+    fn new(expected: Option<&Value>, actual: Option<&Value>) -> Self {
+        let (expected, actual) = match (expected, actual) {
+            (Some(e), Some(a)) => (e, a),
+            _ => return PointDeviation::Missing,
+        };
+        if expected.unit() != actual.unit() {
+            return PointDeviation::Unequal;
+        }
+        match (
+            expected.to_decimal(BitEncoding::Strict),
+            actual.to_decimal(BitEncoding::Strict),
+        ) {
+            (Ok(e), Ok(a)) => {
+                let deviation = a - e;
+                let percent = if e == 0.0 {
+                    None
+                } else {
+                    Some(100.0 * deviation / e.abs())
+                };
+                PointDeviation::Numeric { deviation, percent }
+            }
+            _ if expected == actual => PointDeviation::Equal,
+            _ => PointDeviation::Unequal,
+        }
+    }
+    /// Check if the deviation exceeds a tolerance (in percent).
+    ///
+    /// Numeric deviations from zero always exceed the tolerance.
+    pub fn exceeds(&self, tolerance: f64) -> bool {
+        match self {
+            PointDeviation::Numeric {
+                percent: Some(p), ..
+            } => p.abs() > tolerance,
+            PointDeviation::Numeric { deviation, .. } => *deviation != 0.0,
+            PointDeviation::Equal => false,
+            PointDeviation::Unequal | PointDeviation::Missing => true,
+        }
+    }
+}
+
+/// A point-by-point comparison of two [IoState]s
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let mut simulated = IoState::default();
+/// simulated.inputs.insert("temp".into(), 60.0.into());
+/// let mut live = IoState::default();
+/// live.inputs.insert("temp".into(), 66.0.into());
+///
+/// let report = live.deviations(&simulated);
+/// let drifting = report.exceeding(5.0);
+/// assert_eq!(drifting, vec![&Source::In("temp".into())]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DeviationReport {
+    /// The deviation of each point
+    /// (inputs, outputs and memory values sorted by their IDs)
+    pub points: Vec<(Source, PointDeviation)>,
+}
+
+impl DeviationReport {
+    /// The deviation of a single point.
+    pub fn get(&self, src: &Source) -> Option<&PointDeviation> {
+        self.points.iter().find(|(s, _)| s == src).map(|(_, d)| d)
+    }
+    /// The points that exceed a tolerance (in percent).
+    pub fn exceeding(&self, tolerance: f64) -> Vec<&Source> {
+        self.points
+            .iter()
+            .filter(|(_, d)| d.exceeds(tolerance))
+            .map(|(s, _)| s)
+            .collect()
+    }
+}
+
+fn deviations<F>(
+    actual: &HashMap<String, Value>,
+    expected: &HashMap<String, Value>,
+    src: F,
+    points: &mut Vec<(Source, PointDeviation)>,
+) where
+    F: Fn(String) -> Source,
+{
+    let mut ids: Vec<_> = actual.keys().chain(expected.keys()).collect();
+    ids.sort();
+    ids.dedup();
+    for id in ids {
+        let deviation = PointDeviation::new(expected.get(id), actual.get(id));
+        points.push((src(id.clone()), deviation));
+    }
+}
+
+/// A filtered, read-only view of an [IoState]
+///
+/// The values are not copied.
+#[derive(Debug, Clone, Copy)]
+pub struct IoStateView<'a> {
+    state: &'a IoState,
+    prefix: &'a str,
+}
+
+impl<'a> IoStateView<'a> {
+    /// The prefix of all IDs within this view.
+    pub fn prefix(&self) -> &'a str {
+        self.prefix
+    }
+    /// The matching inputs.
+    pub fn inputs(&self) -> impl Iterator<Item = (&'a str, &'a Value)> {
+        filter_prefix(&self.state.inputs, self.prefix)
+    }
+    /// The matching outputs.
+    pub fn outputs(&self) -> impl Iterator<Item = (&'a str, &'a Value)> {
+        filter_prefix(&self.state.outputs, self.prefix)
+    }
+    /// The matching memory values.
+    pub fn mem(&self) -> impl Iterator<Item = (&'a str, &'a Value)> {
+        filter_prefix(&self.state.mem, self.prefix)
+    }
+    /// Get a matching input.
+    pub fn input(&self, id: &str) -> Option<&'a Value> {
+        id.starts_with(self.prefix)
+            .then(|| self.state.inputs.get(id))
+            .flatten()
+    }
+    /// Get a matching output.
+    pub fn output(&self, id: &str) -> Option<&'a Value> {
+        id.starts_with(self.prefix)
+            .then(|| self.state.outputs.get(id))
+            .flatten()
+    }
+}
+
+fn filter_prefix<'a>(
+    values: &'a HashMap<String, Value>,
+    prefix: &'a str,
+) -> impl Iterator<Item = (&'a str, &'a Value)> {
+    values
+        .iter()
+        .filter(move |(id, _)| id.starts_with(prefix))
+        .map(|(id, v)| (id.as_str(), v))
+}
+
 impl SyncIoSystem for IoState {
     fn read(&mut self, id: &str) -> Result<Value> {
         Ok(self
@@ -263,6 +640,10 @@ pub enum Source {
     Setpoint(String),
     Timeout(String),
     Const(Value),
+    /// A state variable of a controller, e.g. `ControllerVar("pid".into(), "i".into())`
+    ///
+    /// See [Introspect] for the available variables.
+    ControllerVar(String, String),
 }
 
 impl Source {
@@ -284,11 +665,43 @@ impl Source {
     pub fn cmp_gt(self, right: Source) -> Comparison {
         self.cmp(right, Comparator::Greater)
     }
+    /// Compare numbers with an absolute tolerance.
+    pub fn approx_eq(self, right: Source, tolerance: f64) -> Comparison {
+        Comparison {
+            tolerance: Tolerance::Absolute(tolerance),
+            ..self.cmp(right, Comparator::ApproxEqual)
+        }
+    }
+    /// Compare numbers with a tolerance in percent of the reading
+    /// (the left hand side).
+    pub fn approx_eq_pct(self, right: Source, pct: f64) -> Comparison {
+        Comparison {
+            tolerance: Tolerance::Percent(pct),
+            ..self.cmp(right, Comparator::ApproxEqual)
+        }
+    }
+    /// Check if the value equals one of the given values,
+    /// e.g. to check categorical values like a mode.
+    ///
+    /// The resulting expression is `false` for an empty set.
+    /// Comparing values of different types results in an error.
+    pub fn is_one_of<I>(self, set: I) -> BoolExpr<Comparison>
+    where
+        I: IntoIterator,
+        I::Item: Into<Value>,
+    {
+        set.into_iter()
+            .map(|v| BoolExpr::Eval(self.clone().cmp_eq(Source::Const(v.into()))))
+            .reduce(|a, b| BoolExpr::Or(Box::new(a), Box::new(b)))
+            .unwrap_or(BoolExpr::False)
+    }
     fn cmp(self, right: Source, cmp: Comparator) -> Comparison {
         Comparison {
             left: self,
             cmp,
             right,
+            encoding: BitEncoding::Strict,
+            tolerance: Tolerance::default(),
         }
     }
 }
@@ -318,9 +731,32 @@ pub trait Evaluation<In> {
     fn eval(&self, input: &In) -> Result<Self::Output>;
 }
 
+/// Allows to mix different kinds of evaluations,
+/// e.g. within a `BoolExpr<Box<dyn Evaluation<IoState, Output = bool>>>`.
+impl<In, T> Evaluation<In> for Box<T>
+where
+    T: Evaluation<In> + ?Sized,
+{
+    type Output = T::Output;
+    fn eval(&self, input: &In) -> Result<Self::Output> {
+        (**self).eval(input)
+    }
+}
+
 /// Extract sources
 pub trait Sources {
     fn sources(&self) -> Vec<Source>;
+    /// The sources that refer to a value (i.e. no constants),
+    /// each listed once.
+    fn referenced_ids(&self) -> Vec<Source> {
+        let mut ids: Vec<Source> = vec![];
+        for src in self.sources() {
+            if !matches!(src, Source::Const(_)) && !ids.contains(&src) {
+                ids.push(src);
+            }
+        }
+        ids
+    }
 }
 
 impl Sources for BoolExpr<Comparison> {
@@ -339,12 +775,12 @@ impl Sources for BoolExpr<Comparison> {
     }
 }
 
-impl<T> Evaluation<SystemState> for BoolExpr<T>
+impl<S, T> Evaluation<S> for BoolExpr<T>
 where
-    T: Evaluation<SystemState, Output = bool>,
+    T: Evaluation<S, Output = bool>,
 {
     type Output = bool;
-    fn eval(&self, state: &SystemState) -> Result<Self::Output> {
+    fn eval(&self, state: &S) -> Result<Self::Output> {
         use crate::BoolExpr::*;
         match self {
             True => Ok(true),
@@ -396,6 +832,43 @@ mod tests {
         assert_eq!(io.read("foo").unwrap(), Value::Bit(true));
     }
 
+    #[test]
+    fn view_io_state_by_prefix() {
+        let mut state = IoState::default();
+        state
+            .inputs
+            .insert("boiler.temp".into(), Value::Decimal(80.0));
+        state
+            .inputs
+            .insert("boiler.pressure".into(), Value::Decimal(1.2));
+        state
+            .inputs
+            .insert("pump.speed".into(), Value::Decimal(900.0));
+        state
+            .outputs
+            .insert("boiler.burner".into(), Value::Bit(true));
+        state.outputs.insert("pump.run".into(), Value::Bit(true));
+        state
+            .mem
+            .insert("boiler.mode".into(), Value::Text("auto".into()));
+
+        let view = state.view("boiler.");
+        assert_eq!(view.prefix(), "boiler.");
+        let mut inputs: Vec<_> = view.inputs().map(|(id, _)| id).collect();
+        inputs.sort_unstable();
+        assert_eq!(inputs, vec!["boiler.pressure", "boiler.temp"]);
+        let outputs: Vec<_> = view.outputs().collect();
+        assert_eq!(outputs, vec![("boiler.burner", &Value::Bit(true))]);
+        assert_eq!(view.mem().count(), 1);
+
+        assert_eq!(view.input("boiler.temp"), Some(&Value::Decimal(80.0)));
+        assert_eq!(view.input("pump.speed"), None);
+        assert_eq!(view.output("pump.run"), None);
+
+        assert_eq!(state.view("").inputs().count(), 3);
+        assert_eq!(state.view("tank.").outputs().count(), 0);
+    }
+
     #[test]
     fn bool_expr_eval() {
         use crate::BoolExpr::*;
@@ -466,6 +939,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn source_is_one_of() {
+        use crate::Source::*;
+
+        let mut state = IoState::default();
+        let mode =
+            In("mode".into()).is_one_of(["heat", "cool", "auto"].iter().map(|m| m.to_string()));
+        state
+            .inputs
+            .insert("mode".into(), Value::Text("cool".into()));
+        assert!(mode.eval(&state).unwrap());
+        state
+            .inputs
+            .insert("mode".into(), Value::Text("auto".into()));
+        assert!(mode.eval(&state).unwrap());
+        state
+            .inputs
+            .insert("mode".into(), Value::Text("off".into()));
+        assert!(!mode.eval(&state).unwrap());
+        state.inputs.insert("mode".into(), Value::Integer(1));
+        assert!(mode.eval(&state).is_err());
+
+        let level = In("level".into()).is_one_of(vec![1, 2, 3]);
+        state.inputs.insert("level".into(), Value::Integer(2));
+        assert!(level.eval(&state).unwrap());
+        state.inputs.insert("level".into(), Value::Integer(4));
+        assert!(!level.eval(&state).unwrap());
+
+        let none = In("level".into()).is_one_of(Vec::<Value>::new());
+        assert!(!none.eval(&state).unwrap());
+    }
+
     #[test]
     fn bool_expr_from_comparison() {
         use crate::Source::*;
@@ -548,4 +1053,71 @@ mod tests {
         loop0.outputs = vec!["output".into()];
         assert!(loop0.next((&controller, &io, &dt)).is_ok());
     }
+
+    #[test]
+    fn report_deviations_between_states() {
+        let mut expected = IoState::default();
+        expected.inputs.insert("temp".into(), Value::Decimal(80.0));
+        expected.inputs.insert("level".into(), Value::Decimal(2.0));
+        expected.outputs.insert("pump".into(), Value::Bit(true));
+        expected.mem.insert("zero".into(), Value::Decimal(0.0));
+        expected
+            .mem
+            .insert("flow".into(), Value::quantity(1.0, "l/s"));
+
+        let mut actual = expected.clone();
+        actual.inputs.insert("temp".into(), Value::Decimal(70.0));
+        actual.outputs.insert("valve".into(), Value::Decimal(0.0));
+        actual.mem.insert("zero".into(), Value::Decimal(0.5));
+        actual
+            .mem
+            .insert("flow".into(), Value::quantity(1.0, "m3/h"));
+
+        let report = actual.deviations(&expected);
+        assert_eq!(report.points.len(), 6);
+        assert_eq!(
+            report.get(&Source::In("temp".into())),
+            Some(&PointDeviation::Numeric {
+                deviation: -10.0,
+                percent: Some(-12.5)
+            })
+        );
+        assert_eq!(
+            report.get(&Source::In("level".into())),
+            Some(&PointDeviation::Numeric {
+                deviation: 0.0,
+                percent: Some(0.0)
+            })
+        );
+        assert_eq!(
+            report.get(&Source::Out("pump".into())),
+            Some(&PointDeviation::Equal)
+        );
+        assert_eq!(
+            report.get(&Source::Out("valve".into())),
+            Some(&PointDeviation::Missing)
+        );
+        assert_eq!(
+            report.get(&Source::Mem("zero".into())),
+            Some(&PointDeviation::Numeric {
+                deviation: 0.5,
+                percent: None
+            })
+        );
+        assert_eq!(
+            report.get(&Source::Mem("flow".into())),
+            Some(&PointDeviation::Unequal)
+        );
+        assert_eq!(
+            report.exceeding(10.0),
+            vec![
+                &Source::In("temp".into()),
+                &Source::Out("valve".into()),
+                &Source::Mem("flow".into()),
+                &Source::Mem("zero".into()),
+            ]
+        );
+        assert!(report.exceeding(15.0).len() == 3);
+        assert!(actual.deviations(&actual).exceeding(0.0).is_empty());
+    }
 }
@@ -0,0 +1,339 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{Controller, IoState, Value, mode::*, pid::*};
+//! use std::time::Duration;
+//!
+//! let pid = Pid::new(PidConfig::default());
+//! let mut ctl = ModeSwitch::new(pid, ModeSwitchConfig::new("tc1"));
+//! let mut io = IoState::default();
+//!
+//! // the HMI switches to manual mode
+//! io.mem.insert("tc1.mode".into(), Value::Text("manual".into()));
+//! io.mem.insert("tc1.manual".into(), Value::Decimal(35.0));
+//!
+//! let dt = Duration::from_secs(1);
+//! let output = ctl.next(((20.0, &dt), &mut io)).unwrap();
+//! assert_eq!(output, Some(35.0));
+//! ```
+
+use super::{BitEncoding, Controller, IoState, Value, WarmStart};
+use crate::crossfade::{CrossFade, CrossFadeConfig};
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+    str::FromStr,
+    time::Duration,
+};
+
+/// The operating mode of a controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Mode {
+    /// The output is calculated by the controller
+    #[default]
+    Auto,
+    /// The output is set by an operator
+    Manual,
+    /// The output follows an external value
+    Tracking,
+    /// There is no output
+    Disabled,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Mode::Auto => "auto",
+            Mode::Manual => "manual",
+            Mode::Tracking => "tracking",
+            Mode::Disabled => "disabled",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Mode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Mode::Auto),
+            "manual" => Ok(Mode::Manual),
+            "tracking" => Ok(Mode::Tracking),
+            "disabled" => Ok(Mode::Disabled),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid controller mode '{}'", s),
+            )),
+        }
+    }
+}
+
+/// The points of a [ModeSwitch] within the memory of an [IoState]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ModeSwitchConfig {
+    /// The (text) point of the mode
+    pub mode: String,
+    /// The (decimal) point of the manual output
+    pub manual: String,
+    /// The input that is followed in [Mode::Tracking]
+    pub tracking: Option<String>,
+}
+
+impl ModeSwitchConfig {
+    /// Use the points `<id>.mode` and `<id>.manual`.
+    pub fn new(id: &str) -> Self {
+        ModeSwitchConfig {
+            mode: format!("{}.mode", id),
+            manual: format!("{}.manual", id),
+            tracking: None,
+        }
+    }
+}
+
+/// A controller wrapper with an operating mode
+///
+/// The mode is read from a memory point in each cycle, so it can be
+/// displayed and commanded (e.g. from an HMI) like any other value.
+/// If the point doesn't exist, the current mode is written to it.
+///
+/// Outside of manual mode the output is written to the manual point,
+/// so switching to manual mode holds the last output (bumpless).
+/// The wrapped controller only runs in [Mode::Auto]
+/// and resumes from the last output (see [WarmStart]).
+/// Optionally the output cross-fades from the last output
+/// on each mode change to eliminate any residual bump.
+#[derive(Debug, Clone)]
+pub struct ModeSwitch<C> {
+    /// The wrapped controller
+    pub controller: C,
+    cfg: ModeSwitchConfig,
+    mode: Mode,
+    fade: Option<CrossFade>,
+    output: Option<f64>,
+}
+
+impl<C> ModeSwitch<C> {
+    /// Wrap a controller (in [Mode::Auto]).
+    pub fn new(controller: C, cfg: ModeSwitchConfig) -> Self {
+        ModeSwitch {
+            controller,
+            cfg,
+            mode: Mode::default(),
+            fade: None,
+            output: None,
+        }
+    }
+    /// Cross-fade the output on mode changes.
+    pub fn with_cross_fade(mut self, cfg: CrossFadeConfig) -> Self {
+        self.fade = Some(CrossFade::new(cfg));
+        self
+    }
+    /// The current mode
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+    fn read_mode(&self, io: &IoState) -> Result<Mode> {
+        match io.mem.get(&self.cfg.mode) {
+            Some(Value::Text(mode)) => mode.parse(),
+            Some(v) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid controller mode {:?}", v),
+            )),
+            None => Ok(self.mode),
+        }
+    }
+}
+
+impl<C> Controller<((f64, &Duration), &mut IoState), Result<Option<f64>>> for ModeSwitch<C>
+where
+    C: for<'a> Controller<(f64, &'a Duration), f64> + WarmStart,
+{
+    fn next(&mut self, input: ((f64, &Duration), &mut IoState)) -> Result<Option<f64>> {
+        let ((actual, duration), io) = input;
+        let prev_mode = self.mode;
+        self.mode = self.read_mode(io)?;
+        io.mem
+            .insert(self.cfg.mode.clone(), Value::Text(self.mode.to_string()));
+        let output = match self.mode {
+            Mode::Auto => {
+                if prev_mode != Mode::Auto {
+                    if let Some(last) = self.output {
+                        self.controller.warm_start(actual, last);
+                    }
+                }
+                Some(self.controller.next((actual, duration)))
+            }
+            Mode::Manual => match io.mem.get(&self.cfg.manual) {
+                Some(v) => Some(v.to_decimal(BitEncoding::Strict).map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Invalid manual output {:?}", v),
+                    )
+                })?),
+                None => None,
+            },
+            Mode::Tracking => {
+                let id =
+                    self.cfg.tracking.as_ref().ok_or_else(|| {
+                        Error::new(ErrorKind::NotFound, "There is no tracking input")
+                    })?;
+                let v = io
+                    .inputs
+                    .get(id)
+                    .and_then(|v| v.to_decimal(BitEncoding::Strict).ok())
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidData,
+                            format!("The tracking input '{}' has no numeric value", id),
+                        )
+                    })?;
+                Some(v)
+            }
+            Mode::Disabled => None,
+        };
+        let output = match (&mut self.fade, output) {
+            (Some(fade), Some(v)) => {
+                if self.mode != prev_mode {
+                    if let Some(last) = self.output {
+                        fade.start(last);
+                    }
+                }
+                Some(fade.next((v, duration)))
+            }
+            _ => output,
+        };
+        self.output = output;
+        if self.mode != Mode::Manual {
+            if let Some(v) = output {
+                io.mem.insert(self.cfg.manual.clone(), Value::Decimal(v));
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::pid::*;
+
+    #[test]
+    fn parse_and_print_modes() {
+        for mode in [Mode::Auto, Mode::Manual, Mode::Tracking, Mode::Disabled] {
+            assert_eq!(mode.to_string().parse::<Mode>().unwrap(), mode);
+        }
+        assert_eq!(" Manual".parse::<Mode>().unwrap(), Mode::Manual);
+        assert!("foo".parse::<Mode>().is_err());
+    }
+
+    #[test]
+    fn command_mode_via_points() {
+        let mut pid = Pid::new(PidConfig::default());
+        pid.set_target(10.0);
+        let mut cfg = ModeSwitchConfig::new("tc1");
+        cfg.tracking = Some("valve.position".into());
+        let mut ctl = ModeSwitch::new(pid, cfg);
+        let mut io = IoState::default();
+        let dt = Duration::from_secs(1);
+
+        assert_eq!(ctl.next(((4.0, &dt), &mut io)).unwrap(), Some(6.0));
+        assert_eq!(io.mem["tc1.mode"], Value::Text("auto".into()));
+        assert_eq!(io.mem["tc1.manual"], Value::Decimal(6.0));
+
+        // switch to manual
+        io.mem
+            .insert("tc1.mode".into(), Value::Text("manual".into()));
+        assert_eq!(ctl.next(((0.0, &dt), &mut io)).unwrap(), Some(6.0));
+        assert_eq!(ctl.mode(), Mode::Manual);
+        io.mem.insert("tc1.manual".into(), Value::Decimal(42.0));
+        assert_eq!(ctl.next(((0.0, &dt), &mut io)).unwrap(), Some(42.0));
+        assert_eq!(ctl.next(((9.0, &dt), &mut io)).unwrap(), Some(42.0));
+        // the controller didn't run
+        assert_eq!(ctl.controller.state.prev_value, Some(4.0));
+
+        io.mem
+            .insert("tc1.mode".into(), Value::Text("tracking".into()));
+        io.inputs
+            .insert("valve.position".into(), Value::Decimal(17.0));
+        assert_eq!(ctl.next(((9.0, &dt), &mut io)).unwrap(), Some(17.0));
+        assert_eq!(io.mem["tc1.manual"], Value::Decimal(17.0));
+
+        io.mem
+            .insert("tc1.mode".into(), Value::Text("disabled".into()));
+        assert_eq!(ctl.next(((9.0, &dt), &mut io)).unwrap(), None);
+
+        io.mem.insert("tc1.mode".into(), Value::Text("foo".into()));
+        assert!(ctl.next(((9.0, &dt), &mut io)).is_err());
+        assert_eq!(ctl.mode(), Mode::Disabled);
+    }
+
+    #[test]
+    fn cross_fade_on_mode_changes() {
+        let mut pid = Pid::new(PidConfig::default());
+        pid.set_target(10.0);
+        let mut cfg = ModeSwitchConfig::new("tc1");
+        cfg.tracking = Some("valve.position".into());
+        let mut ctl = ModeSwitch::new(pid, cfg).with_cross_fade(CrossFadeConfig {
+            duration: Duration::from_secs(4),
+            ..Default::default()
+        });
+        let mut io = IoState::default();
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next(((4.0, &dt), &mut io)).unwrap(), Some(6.0));
+
+        io.mem
+            .insert("tc1.mode".into(), Value::Text("tracking".into()));
+        io.inputs
+            .insert("valve.position".into(), Value::Decimal(18.0));
+        for expected in [9.0, 12.0, 15.0, 18.0, 18.0] {
+            assert_eq!(ctl.next(((4.0, &dt), &mut io)).unwrap(), Some(expected));
+        }
+
+        // back to auto the controller resumes from the tracked output
+        io.mem.insert("tc1.mode".into(), Value::Text("auto".into()));
+        assert_eq!(ctl.next(((4.0, &dt), &mut io)).unwrap(), Some(18.0));
+        assert_eq!(ctl.next(((4.0, &dt), &mut io)).unwrap(), Some(18.0));
+        assert_eq!(io.mem["tc1.manual"], Value::Decimal(18.0));
+    }
+
+    #[test]
+    fn resume_from_numeric_manual_and_tracking_values() {
+        let mut pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            k_i: 0.5,
+            ..Default::default()
+        });
+        pid.set_target(10.0);
+        let mut cfg = ModeSwitchConfig::new("tc1");
+        cfg.tracking = Some("valve.position".into());
+        let mut ctl = ModeSwitch::new(pid, cfg);
+        let mut io = IoState::default();
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next(((4.0, &dt), &mut io)).unwrap(), Some(9.0));
+
+        io.mem
+            .insert("tc1.mode".into(), Value::Text("manual".into()));
+        io.mem
+            .insert("tc1.manual".into(), Value::Quantity(40.0, "%".into()));
+        assert_eq!(ctl.next(((10.0, &dt), &mut io)).unwrap(), Some(40.0));
+
+        // bumpless transfer at zero error
+        io.mem.insert("tc1.mode".into(), Value::Text("auto".into()));
+        for _ in 0..3 {
+            assert_eq!(ctl.next(((10.0, &dt), &mut io)).unwrap(), Some(40.0));
+        }
+
+        io.mem
+            .insert("tc1.mode".into(), Value::Text("tracking".into()));
+        io.inputs
+            .insert("valve.position".into(), Value::Integer(25));
+        assert_eq!(ctl.next(((10.0, &dt), &mut io)).unwrap(), Some(25.0));
+        io.inputs.insert("valve.position".into(), Value::Bit(true));
+        assert!(ctl.next(((10.0, &dt), &mut io)).is_err());
+
+        io.mem.insert("tc1.mode".into(), Value::Text("auto".into()));
+        assert_eq!(ctl.next(((10.0, &dt), &mut io)).unwrap(), Some(25.0));
+    }
+}
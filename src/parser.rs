@@ -1,7 +1,249 @@
 use super::*;
+use std::fmt;
 use std::io::{Error, ErrorKind, Result};
 use std::str::FromStr;
 
+/// Parse a rule condition like `in.x > 5.0 AND (in.y == true OR NOT mem.z < 3)`.
+///
+/// `NOT` binds stronger than `AND` that binds stronger than `OR`.
+/// Keywords are case insensitive.
+impl FromStr for BoolExpr<Comparison> {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = ExprParser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(t) = parser.tokens.get(parser.pos) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unexpected token {:?}", t),
+            ));
+        }
+        Ok(expr)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(String),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in s.chars() {
+        if quoted {
+            word.push(c);
+            quoted = c != '\'';
+            continue;
+        }
+        match c {
+            '\'' => {
+                quoted = true;
+                word.push(c);
+            }
+            '(' | ')' => {
+                push_word(&mut tokens, &mut word);
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            c if c.is_whitespace() => push_word(&mut tokens, &mut word),
+            c => word.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::new(ErrorKind::InvalidInput, "unterminated text"));
+    }
+    push_word(&mut tokens, &mut word);
+    Ok(tokens)
+}
+
+fn push_word(tokens: &mut Vec<Token>, word: &mut String) {
+    if word.is_empty() {
+        return;
+    }
+    let token = match word.to_uppercase().as_str() {
+        "AND" => Token::And,
+        "OR" => Token::Or,
+        "NOT" => Token::Not,
+        _ => Token::Word(word.clone()),
+    };
+    tokens.push(token);
+    word.clear();
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn accept(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<BoolExpr<Comparison>> {
+        let mut expr = self.parse_and()?;
+        while self.accept(&Token::Or) {
+            expr = BoolExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<BoolExpr<Comparison>> {
+        let mut expr = self.parse_not()?;
+        while self.accept(&Token::And) {
+            expr = BoolExpr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<BoolExpr<Comparison>> {
+        if self.accept(&Token::Not) {
+            Ok(BoolExpr::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_atom()
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<BoolExpr<Comparison>> {
+        if self.accept(&Token::Open) {
+            let expr = self.parse_or()?;
+            if !self.accept(&Token::Close) {
+                return Err(Error::new(ErrorKind::InvalidInput, "missing ')'"));
+            }
+            return Ok(expr);
+        }
+        let mut words = vec![];
+        while let Some(Token::Word(w)) = self.tokens.get(self.pos) {
+            words.push(w.as_str());
+            self.pos += 1;
+        }
+        if words.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "missing expression"));
+        }
+        let atom = words.join(" ");
+        match atom.to_lowercase().as_str() {
+            "true" => Ok(BoolExpr::True),
+            "false" => Ok(BoolExpr::False),
+            _ => Ok(BoolExpr::Eval(Comparison::from_str(&atom)?)),
+        }
+    }
+}
+
+impl<T: fmt::Display> BoolExpr<T> {
+    fn precedence(&self) -> u8 {
+        use crate::BoolExpr::*;
+        match self {
+            Or(_, _) => 1,
+            And(_, _) => 2,
+            Not(_) => 3,
+            True | False | Eval(_) => 4,
+        }
+    }
+
+    fn fmt_with_precedence(&self, f: &mut fmt::Formatter, min: u8) -> fmt::Result {
+        use crate::BoolExpr::*;
+        let prec = self.precedence();
+        if prec < min {
+            write!(f, "(")?;
+        }
+        match self {
+            True => write!(f, "true")?,
+            False => write!(f, "false")?,
+            Eval(x) => write!(f, "{}", x)?,
+            Not(x) => {
+                write!(f, "NOT ")?;
+                x.fmt_with_precedence(f, prec)?;
+            }
+            And(a, b) | Or(a, b) => {
+                a.fmt_with_precedence(f, prec)?;
+                write!(f, " {} ", if prec == 1 { "OR" } else { "AND" })?;
+                // Operators are left associative, so the right hand side
+                // needs parentheses to keep the structure.
+                b.fmt_with_precedence(f, prec + 1)?;
+            }
+        }
+        if prec < min {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Render an expression in the rule syntax that can be parsed again.
+impl<T: fmt::Display> fmt::Display for BoolExpr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with_precedence(f, 0)
+    }
+}
+
+/// Approximate comparisons are rendered with their tolerance
+/// (e.g. `in.x ~= 5.0 +/- 2%`) and numeric bit encodings
+/// with a marker (e.g. `in.x > 0.5 [bits: 1.0/0.0]`).
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.left,
+            comparator_as_str(self.cmp),
+            self.right
+        )?;
+        match (self.cmp, self.tolerance) {
+            (Comparator::ApproxEqual, Tolerance::Absolute(t)) => write!(f, " +/- {:?}", t),
+            (Comparator::ApproxEqual, Tolerance::Percent(p)) => write!(f, " +/- {:?}%", p),
+            _ => Ok(()),
+        }?;
+        match self.encoding {
+            BitEncoding::Numeric { on, off } => write!(f, " [bits: {:?}/{:?}]", on, off),
+            BitEncoding::Strict => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::Source::*;
+        match self {
+            In(id) => write!(f, "in.{}", id),
+            Out(id) => write!(f, "out.{}", id),
+            Mem(id) => write!(f, "mem.{}", id),
+            Setpoint(id) => write!(f, "setpoint.{}", id),
+            Timeout(id) => write!(f, "timeout.{}", id),
+            ControllerVar(id, var) => write!(f, "controller.{}.{}", id, var),
+            Const(Value::Bit(v)) => write!(f, "{}", v),
+            // Debug formatting keeps the decimal point of whole numbers.
+            Const(Value::Decimal(v)) => write!(f, "{:?}", v),
+            Const(Value::Integer(v)) => write!(f, "{}", v),
+            Const(Value::Text(v)) => write!(f, "'{}'", v),
+            Const(Value::Quantity(v, unit)) => write!(f, "{:?} {}", v, unit),
+            Const(Value::Bin(v)) => {
+                write!(f, "0x")?;
+                for b in v {
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+            // IEC 61131-3 style time literals (e.g. `t#1500ms`)
+            Const(Value::Timeout(t)) if t.subsec_nanos() % 1_000_000 == 0 => {
+                write!(f, "t#{}ms", t.as_millis())
+            }
+            Const(Value::Timeout(t)) => write!(f, "t#{}ns", t.as_nanos()),
+        }
+    }
+}
+
 impl FromStr for Comparison {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self> {
@@ -9,15 +251,35 @@ impl FromStr for Comparison {
         if s.trim().is_empty() {
             return Err(Error::new(ErrorKind::InvalidInput, "empty str"));
         }
+        let (s, encoding) = parse_bit_encoding(s)?;
+        if let Some(cmp) = parse_approx_comparison(s)? {
+            return Ok(cmp.with_bit_encoding(encoding));
+        }
         for cmp in &[GreaterOrEqual, Greater, Equal, LessOrEqual, Less, NotEqual] {
             if let Some(cmp) = parse_comparison(s, *cmp)? {
-                return Ok(cmp);
+                return Ok(cmp.with_bit_encoding(encoding));
             }
         }
         Err(Error::new(ErrorKind::InvalidInput, "invalid comparison"))
     }
 }
 
+/// Split off a bit encoding marker like `[bits: 1.0/0.0]`.
+fn parse_bit_encoding(s: &str) -> Result<(&str, BitEncoding)> {
+    let (s, marker) = match s.trim_end().strip_suffix(']') {
+        Some(rest) => match rest.rsplit_once("[bits:") {
+            Some(split) => split,
+            None => return Ok((s, BitEncoding::Strict)),
+        },
+        None => return Ok((s, BitEncoding::Strict)),
+    };
+    let invalid = || Error::new(ErrorKind::InvalidInput, "invalid bit encoding");
+    let (on, off) = marker.split_once('/').ok_or_else(invalid)?;
+    let on = on.trim().parse().map_err(|_| invalid())?;
+    let off = off.trim().parse().map_err(|_| invalid())?;
+    Ok((s, BitEncoding::Numeric { on, off }))
+}
+
 fn comparator_as_str(cmp: Comparator) -> &'static str {
     use crate::Comparator::*;
     match cmp {
@@ -27,6 +289,7 @@ fn comparator_as_str(cmp: Comparator) -> &'static str {
         GreaterOrEqual => ">=",
         Equal => "==",
         NotEqual => "!=",
+        ApproxEqual => "~=",
     }
 }
 
@@ -41,6 +304,8 @@ fn parse_comparison(s: &str, cmp: Comparator) -> Result<Option<Comparison>> {
                         left: Source::from_str(lhs)?,
                         cmp,
                         right: Source::from_str(rhs)?,
+                        encoding: BitEncoding::Strict,
+                        tolerance: Tolerance::default(),
                     }));
                 }
             }
@@ -55,6 +320,39 @@ fn parse_comparison(s: &str, cmp: Comparator) -> Result<Option<Comparison>> {
     }
 }
 
+fn parse_approx_comparison(s: &str) -> Result<Option<Comparison>> {
+    let cmp_str = comparator_as_str(Comparator::ApproxEqual);
+    let mut vals = s.split(cmp_str);
+    let lhs = vals.next().unwrap_or_default();
+    let rhs = match vals.next() {
+        Some(rhs) => rhs,
+        // Ignore input strings without a comparator
+        None => return Ok(None),
+    };
+    let mut rhs = rhs.split("+/-");
+    let (right, tolerance) = match (vals.next(), rhs.next(), rhs.next(), rhs.next()) {
+        (None, Some(right), Some(tolerance), None) => (right, tolerance.trim()),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("expected a comparison like 'a {} b +/- t'", cmp_str),
+            ))
+        }
+    };
+    let invalid_tolerance = |_| Error::new(ErrorKind::InvalidInput, "invalid tolerance");
+    let tolerance = match tolerance.strip_suffix('%') {
+        Some(p) => Tolerance::Percent(p.trim().parse().map_err(invalid_tolerance)?),
+        None => Tolerance::Absolute(tolerance.parse().map_err(invalid_tolerance)?),
+    };
+    Ok(Some(Comparison {
+        left: Source::from_str(lhs)?,
+        cmp: Comparator::ApproxEqual,
+        right: Source::from_str(right)?,
+        encoding: BitEncoding::Strict,
+        tolerance,
+    }))
+}
+
 impl FromStr for Source {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self> {
@@ -71,7 +369,27 @@ impl FromStr for Source {
         if let Ok(v) = s.parse::<f64>() {
             return Ok(Source::Const(v.into()));
         }
+        if let Some(hex) = s.strip_prefix("0x") {
+            return parse_bin(hex).map(Source::Const);
+        }
+        if let Some(t) = s.strip_prefix("t#").or_else(|| s.strip_prefix("T#")) {
+            return parse_time(t).map(Source::Const);
+        }
+        if let Some((v, unit)) = s.split_once(' ') {
+            if let Ok(v) = v.parse::<f64>() {
+                return Ok(Source::Const(Value::Quantity(v, unit.trim().into())));
+            }
+        }
         let s = s.to_lowercase();
+        // checked first, since IDs like `main` contain other prefixes
+        if let Some(rest) = s.strip_prefix("controller.") {
+            return match rest.rsplit_once('.') {
+                Some((id, var)) if !id.is_empty() && !var.is_empty() => {
+                    Ok(Source::ControllerVar(id.into(), var.into()))
+                }
+                _ => Err(Error::new(ErrorKind::InvalidInput, "invalid identifier")),
+            };
+        }
         if s.contains("timeout.") {
             let res = s.split("timeout.").collect::<Vec<&str>>();
             if res.len() < 2 || res[1].is_empty() {
@@ -113,10 +431,39 @@ impl FromStr for Source {
         if s.contains("false") {
             return Ok(Source::Const(false.into()));
         }
-        unimplemented!()
+        Err(Error::new(ErrorKind::InvalidInput, "invalid source"))
     }
 }
 
+fn parse_bin(hex: &str) -> Result<Value> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|b| {
+            std::str::from_utf8(b)
+                .ok()
+                .filter(|b| b.len() == 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid binary literal"))
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Value::Bin)
+}
+
+fn parse_time(s: &str) -> Result<Value> {
+    let invalid = |_| Error::new(ErrorKind::InvalidInput, "invalid time literal");
+    let s = s.to_lowercase();
+    let t = if let Some(v) = s.strip_suffix("ms") {
+        Duration::from_millis(v.parse().map_err(invalid)?)
+    } else if let Some(v) = s.strip_suffix("ns") {
+        Duration::from_nanos(v.parse().map_err(invalid)?)
+    } else if let Some(v) = s.strip_suffix('s') {
+        Duration::from_secs(v.parse().map_err(invalid)?)
+    } else {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid time literal"));
+    };
+    Ok(Value::Timeout(t))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,6 +504,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_controller_src() {
+        let src = Source::ControllerVar("main".into(), "saturated".into());
+        assert_eq!(Source::from_str("controller.main.saturated").unwrap(), src);
+        assert_eq!(src.to_string(), "controller.main.saturated");
+        assert_eq!(
+            Source::from_str("controller.zone.1.i").unwrap(),
+            Source::ControllerVar("zone.1".into(), "i".into())
+        );
+        assert!(Source::from_str("controller.main").is_err());
+        assert!(Source::from_str("controller..i").is_err());
+        assert!(Source::from_str("controller.main.").is_err());
+    }
+
     #[test]
     fn parse_timeout_src() {
         assert_eq!(
@@ -246,8 +607,158 @@ mod tests {
         for (s, left, cmp, right) in tests {
             assert_eq!(
                 Comparison::from_str(s).unwrap(),
-                Comparison { left, cmp, right }
+                Comparison {
+                    left,
+                    cmp,
+                    right,
+                    encoding: BitEncoding::Strict,
+                    tolerance: Tolerance::default(),
+                }
             );
         }
     }
+
+    #[test]
+    fn parse_expressions_with_precedence() {
+        use crate::BoolExpr::*;
+        let x = || Eval(Comparison::from_str("in.x > 5.0").unwrap());
+        let y = || Eval(Comparison::from_str("in.y == true").unwrap());
+        let z = || Eval(Comparison::from_str("mem.z < 3").unwrap());
+        let and = |a, b| And(Box::new(a), Box::new(b));
+        let or = |a, b| Or(Box::new(a), Box::new(b));
+
+        let parse = |s: &str| BoolExpr::<Comparison>::from_str(s).unwrap();
+        assert_eq!(parse("in.x > 5.0 AND in.y == true"), and(x(), y()));
+        assert_eq!(
+            parse("in.x > 5.0 or in.y == true and mem.z < 3"),
+            or(x(), and(y(), z()))
+        );
+        assert_eq!(
+            parse("(in.x > 5.0 OR in.y == true) AND mem.z < 3"),
+            and(or(x(), y()), z())
+        );
+        assert_eq!(
+            parse("NOT in.x > 5.0 AND in.y == true"),
+            and(Not(Box::new(x())), y())
+        );
+        assert_eq!(parse("NOT (true OR false)"), Not(Box::new(or(True, False))));
+        assert_eq!(
+            parse("in.t == 'A and B'"),
+            Eval(Source::In("t".into()).cmp_eq(Source::Const(Value::Text("A and B".into()))))
+        );
+    }
+
+    #[test]
+    fn reject_invalid_expressions() {
+        for s in &[
+            "",
+            "in.x > 5.0 AND",
+            "(in.x > 5.0",
+            "in.x > 5.0)",
+            "in.x > 5.0 AND OR in.y < 1",
+            "in.x ?? 3",
+            "in.x > foo",
+            "in.t == 'text",
+        ] {
+            assert!(BoolExpr::<Comparison>::from_str(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn round_trip_expressions() {
+        for s in &[
+            "in.x > 5.0 AND in.y == true",
+            "in.x > 5.0 OR in.y == true AND mem.z <= -3",
+            "(in.x > 5.0 OR in.y == true) AND NOT mem.z != 3",
+            "in.x > 5.0 AND (in.y == true AND out.z >= 1.5)",
+            "NOT (setpoint.a < 7.25 OR timeout.t == false)",
+            "in.t == 'foo bar' OR true",
+        ] {
+            let expr = BoolExpr::<Comparison>::from_str(s).unwrap();
+            assert_eq!(expr.to_string(), *s);
+            assert_eq!(BoolExpr::from_str(&expr.to_string()).unwrap(), expr);
+        }
+        assert_eq!(
+            BoolExpr::<Comparison>::from_str("  in.x>5.0 and(in.y==true)")
+                .unwrap()
+                .to_string(),
+            "in.x > 5.0 AND in.y == true"
+        );
+    }
+
+    #[test]
+    fn round_trip_approx_comparisons() {
+        for s in &[
+            "in.x ~= 5.0 +/- 0.5",
+            "in.x ~= out.y +/- 2.0%",
+            "in.x ~= 5 +/- 1.0 AND NOT in.y ~= -3.5 +/- 10.0%",
+        ] {
+            let expr = BoolExpr::<Comparison>::from_str(s).unwrap();
+            assert_eq!(expr.to_string(), *s);
+            assert_eq!(BoolExpr::from_str(&expr.to_string()).unwrap(), expr);
+        }
+        assert_eq!(
+            Comparison::from_str("in.x~=5.0+/-2 %").unwrap(),
+            Source::In("x".into()).approx_eq_pct(5.0.into(), 2.0)
+        );
+        assert!(Comparison::from_str("in.x ~= 5.0").is_err());
+        assert!(Comparison::from_str("in.x ~= 5.0 +/- foo").is_err());
+        assert!(Comparison::from_str("in.x ~= 5.0 +/- 1 +/- 2").is_err());
+        assert!(Comparison::from_str("in.x ~= 5.0 ~= 1 +/- 2").is_err());
+    }
+
+    #[test]
+    fn evaluate_parsed_expression() {
+        let expr = BoolExpr::<Comparison>::from_str("in.x > 5.0 AND NOT in.y == true").unwrap();
+        let mut io = IoState::default();
+        io.inputs.insert("x".into(), 6.0.into());
+        io.inputs.insert("y".into(), false.into());
+        assert!(expr.eval(&io).unwrap());
+        io.inputs.insert("y".into(), true.into());
+        assert!(!expr.eval(&io).unwrap());
+    }
+
+    #[test]
+    fn round_trip_const_values() {
+        for v in &[
+            Value::Quantity(21.5, "°C".into()),
+            Value::Quantity(-3.0, "m/s".into()),
+            Value::Bin(vec![0x66, 0x6F, 0x00]),
+            Value::Bin(vec![]),
+            Value::Timeout(Duration::from_millis(1500)),
+            Value::Timeout(Duration::from_nanos(1_000_001)),
+        ] {
+            let src = Source::Const(v.clone());
+            assert_eq!(Source::from_str(&src.to_string()).unwrap(), src);
+        }
+        for s in &[
+            "in.x > 21.5 °C",
+            "in.b == 0x666f6f AND timeout.t < t#1500ms",
+            "in.x ~= 5.0 kW +/- 2.0%",
+        ] {
+            let expr = BoolExpr::<Comparison>::from_str(s).unwrap();
+            assert_eq!(expr.to_string(), *s);
+        }
+        assert!(Source::from_str("0x6").is_err());
+        assert!(Source::from_str("0xzz").is_err());
+        assert!(Source::from_str("t#5min").is_err());
+    }
+
+    #[test]
+    fn round_trip_bit_encodings() {
+        for s in &[
+            "in.x > 0.5 [bits: 1.0/0.0]",
+            "in.x ~= 5.0 +/- 0.5 [bits: -1.0/0.0] OR in.y == true",
+        ] {
+            let expr = BoolExpr::<Comparison>::from_str(s).unwrap();
+            assert_eq!(expr.to_string(), *s);
+            assert_eq!(BoolExpr::from_str(&expr.to_string()).unwrap(), expr);
+        }
+        let cmp = Source::In("x".into())
+            .cmp_ge(Source::Const(1.into()))
+            .with_bit_encoding(BitEncoding::Numeric { on: 2.0, off: -2.0 });
+        assert_eq!(Comparison::from_str(&cmp.to_string()).unwrap(), cmp);
+        assert!(Comparison::from_str("in.x > 0.5 [bits: 1.0]").is_err());
+        assert!(Comparison::from_str("in.x > 0.5 [bits: on/off]").is_err());
+    }
 }
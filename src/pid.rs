@@ -37,9 +37,11 @@
 //! }
 //! ```
 
-use super::{Controller, PureController};
-use crate::util::limit;
-use std::{f64, time::Duration};
+use super::{Controller, Introspect, Introspection, PureController, WarmStart};
+use crate::rate_limiter::{RateLimiter, RateLimiterConfig};
+use crate::sensor_health::SensorFault;
+use crate::util::{cap_delta_t, limit, DurationInSeconds};
+use std::{error, f64, fmt, io, time::Duration};
 
 /// PID controller implementation
 #[derive(Debug, Clone)]
@@ -62,6 +64,28 @@ pub struct PidState {
     pub i: f64,
     /// Derivative portion
     pub d: f64,
+    /// Feedforward portion (added to the output)
+    pub feed_forward: f64,
+    /// Manual bias set by an operator (added to the output)
+    pub bias: f64,
+    /// The part of the bias that was absorbed by the integral portion
+    pub absorbed_bias: f64,
+    /// Target of the previous step
+    /// (only tracked with a setpoint derivative feedforward)
+    pub prev_target: Option<f64>,
+    /// Setpoint derivative feedforward portion
+    pub target_feed_forward: f64,
+    /// Dynamic lower limit of the output (e.g. set by an interlock)
+    pub output_min: Option<f64>,
+    /// Dynamic upper limit of the output (e.g. set by an interlock)
+    pub output_max: Option<f64>,
+    /// The saturation of the output in the last step
+    pub saturation: Option<Saturation>,
+    /// The output that was actually applied in the last step
+    /// (external reset feedback)
+    pub external_feedback: Option<f64>,
+    /// Oscillation detection
+    pub oscillation: OscillationState,
 }
 
 impl Default for PidState {
@@ -72,6 +96,16 @@ impl Default for PidState {
             p: 0.0,
             i: 0.0,
             d: 0.0,
+            feed_forward: 0.0,
+            bias: 0.0,
+            absorbed_bias: 0.0,
+            prev_target: None,
+            target_feed_forward: 0.0,
+            output_min: None,
+            output_max: None,
+            saturation: None,
+            external_feedback: None,
+            oscillation: OscillationState::default(),
         }
     }
 }
@@ -87,10 +121,137 @@ impl Pid {
     pub fn set_target(&mut self, target: f64) {
         self.state.target = target;
     }
+    /// Check if a sustained oscillation of the control error was detected.
+    pub fn is_oscillating(&self) -> bool {
+        self.state.oscillation.detected
+    }
+    /// Set the feedforward portion.
+    pub fn set_feed_forward(&mut self, feed_forward: f64) {
+        self.state.feed_forward = feed_forward;
+    }
+    /// Set the manual bias.
+    ///
+    /// The bias is added to the output immediately.
+    /// With a [bias absorption](PidConfig::bias_absorption) the
+    /// integral portion compensates the bias over time,
+    /// so it doesn't offset the steady state permanently.
+    pub fn set_bias(&mut self, bias: f64) {
+        self.state.bias = bias;
+    }
+    /// Set dynamic output limits in addition to the configured ones.
+    ///
+    /// While the output is at a dynamic limit, the integral portion is
+    /// back-calculated so that the unlimited output matches the limit.
+    /// This way the controller leaves the limit without a bump
+    /// if the limit is relaxed later on.
+    pub fn set_output_limits(&mut self, min: Option<f64>, max: Option<f64>) {
+        self.state.output_min = min;
+        self.state.output_max = max;
+    }
+    /// Set the output that was actually applied in the last step.
+    ///
+    /// If the command of the controller was not applied as is
+    /// (e.g. because another controller won a selector),
+    /// the integral portion is back-calculated from the difference
+    /// at the beginning of the next step, so the integral
+    /// does not wind up.
+    /// Pass `None` to disable the external feedback.
+    pub fn set_external_feedback(&mut self, feedback: Option<f64>) {
+        self.state.external_feedback = feedback;
+    }
+    /// Initialize the internal state from the current operating point.
+    ///
+    /// See [PidConfig::warm_start].
+    pub fn warm_start(&mut self, actual: f64, output: f64) {
+        self.state = self.cfg.warm_start(self.state, actual, output);
+    }
+    /// Calculate the next output and report abnormal conditions.
+    ///
+    /// In contrast to [Controller::next] a sensor fault
+    /// (e.g. of a [SensorHealth](crate::sensor_health::SensorHealth) monitor)
+    /// or an actual value that is not a finite number is reported as
+    /// an error: the step is rejected and the state is kept.
+    /// A performed step returns the (limited) output
+    /// together with its saturation.
+    pub fn try_next(
+        &mut self,
+        actual: f64,
+        sensor: Option<SensorFault>,
+        duration: &Duration,
+    ) -> Result<(f64, Option<Saturation>), Fault> {
+        if let Some(fault) = sensor {
+            return Err(Fault::Sensor(fault));
+        }
+        if !actual.is_finite() {
+            return Err(Fault::InvalidInput);
+        }
+        let output = self.next((actual, duration));
+        Ok((output, self.state.saturation))
+    }
     /// Reset the internal controller state.
+    ///
+    /// Dynamic output limits and the manual bias are kept.
     pub fn reset(&mut self) {
-        self.state = PidState::default();
-        self.state.target = self.cfg.default_target;
+        self.state = PidState {
+            target: self.cfg.default_target,
+            bias: self.state.bias,
+            output_min: self.state.output_min,
+            output_max: self.state.output_max,
+            ..Default::default()
+        };
+    }
+}
+
+impl WarmStart for Pid {
+    fn warm_start(&mut self, actual: f64, output: f64) {
+        Pid::warm_start(self, actual, output);
+    }
+}
+
+impl Introspect for Pid {
+    fn introspect(&self) -> Introspection {
+        let cfg = &self.cfg;
+        let optional = [
+            ("min", cfg.min),
+            ("max", cfg.max),
+            ("p_min", cfg.p_min),
+            ("p_max", cfg.p_max),
+            ("i_min", cfg.i_min),
+            ("i_max", cfg.i_max),
+        ];
+        let mut info = Introspection::default()
+            .with_parameter("k_p", cfg.k_p)
+            .with_parameter("k_i", cfg.k_i)
+            .with_parameter("k_d", cfg.k_d)
+            .with_parameter("default_target", cfg.default_target)
+            .with_parameter("k_sp", cfg.k_sp)
+            .with_parameter("bias_absorption", cfg.bias_absorption);
+        for (name, value) in optional {
+            if let Some(v) = value {
+                info = info.with_parameter(name, v);
+            }
+        }
+        info.state = self.state.introspect().state;
+        info
+    }
+}
+
+/// Only contains state variables.
+impl Introspect for PidState {
+    fn introspect(&self) -> Introspection {
+        let mut info = Introspection::default()
+            .with_state("target", self.target)
+            .with_state("p", self.p)
+            .with_state("i", self.i)
+            .with_state("d", self.d)
+            .with_state("feed_forward", self.feed_forward)
+            .with_state("bias", self.bias)
+            .with_state("target_feed_forward", self.target_feed_forward)
+            .with_state("saturated", self.saturation.is_some());
+        if let Some(v) = self.prev_value {
+            info = info.with_state("prev_value", v);
+        }
+        info
     }
 }
 
@@ -118,6 +279,74 @@ pub struct PidConfig {
     pub i_min: Option<f64>,
     /// Maximum integral portion
     pub i_max: Option<f64>,
+    /// Maximum plausible time step
+    ///
+    /// Larger steps (e.g. after a pause of the loop) are capped for the
+    /// integral calculation. The derivative portion still uses the real
+    /// time step, since a capped step would amplify the slope.
+    pub max_delta_t: Option<Duration>,
+    /// Integral leak (fraction per second)
+    ///
+    /// The integral portion decays towards zero by this fraction
+    /// per second, e.g. `0.001` for 0.1 % per second.
+    /// This prevents an indefinite buildup with persistent small offsets
+    /// at the cost of a small steady-state error. `0.0` disables the leak.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub i_leak: f64,
+    /// Preload the integral portion on startup
+    ///
+    /// If enabled, the integral portion of the first step cancels the
+    /// proportional and derivative portions, so the output starts at the
+    /// feedforward value without any feedback contribution.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub preload_integral: bool,
+    /// Setpoint derivative feedforward coefficient
+    ///
+    /// The rate of change of the target multiplied by this coefficient
+    /// is added to the output, so the controller follows setpoint ramps
+    /// without waiting for an error to build up. `0.0` disables it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub k_sp: f64,
+    /// Manual bias absorption (fraction per second)
+    ///
+    /// The integral portion takes over this fraction of the
+    /// remaining manual bias per second, so the output returns
+    /// to its previous level after a bias change.
+    /// `0.0` keeps the bias permanently.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bias_absorption: f64,
+    /// Oscillation detection
+    pub oscillation: Option<OscillationConfig>,
+    /// Scale the proportional gain with the error magnitude
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub error_gain: Option<ErrorGain>,
+}
+
+/// A proportional gain that depends on the error magnitude
+/// (also known as "error-squared" or "gap" control)
+///
+/// The effective gain is
+/// `k_p * (linearity + (1 - linearity) * |error| / span)`,
+/// i.e. the controller acts gently near the setpoint and
+/// aggressively far from it. This is commonly used for level control.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ErrorGain {
+    /// The portion of the gain that is independent of the error
+    /// (`0.0` = pure error-squared, `1.0` = linear)
+    pub linearity: f64,
+    /// The error magnitude at which the full gain `k_p` is applied
+    pub span: f64,
+}
+
+impl ErrorGain {
+    /// The gain factor for the given error.
+    pub fn factor(&self, error: f64) -> f64 {
+        if self.span <= 0.0 {
+            return 1.0;
+        }
+        self.linearity + (1.0 - self.linearity) * error.abs() / self.span
+    }
 }
 
 impl Default for PidConfig {
@@ -133,7 +362,548 @@ impl Default for PidConfig {
             p_max: None,
             i_min: None,
             i_max: None,
+            max_delta_t: None,
+            i_leak: 0.0,
+            preload_integral: false,
+            k_sp: 0.0,
+            bias_absorption: 0.0,
+            oscillation: None,
+            error_gain: None,
+        }
+    }
+}
+
+impl PidConfig {
+    /// The proportional portion for the given error.
+    fn proportional(&self, error: f64) -> f64 {
+        let k_p = match self.error_gain {
+            Some(ref gain) => self.k_p * gain.factor(error),
+            None => self.k_p,
+        };
+        limit(self.p_min, self.p_max, k_p * error)
+    }
+    /// Initialize a state from the current operating point.
+    ///
+    /// The integral portion is seeded so that the output of the
+    /// controller matches the present `output` for the given `actual` value.
+    /// This allows to resume control without bumping the actuator.
+    pub fn warm_start(&self, state: PidState, actual: f64, output: f64) -> PidState {
+        let mut state = state;
+        state.p = self.proportional(state.target - actual);
+        state.i = limit(
+            self.i_min,
+            self.i_max,
+            output - state.p - state.feed_forward - state.bias,
+        );
+        // the integral already compensates the bias
+        state.absorbed_bias = state.bias;
+        state.d = 0.0;
+        state.target_feed_forward = 0.0;
+        if self.k_sp != 0.0 {
+            state.prev_target = Some(state.target);
         }
+        state.prev_value = Some(actual);
+        state
+    }
+}
+
+/// A bank of identical PID controllers sharing a common setpoint
+///
+/// Each zone (e.g. a heater of a multi-zone oven) has its own
+/// measurement and its own controller state.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, pid::*};
+/// use std::time::Duration;
+///
+/// let mut bank = ControllerBank::new(PidConfig::default());
+/// bank.add_zone("front");
+/// bank.add_zone("back");
+/// bank.set_target(180.0);
+///
+/// let dt = Duration::from_secs(1);
+/// let outputs = bank.next(&[175.0, 181.0], &dt).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ControllerBank {
+    cfg: PidConfig,
+    target: f64,
+    /// The zones
+    pub zones: Vec<Zone>,
+}
+
+/// A zone of a [ControllerBank]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    /// The ID of the zone
+    pub id: String,
+    /// The controller state
+    pub state: PidState,
+    /// The last output
+    pub output: Option<f64>,
+}
+
+/// Aggregated diagnostics of a [ControllerBank]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BankDiagnostics {
+    /// The lowest output of all zones
+    pub min_output: Option<f64>,
+    /// The highest output of all zones
+    pub max_output: Option<f64>,
+    /// The mean output of all zones
+    pub mean_output: Option<f64>,
+    /// The zone with the largest (absolute) control error
+    pub max_error: Option<(String, f64)>,
+}
+
+impl ControllerBank {
+    /// Create a bank without any zones.
+    pub fn new(cfg: PidConfig) -> Self {
+        ControllerBank {
+            target: cfg.default_target,
+            cfg,
+            zones: vec![],
+        }
+    }
+    /// Set the common target of all zones.
+    pub fn set_target(&mut self, target: f64) {
+        self.target = target;
+        for z in &mut self.zones {
+            z.state.target = target;
+        }
+    }
+    /// Add a zone.
+    ///
+    /// An existing zone with the same ID is reset
+    /// and keeps its position.
+    pub fn add_zone<S: Into<String>>(&mut self, id: S) {
+        let zone = Zone {
+            id: id.into(),
+            state: PidState {
+                target: self.target,
+                ..Default::default()
+            },
+            output: None,
+        };
+        match self.zones.iter_mut().find(|z| z.id == zone.id) {
+            Some(z) => *z = zone,
+            None => self.zones.push(zone),
+        }
+    }
+    /// Remove a zone.
+    ///
+    /// Returns `false` if there is no such zone.
+    pub fn remove_zone(&mut self, id: &str) -> bool {
+        let len = self.zones.len();
+        self.zones.retain(|z| z.id != id);
+        self.zones.len() != len
+    }
+    /// Aggregate the states of all zones.
+    pub fn diagnostics(&self) -> BankDiagnostics {
+        let outputs: Vec<f64> = self.zones.iter().filter_map(|z| z.output).collect();
+        let mut diag = BankDiagnostics::default();
+        if !outputs.is_empty() {
+            diag.min_output = Some(outputs.iter().cloned().fold(f64::INFINITY, f64::min));
+            diag.max_output = Some(outputs.iter().cloned().fold(f64::NEG_INFINITY, f64::max));
+            diag.mean_output = Some(outputs.iter().sum::<f64>() / outputs.len() as f64);
+        }
+        for z in &self.zones {
+            if let Some(actual) = z.state.prev_value {
+                let err = z.state.target - actual;
+                if diag
+                    .max_error
+                    .as_ref()
+                    .map(|(_, e)| err.abs() > e.abs())
+                    .unwrap_or(true)
+                {
+                    diag.max_error = Some((z.id.clone(), err));
+                }
+            }
+        }
+        diag
+    }
+}
+
+/// Calculates the outputs for one measurement per zone
+/// (in the order of the zones).
+///
+/// The number of measurements must match the number of zones,
+/// otherwise the input is rejected without changing any zone.
+impl Controller<(&[f64], &Duration), io::Result<Vec<f64>>> for ControllerBank {
+    fn next(&mut self, input: (&[f64], &Duration)) -> io::Result<Vec<f64>> {
+        let (measurements, duration) = input;
+        if measurements.len() != self.zones.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Expected {} measurements (one per zone) but got {}",
+                    self.zones.len(),
+                    measurements.len()
+                ),
+            ));
+        }
+        let cfg = &self.cfg;
+        Ok(self
+            .zones
+            .iter_mut()
+            .zip(measurements)
+            .map(|(z, actual)| {
+                let (state, output) = cfg.next((z.state, *actual, duration));
+                z.state = state;
+                z.output = Some(output);
+                output
+            })
+            .collect())
+    }
+}
+
+/// Two cascaded PID controllers
+///
+/// The output of the outer loop is the setpoint of the inner loop.
+/// While the inner loop is saturated (its output is at `min` or `max`),
+/// the integral portion of the outer loop is not allowed to grow
+/// in the direction of the saturation, since the inner loop
+/// can't follow anyway (anti-windup).
+///
+/// Both loops are expected to act in the same direction,
+/// i.e. a higher inner setpoint results in a higher inner output.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, pid::*};
+/// use std::time::Duration;
+///
+/// let outer = PidConfig { k_p: 2.0, k_i: 0.1, ..Default::default() };
+/// let inner = PidConfig { k_p: 0.5, min: Some(0.0), max: Some(100.0), ..Default::default() };
+/// let mut cascade = Cascade::new(outer, inner);
+/// cascade.set_target(80.0);
+///
+/// let dt = Duration::from_secs(1);
+/// let (level, flow) = (75.0, 3.2);
+/// let valve = cascade.next((level, flow), &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cascade {
+    /// The outer (primary) loop
+    pub outer: Pid,
+    /// The inner (secondary) loop
+    pub inner: Pid,
+    saturation: Option<Saturation>,
+}
+
+/// The saturation of a controller output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saturation {
+    /// The output is at its lower limit
+    Low,
+    /// The output is at its upper limit
+    High,
+}
+
+/// A fault that rejects a controller step (see [Pid::try_next])
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// The actual value is not a finite number
+    InvalidInput,
+    /// The sensor of the actual value is faulty
+    Sensor(SensorFault),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::InvalidInput => write!(f, "The actual value is not a finite number"),
+            Fault::Sensor(fault) => write!(f, "Sensor fault: {:?}", fault),
+        }
+    }
+}
+
+impl error::Error for Fault {}
+
+impl Cascade {
+    /// Create a new cascade.
+    pub fn new(outer: PidConfig, inner: PidConfig) -> Self {
+        Cascade {
+            outer: Pid::new(outer),
+            inner: Pid::new(inner),
+            saturation: None,
+        }
+    }
+    /// Set the target of the outer loop.
+    pub fn set_target(&mut self, target: f64) {
+        self.outer.set_target(target);
+    }
+    /// The saturation of the inner loop in the last step.
+    pub fn inner_saturation(&self) -> Option<Saturation> {
+        self.saturation
+    }
+    /// Reset both loops.
+    pub fn reset(&mut self) {
+        self.outer.reset();
+        self.inner.reset();
+        self.saturation = None;
+    }
+}
+
+impl PidConfig {
+    /// The sum of all portions of the output (before any limits are applied).
+    fn output(&self, state: &PidState) -> f64 {
+        state.p + state.i + state.d + state.feed_forward + state.target_feed_forward + state.bias
+    }
+    fn saturation(&self, output: f64) -> Option<Saturation> {
+        match (self.min, self.max) {
+            (Some(min), _) if output <= min => Some(Saturation::Low),
+            (_, Some(max)) if output >= max => Some(Saturation::High),
+            _ => None,
+        }
+    }
+}
+
+/// The input is a tuple of the outer and inner actual values.
+impl Controller<((f64, f64), &Duration), f64> for Cascade {
+    fn next(&mut self, input: ((f64, f64), &Duration)) -> f64 {
+        let ((outer_actual, inner_actual), duration) = input;
+
+        let (outer, setpoint) =
+            self.outer
+                .cfg
+                .step(self.outer.state, outer_actual, duration, self.saturation);
+        self.outer.state = outer;
+
+        self.inner.set_target(setpoint);
+        let output = self.inner.next((inner_actual, duration));
+        self.saturation = self.inner.cfg.saturation(output);
+        output
+    }
+}
+
+/// A PID controller with independent rate limits
+/// for the setpoint and the output
+///
+/// The setpoint limit defines how aggressively the loop responds to
+/// new commands, the output limit how fast it drives the actuator.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, pid::*, rate_limiter::*};
+/// use std::time::Duration;
+///
+/// let mut ctl = RateLimitedPid::new(
+///     Pid::new(PidConfig::default()),
+///     RateLimiterConfig { rate: 0.5, ..Default::default() },
+///     RateLimiterConfig { rate: 10.0, ..Default::default() },
+/// );
+/// let dt = Duration::from_secs(1);
+/// let (setpoint, actual) = (80.0, 21.5);
+/// let valve = ctl.next((setpoint, actual), &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimitedPid {
+    /// The controller
+    pub pid: Pid,
+    /// The rate limiter of the setpoint
+    pub setpoint: RateLimiter,
+    /// The rate limiter of the output
+    pub output: RateLimiter,
+}
+
+impl RateLimitedPid {
+    /// Wrap a controller.
+    pub fn new(pid: Pid, setpoint: RateLimiterConfig, output: RateLimiterConfig) -> Self {
+        RateLimitedPid {
+            pid,
+            setpoint: RateLimiter::new(setpoint),
+            output: RateLimiter::new(output),
+        }
+    }
+    /// Reset the controller and both rate limiters.
+    pub fn reset(&mut self) {
+        self.pid.reset();
+        self.setpoint.reset();
+        self.output.reset();
+    }
+}
+
+/// The input is a tuple of the setpoint and the actual value.
+impl Controller<((f64, f64), &Duration), f64> for RateLimitedPid {
+    fn next(&mut self, input: ((f64, f64), &Duration)) -> f64 {
+        let ((setpoint, actual), duration) = input;
+        let target = self.setpoint.next((setpoint, duration));
+        self.pid.set_target(target);
+        let output = self.pid.next((actual, duration));
+        self.output.next((output, duration))
+    }
+}
+
+/// A PID controller with a setpoint soft-start
+///
+/// When the controller is [enabled](SoftStartPid::enable),
+/// the effective setpoint starts at the current measurement
+/// and ramps linearly to the target within the configured time.
+/// So the loop doesn't start with a huge control error.
+/// A disabled controller has no output.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, pid::*};
+/// use std::time::Duration;
+///
+/// let mut ctl = SoftStartPid::new(
+///     Pid::new(PidConfig::default()),
+///     SoftStartConfig { duration: Duration::from_secs(300) },
+/// );
+/// ctl.enable();
+/// let dt = Duration::from_secs(1);
+/// let (setpoint, actual) = (80.0, 21.5);
+/// let heater = ctl.next((setpoint, actual), &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SoftStartPid {
+    /// The controller
+    pub pid: Pid,
+    cfg: SoftStartConfig,
+    /// Current soft-start state
+    pub state: SoftStartState,
+}
+
+/// Soft-start configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SoftStartConfig {
+    /// The time to ramp from the measurement to the target
+    pub duration: Duration,
+}
+
+impl Default for SoftStartConfig {
+    fn default() -> Self {
+        SoftStartConfig {
+            duration: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Soft-start state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SoftStartState {
+    /// Is the controller enabled?
+    pub enabled: bool,
+    /// The measurement on enable
+    pub start: Option<f64>,
+    /// The time since the controller was enabled
+    pub elapsed: Duration,
+    /// The setpoint of the last step
+    pub setpoint: Option<f64>,
+}
+
+impl SoftStartPid {
+    /// Wrap a controller.
+    pub fn new(pid: Pid, cfg: SoftStartConfig) -> Self {
+        SoftStartPid {
+            pid,
+            cfg,
+            state: SoftStartState::default(),
+        }
+    }
+    /// Enable the controller and start the ramp.
+    ///
+    /// The controller is reset and the ramp starts
+    /// at the measurement of the next step.
+    pub fn enable(&mut self) {
+        self.pid.reset();
+        self.state = SoftStartState {
+            enabled: true,
+            ..Default::default()
+        };
+    }
+    /// Disable the controller.
+    pub fn disable(&mut self) {
+        self.state = SoftStartState::default();
+    }
+    /// Check if the controller is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.state.enabled
+    }
+    /// The effective setpoint of the last step.
+    pub fn setpoint(&self) -> Option<f64> {
+        self.state.setpoint
+    }
+}
+
+/// The input is a tuple of the setpoint and the actual value.
+impl Controller<((f64, f64), &Duration), Option<f64>> for SoftStartPid {
+    fn next(&mut self, input: ((f64, f64), &Duration)) -> Option<f64> {
+        let ((target, actual), duration) = input;
+        if !self.state.enabled {
+            return None;
+        }
+        let start = *self.state.start.get_or_insert(actual);
+        let setpoint = if self.state.elapsed < self.cfg.duration {
+            let progress = self.state.elapsed.as_secs_f64() / self.cfg.duration.as_secs_f64();
+            start + (target - start) * progress
+        } else {
+            target
+        };
+        self.state.setpoint = Some(setpoint);
+        self.state.elapsed += *duration;
+        self.pid.set_target(setpoint);
+        Some(self.pid.next((actual, duration)))
+    }
+}
+
+/// Oscillation detection configuration
+///
+/// The control error is observed within consecutive windows.
+/// An oscillation is detected if the error crossed zero at least
+/// `min_zero_crossings` times with an amplitude of at least
+/// `min_amplitude` within a window.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OscillationConfig {
+    /// Observation window
+    pub window: Duration,
+    /// Minimum number of zero-crossings within a window
+    pub min_zero_crossings: u32,
+    /// Minimum (absolute) error amplitude within a window
+    pub min_amplitude: f64,
+}
+
+/// Oscillation detection state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OscillationState {
+    /// Elapsed time of the current window
+    pub elapsed: Duration,
+    /// Zero-crossings within the current window
+    pub zero_crossings: u32,
+    /// Maximum (absolute) error within the current window
+    pub amplitude: f64,
+    /// Error of the previous step
+    pub prev_error: Option<f64>,
+    /// Result of the last completed window
+    pub detected: bool,
+}
+
+impl PureController<(OscillationState, f64, &Duration), OscillationState> for OscillationConfig {
+    fn next(&self, input: (OscillationState, f64, &Duration)) -> OscillationState {
+        let (mut state, error, duration) = input;
+        if let Some(prev_error) = state.prev_error {
+            if prev_error * error < 0.0 {
+                state.zero_crossings += 1;
+            }
+        }
+        if error != 0.0 {
+            state.prev_error = Some(error);
+        }
+        state.amplitude = state.amplitude.max(error.abs());
+        state.elapsed += *duration;
+        if state.elapsed >= self.window {
+            state.detected = state.zero_crossings >= self.min_zero_crossings
+                && state.amplitude >= self.min_amplitude;
+            state.elapsed = Duration::new(0, 0);
+            state.zero_crossings = 0;
+            state.amplitude = 0.0;
+        }
+        state
     }
 }
 
@@ -149,18 +919,54 @@ impl<'a> Controller<(f64, &'a Duration), f64> for Pid {
 impl<'a> PureController<(PidState, f64, &'a Duration), (PidState, f64)> for PidConfig {
     fn next(&self, input: (PidState, f64, &Duration)) -> (PidState, f64) {
         let (state, actual, duration) = input;
+        self.step(state, actual, duration, None)
+    }
+}
 
+impl PidConfig {
+    /// Calculate the next state and output.
+    ///
+    /// The integration of the error is suppressed if it would
+    /// drive the integral in the direction of `inhibit`
+    /// (see [Cascade]).
+    fn step(
+        &self,
+        state: PidState,
+        actual: f64,
+        duration: &Duration,
+        inhibit: Option<Saturation>,
+    ) -> (PidState, f64) {
         let delta_t = DurationInSeconds::from(*duration);
         debug_assert!(delta_t.is_valid());
 
         let mut state = state;
 
+        // back-calculation of the integral from the external feedback
+        if let (Some(feedback), Some(_)) = (state.external_feedback, state.prev_value) {
+            let prev_result = limit(self.min, self.max, self.output(&state));
+            state.i = limit(self.i_min, self.i_max, state.i + (feedback - prev_result));
+        }
+
         let err_p = state.target - actual;
-        state.p = self.k_p * err_p;
-        state.p = limit(self.p_min, self.p_max, state.p);
+        if let Some(ref oscillation) = self.oscillation {
+            state.oscillation = oscillation.next((state.oscillation, err_p, duration));
+        }
+        state.p = self.proportional(err_p);
 
-        let err_i = err_p * f64::from(delta_t);
-        state.i += self.k_i * err_i;
+        let delta_t_i = DurationInSeconds::from(cap_delta_t(duration, self.max_delta_t));
+        if self.i_leak > 0.0 {
+            state.i *= (1.0 - self.i_leak.min(1.0)).powf(f64::from(delta_t_i));
+        }
+        let err_i = err_p * f64::from(delta_t_i);
+        let delta_i = self.k_i * err_i;
+        let winding_up = match inhibit {
+            Some(Saturation::High) => delta_i > 0.0,
+            Some(Saturation::Low) => delta_i < 0.0,
+            None => false,
+        };
+        if !winding_up {
+            state.i += delta_i;
+        }
         state.i = limit(self.i_min, self.i_max, state.i);
 
         state.d = if delta_t.is_empty() {
@@ -176,47 +982,58 @@ impl<'a> PureController<(PidState, f64, &'a Duration), (PidState, f64)> for PidC
             0.0
         };
 
-        state.prev_value = Some(actual);
-
-        let result = state.p + state.i + state.d;
-
-        let result = limit(self.min, self.max, result);
+        state.target_feed_forward = match state.prev_target {
+            Some(prev_target) if self.k_sp != 0.0 && !delta_t.is_empty() => {
+                self.k_sp * (state.target - prev_target) / f64::from(delta_t)
+            }
+            _ => 0.0,
+        };
+        if self.k_sp != 0.0 {
+            state.prev_target = Some(state.target);
+        }
 
-        (state, result)
-    }
-}
+        if self.preload_integral && state.prev_value.is_none() {
+            state.i = limit(self.i_min, self.i_max, -(state.p + state.d));
+        }
 
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
-struct DurationInSeconds(f64);
+        state.prev_value = Some(actual);
 
-impl DurationInSeconds {
-    // Number of nanoseconds in a second.
-    const NANOS_PER_SEC: f64 = 1e9;
+        let mut result = self.output(&state);
 
-    pub fn is_empty(self) -> bool {
-        self.0 == 0.0
-    }
+        // back-calculation of the integral for dynamic limits
+        if let Some(max) = state.output_max {
+            if result > max {
+                state.i = limit(self.i_min, self.i_max, state.i - (result - max));
+                result = max;
+            }
+        }
+        if let Some(min) = state.output_min {
+            if result < min {
+                state.i = limit(self.i_min, self.i_max, state.i + (min - result));
+                result = min;
+            }
+        }
 
-    pub fn is_valid(self) -> bool {
-        self.0 >= 0.0
-    }
+        let result = limit(self.min, self.max, result);
 
-    pub fn seconds(self) -> f64 {
-        self.0
-    }
-}
+        // the integral takes over the bias for the next steps
+        if self.bias_absorption > 0.0 && !delta_t.is_empty() {
+            let remaining = state.bias - state.absorbed_bias;
+            let fraction = 1.0 - (1.0 - self.bias_absorption.min(1.0)).powf(f64::from(delta_t));
+            let i = limit(self.i_min, self.i_max, state.i - remaining * fraction);
+            state.absorbed_bias += state.i - i;
+            state.i = i;
+        }
 
-impl From<Duration> for DurationInSeconds {
-    fn from(from: Duration) -> Self {
-        DurationInSeconds(
-            from.as_secs() as f64 + f64::from(from.subsec_nanos()) / Self::NANOS_PER_SEC,
-        )
-    }
-}
+        state.saturation = self
+            .saturation(result)
+            .or(match (state.output_min, state.output_max) {
+                (Some(min), _) if result <= min => Some(Saturation::Low),
+                (_, Some(max)) if result >= max => Some(Saturation::High),
+                _ => None,
+            });
 
-impl From<DurationInSeconds> for f64 {
-    fn from(from: DurationInSeconds) -> Self {
-        from.seconds()
+        (state, result)
     }
 }
 
@@ -238,6 +1055,12 @@ mod tests {
         assert_eq!(cfg.i_max, None);
         assert_eq!(cfg.p_min, None);
         assert_eq!(cfg.p_max, None);
+        assert_eq!(cfg.max_delta_t, None);
+        assert_eq!(cfg.i_leak, 0.0);
+        assert!(!cfg.preload_integral);
+        assert_eq!(cfg.k_sp, 0.0);
+        assert_eq!(cfg.oscillation, None);
+        assert_eq!(cfg.error_gain, None);
     }
 
     #[test]
@@ -378,4 +1201,658 @@ mod tests {
         assert_eq!(pid.state.target, 9.9);
         assert_eq!(pid.state.prev_value, None);
     }
+
+    #[test]
+    fn detect_oscillation() {
+        use std::f64::consts::PI;
+        let cfg = PidConfig {
+            oscillation: Some(OscillationConfig {
+                window: Duration::from_secs(10),
+                min_zero_crossings: 4,
+                min_amplitude: 0.5,
+            }),
+            ..Default::default()
+        };
+        let mut pid = Pid::new(cfg);
+        let dt = Duration::from_millis(100);
+        // 0.5 Hz sine wave
+        for step in 1..100 {
+            let t = f64::from(step) * 0.1;
+            pid.next(((PI * t).sin(), &dt));
+            assert!(!pid.is_oscillating());
+        }
+        pid.next((0.0, &dt));
+        assert!(pid.is_oscillating());
+        assert_eq!(pid.state.oscillation.zero_crossings, 0);
+
+        // the oscillation decayed
+        for _ in 0..100 {
+            pid.next((0.1, &dt));
+        }
+        assert!(!pid.is_oscillating());
+    }
+
+    #[test]
+    fn ignore_oscillation_with_small_amplitude() {
+        use std::f64::consts::PI;
+        let cfg = PidConfig {
+            oscillation: Some(OscillationConfig {
+                window: Duration::from_secs(10),
+                min_zero_crossings: 4,
+                min_amplitude: 0.5,
+            }),
+            ..Default::default()
+        };
+        let mut pid = Pid::new(cfg);
+        let dt = Duration::from_millis(100);
+        for step in 1..=200 {
+            let t = f64::from(step) * 0.1;
+            pid.next((0.2 * (PI * t).sin(), &dt));
+        }
+        assert!(!pid.is_oscillating());
+    }
+
+    #[test]
+    fn warm_start() {
+        let cfg = PidConfig {
+            k_p: 2.0,
+            k_i: 0.5,
+            k_d: 1.0,
+            default_target: 10.0,
+            ..Default::default()
+        };
+        let mut pid = Pid::new(cfg);
+        pid.warm_start(8.0, 50.0);
+        assert_eq!(pid.state.p, 4.0);
+        assert_eq!(pid.state.i, 46.0);
+        assert_eq!(pid.state.prev_value, Some(8.0));
+        assert_eq!(pid.next((8.0, &Duration::from_secs(0))), 50.0);
+    }
+
+    #[test]
+    fn add_feed_forward() {
+        let mut pid = Pid::new(PidConfig::default());
+        pid.set_target(1.0);
+        pid.set_feed_forward(10.0);
+        let dt = Duration::from_secs(1);
+        assert_eq!(pid.next((0.0, &dt)), 11.0);
+        assert_eq!(pid.next((2.0, &dt)), 9.0);
+    }
+
+    #[test]
+    fn preload_integral_from_feed_forward() {
+        let cfg = PidConfig {
+            k_p: 2.0,
+            k_i: 0.5,
+            default_target: 10.0,
+            preload_integral: true,
+            ..Default::default()
+        };
+        let mut pid = Pid::new(cfg);
+        pid.set_feed_forward(30.0);
+        let dt = Duration::from_secs(1);
+        assert_eq!(pid.next((8.0, &dt)), 30.0);
+        assert_eq!(pid.state.p, 4.0);
+        assert_eq!(pid.state.i, -4.0);
+        // the integral is only preloaded once
+        assert_eq!(pid.next((8.0, &dt)), 31.0);
+        pid.reset();
+        pid.set_feed_forward(30.0);
+        assert_eq!(pid.next((10.0, &dt)), 30.0);
+    }
+
+    #[test]
+    fn track_dynamic_output_limits() {
+        let mut pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            k_i: 1.0,
+            min: Some(0.0),
+            max: Some(100.0),
+            ..Default::default()
+        });
+        pid.set_target(10.0);
+        let dt = Duration::from_secs(1);
+        assert_eq!(pid.next((0.0, &dt)), 20.0);
+        assert_eq!(pid.next((0.0, &dt)), 30.0);
+
+        // an interlock reduces the allowed output
+        pid.set_output_limits(None, Some(25.0));
+        assert_eq!(pid.next((0.0, &dt)), 25.0);
+        // the integral was back-calculated: 10.0 + 15.0 = 25.0
+        assert_eq!(pid.state.i, 15.0);
+        assert_eq!(pid.next((0.0, &dt)), 25.0);
+        assert_eq!(pid.state.i, 15.0);
+
+        // no bump if the limit is relaxed
+        pid.set_output_limits(None, None);
+        assert_eq!(pid.next((0.0, &dt)), 35.0);
+
+        // lower limit
+        pid.set_output_limits(Some(40.0), None);
+        assert_eq!(pid.next((20.0, &dt)), 40.0);
+        assert_eq!(pid.state.p + pid.state.i, 40.0);
+        // continues from the limit with the integral step of -10.0
+        pid.set_output_limits(None, None);
+        assert_eq!(pid.next((20.0, &dt)), 30.0);
+
+        pid.set_output_limits(None, Some(5.0));
+        pid.reset();
+        assert_eq!(pid.state.output_max, Some(5.0));
+        assert_eq!(pid.state.i, 0.0);
+    }
+
+    #[test]
+    fn feed_forward_setpoint_ramps() {
+        let cfg = PidConfig {
+            k_p: 0.0,
+            k_sp: 2.0,
+            ..Default::default()
+        };
+        let mut pid = Pid::new(cfg);
+        let dt = Duration::from_millis(500);
+        assert_eq!(pid.next((0.0, &dt)), 0.0);
+        // ramp with 1.0 per second
+        for i in 1..=4 {
+            pid.set_target(f64::from(i) * 0.5);
+            assert_eq!(pid.next((0.0, &dt)), 2.0);
+            assert_eq!(pid.state.target_feed_forward, 2.0);
+        }
+        // twice the ramp rate results in twice the feedforward
+        pid.set_target(3.0);
+        assert_eq!(pid.next((0.0, &dt)), 4.0);
+        // a constant setpoint has no feedforward
+        assert_eq!(pid.next((0.0, &dt)), 0.0);
+        // a falling setpoint
+        pid.set_target(2.5);
+        assert_eq!(pid.next((0.0, &dt)), -2.0);
+    }
+
+    #[test]
+    fn cap_implausible_time_steps() {
+        let cfg = PidConfig {
+            k_p: 0.0,
+            k_i: 1.0,
+            max_delta_t: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        let mut pid = Pid::new(cfg);
+        pid.set_target(1.0);
+        assert_eq!(pid.next((0.0, &Duration::from_secs(1))), 1.0);
+        // e.g. the loop was paused by a debugger
+        assert_eq!(pid.next((0.0, &Duration::from_secs(3600))), 3.0);
+        assert_eq!(pid.next((0.0, &Duration::from_secs(2))), 5.0);
+
+        let mut pid = Pid::new(PidConfig {
+            k_p: 0.0,
+            k_d: 1.0,
+            max_delta_t: Some(Duration::from_millis(500)),
+            ..Default::default()
+        });
+        pid.next((0.0, &Duration::from_secs(1)));
+        assert_eq!(pid.next((-1.0, &Duration::from_secs(1))), 1.0);
+    }
+
+    #[test]
+    fn run_controller_bank_with_shared_target() {
+        let mut bank = ControllerBank::new(PidConfig {
+            k_p: 2.0,
+            default_target: 10.0,
+            ..Default::default()
+        });
+        for id in &["a", "b", "c"] {
+            bank.add_zone(*id);
+        }
+        let dt = Duration::from_secs(1);
+        assert_eq!(
+            bank.next((&[8.0, 10.0, 13.0], &dt)).unwrap(),
+            vec![4.0, 0.0, -6.0]
+        );
+        let diag = bank.diagnostics();
+        assert_eq!(diag.min_output, Some(-6.0));
+        assert_eq!(diag.max_output, Some(4.0));
+        assert_eq!(diag.mean_output, Some(-2.0 / 3.0));
+        assert_eq!(diag.max_error, Some(("c".into(), -3.0)));
+
+        bank.set_target(12.0);
+        assert_eq!(
+            bank.next((&[8.0, 10.0, 13.0], &dt)).unwrap(),
+            vec![8.0, 4.0, -2.0]
+        );
+
+        // zones can be changed at runtime
+        assert!(bank.remove_zone("b"));
+        assert!(!bank.remove_zone("x"));
+        bank.add_zone("d");
+        assert_eq!(bank.zones[2].id, "d");
+        assert_eq!(bank.zones[2].state.target, 12.0);
+        assert_eq!(bank.zones.len(), 3);
+        assert_eq!(
+            bank.next((&[11.0, 12.5, 9.0], &dt)).unwrap(),
+            vec![2.0, -1.0, 6.0]
+        );
+        // the last zone has no measurement
+        let err = bank.next((&[8.0, 8.0], &dt)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        // there is no zone for the last measurement
+        let err = bank.next((&[8.0, 8.0, 8.0, 7.0], &dt)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(bank.zones[0].output, Some(2.0));
+        assert_eq!(bank.zones[2].output, Some(6.0));
+
+        // re-adding a zone resets it in place
+        bank.add_zone("a");
+        assert_eq!(bank.zones[0].id, "a");
+        assert_eq!(bank.zones[0].output, None);
+        assert_eq!(bank.zones.len(), 3);
+    }
+
+    #[test]
+    fn error_squared_gain() {
+        let cfg = PidConfig {
+            k_p: 2.0,
+            error_gain: Some(ErrorGain {
+                linearity: 0.0,
+                span: 10.0,
+            }),
+            ..Default::default()
+        };
+        let dt = Duration::from_secs(1);
+        let p = |err: f64| cfg.next((PidState::default(), -err, &dt)).0.p;
+        assert_eq!(p(0.0), 0.0);
+        assert_eq!(p(1.0), 0.2);
+        assert_eq!(p(2.0), 0.8);
+        assert_eq!(p(5.0), 5.0);
+        assert_eq!(p(10.0), 20.0);
+        assert_eq!(p(20.0), 80.0);
+        assert_eq!(p(-5.0), -5.0);
+
+        // partially linear
+        let cfg = PidConfig {
+            error_gain: Some(ErrorGain {
+                linearity: 0.5,
+                span: 10.0,
+            }),
+            ..cfg
+        };
+        let p = |err: f64| cfg.next((PidState::default(), -err, &dt)).0.p;
+        assert_eq!(p(1.0), 1.1);
+        assert_eq!(p(10.0), 20.0);
+        assert_eq!(p(20.0), 60.0);
+    }
+
+    #[test]
+    fn stop_outer_integral_while_inner_loop_is_saturated() {
+        let outer = PidConfig {
+            k_p: 1.0,
+            k_i: 1.0,
+            ..Default::default()
+        };
+        let inner = PidConfig {
+            k_p: 1.0,
+            max: Some(10.0),
+            min: Some(0.0),
+            ..Default::default()
+        };
+        let mut cascade = Cascade::new(outer, inner);
+        cascade.set_target(5.0);
+        let dt = Duration::from_secs(1);
+
+        // the inner process doesn't follow
+        assert_eq!(cascade.next(((0.0, 0.0), &dt)), 10.0);
+        assert_eq!(cascade.inner_saturation(), Some(Saturation::High));
+        assert_eq!(cascade.outer.state.i, 5.0);
+        for _ in 0..5 {
+            assert_eq!(cascade.next(((0.0, 0.0), &dt)), 10.0);
+            assert_eq!(cascade.outer.state.i, 5.0);
+        }
+        assert_eq!(cascade.inner.state.target, 10.0);
+
+        // the integral may still decrease
+        assert_eq!(cascade.next(((7.0, 0.0), &dt)), 1.0);
+        assert_eq!(cascade.outer.state.i, 3.0);
+        assert_eq!(cascade.inner_saturation(), None);
+
+        // and grows again without saturation
+        assert_eq!(cascade.next(((4.0, 0.0), &dt)), 5.0);
+        assert_eq!(cascade.outer.state.i, 4.0);
+
+        // without coordination the integral winds up
+        let mut outer = Pid::new(cascade.outer.cfg.clone());
+        outer.set_target(5.0);
+        for _ in 0..6 {
+            outer.next((0.0, &dt));
+        }
+        assert_eq!(outer.state.i, 30.0);
+    }
+
+    #[test]
+    fn integral_leak() {
+        let mut pid = Pid::new(PidConfig {
+            k_p: 0.0,
+            k_i: 1.0,
+            i_leak: 0.5,
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        pid.set_target(4.0);
+        assert_eq!(pid.next((0.0, &dt)), 4.0);
+        // no error
+        assert_eq!(pid.next((4.0, &dt)), 2.0);
+        assert_eq!(pid.next((4.0, &dt)), 1.0);
+        let dt = Duration::from_secs(2);
+        assert_eq!(pid.next((4.0, &dt)), 0.25);
+
+        // no leak
+        let mut pid = Pid::new(PidConfig {
+            i_leak: 0.0,
+            ..pid.cfg
+        });
+        pid.set_target(4.0);
+        pid.next((0.0, &dt));
+        assert_eq!(pid.next((4.0, &dt)), 8.0);
+    }
+
+    #[test]
+    fn introspect_pid() {
+        use crate::Value;
+
+        let mut pid = Pid::new(PidConfig {
+            k_p: 2.0,
+            k_i: 0.5,
+            k_d: 0.1,
+            max: Some(100.0),
+            ..Default::default()
+        });
+        pid.set_target(10.0);
+        pid.next((6.0, &Duration::from_secs(1)));
+        let info = pid.introspect();
+        assert_eq!(info.parameter("k_p"), Some(&Value::Decimal(2.0)));
+        assert_eq!(info.parameter("k_i"), Some(&Value::Decimal(0.5)));
+        assert_eq!(info.parameter("k_d"), Some(&Value::Decimal(0.1)));
+        assert_eq!(info.parameter("max"), Some(&Value::Decimal(100.0)));
+        assert_eq!(info.parameter("min"), None);
+        assert_eq!(info.state_variable("i"), Some(&Value::Decimal(2.0)));
+        assert_eq!(info.state_variable("target"), Some(&Value::Decimal(10.0)));
+        assert_eq!(
+            info.state_variable("prev_value"),
+            Some(&Value::Decimal(6.0))
+        );
+    }
+
+    #[test]
+    fn limit_setpoint_and_output_rates() {
+        let pid = Pid::new(PidConfig {
+            k_p: 10.0,
+            ..Default::default()
+        });
+        let mut ctl = RateLimitedPid::new(
+            pid,
+            RateLimiterConfig {
+                rate: 1.0,
+                ..Default::default()
+            },
+            RateLimiterConfig {
+                rate: 5.0,
+                ..Default::default()
+            },
+        );
+        let dt = Duration::from_secs(1);
+        // the first values pass without limitation
+        assert_eq!(ctl.next(((10.0, 10.0), &dt)), 0.0);
+        // setpoint and measurement change at the same time
+        assert_eq!(ctl.next(((20.0, 8.0), &dt)), 5.0);
+        // the setpoint moved by 1.0 only
+        assert_eq!(ctl.pid.state.target, 11.0);
+        // unlimited output: 10.0 * (11.0 - 8.0) = 30.0
+        assert_eq!(ctl.output.state.current, Some(5.0));
+        assert_eq!(ctl.next(((20.0, 8.0), &dt)), 10.0);
+        assert_eq!(ctl.pid.state.target, 12.0);
+        // the output follows a falling command with its own limit
+        assert_eq!(ctl.next(((20.0, 13.0), &dt)), 5.0);
+        assert_eq!(ctl.pid.state.target, 13.0);
+        assert_eq!(ctl.next(((20.0, 14.0), &dt)), 0.0);
+        assert_eq!(ctl.next(((20.0, 14.0), &dt)), 5.0);
+        assert_eq!(ctl.pid.state.target, 15.0);
+
+        ctl.reset();
+        assert_eq!(ctl.next(((20.0, 10.0), &dt)), 100.0);
+    }
+
+    #[test]
+    fn keep_setpoint_feed_forward_and_bias_of_outer_loop_in_cascade() {
+        let outer = PidConfig {
+            k_p: 1.0,
+            k_i: 1.0,
+            k_sp: 1.0,
+            ..Default::default()
+        };
+        let inner = PidConfig {
+            k_p: 1.0,
+            max: Some(10.0),
+            min: Some(0.0),
+            ..Default::default()
+        };
+        let mut cascade = Cascade::new(outer, inner);
+        cascade.outer.set_bias(2.0);
+        cascade.set_target(5.0);
+        let dt = Duration::from_secs(1);
+
+        assert_eq!(cascade.next(((0.0, 0.0), &dt)), 10.0);
+        assert_eq!(cascade.inner.state.target, 12.0);
+        assert_eq!(cascade.inner_saturation(), Some(Saturation::High));
+
+        // the integral is kept, but not the other portions
+        cascade.set_target(6.0);
+        assert_eq!(cascade.next(((0.0, 0.0), &dt)), 10.0);
+        assert_eq!(cascade.outer.state.i, 5.0);
+        assert_eq!(cascade.outer.state.target_feed_forward, 1.0);
+        assert_eq!(cascade.inner.state.target, 14.0);
+    }
+
+    #[test]
+    fn track_external_feedback() {
+        let mut pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            k_i: 1.0,
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        pid.set_target(10.0);
+        assert_eq!(pid.next((0.0, &dt)), 20.0);
+
+        // only 5.0 were applied, e.g. by a selector
+        pid.set_external_feedback(Some(5.0));
+        assert_eq!(pid.next((0.0, &dt)), 15.0);
+        assert_eq!(pid.state.i, 5.0);
+        // the integral follows the feedback instead of winding up
+        assert_eq!(pid.next((0.0, &dt)), 15.0);
+        assert_eq!(pid.state.i, 5.0);
+        assert_eq!(pid.next((0.0, &dt)), 15.0);
+
+        // the command gets applied again
+        pid.set_external_feedback(None);
+        assert_eq!(pid.next((0.0, &dt)), 25.0);
+        assert_eq!(pid.state.i, 15.0);
+
+        // the feedback has no effect on the first step
+        pid.reset();
+        pid.set_target(10.0);
+        pid.set_external_feedback(Some(0.0));
+        assert_eq!(pid.next((0.0, &dt)), 20.0);
+    }
+
+    #[test]
+    fn soft_start_on_enable() {
+        let mut ctl = SoftStartPid::new(
+            Pid::new(PidConfig::default()),
+            SoftStartConfig {
+                duration: Duration::from_secs(4),
+            },
+        );
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next(((100.0, 20.0), &dt)), None);
+        assert_eq!(ctl.setpoint(), None);
+
+        ctl.enable();
+        assert!(ctl.is_enabled());
+        // no initial control error
+        assert_eq!(ctl.next(((100.0, 20.0), &dt)), Some(0.0));
+        assert_eq!(ctl.setpoint(), Some(20.0));
+        // the ramp ignores the measurement
+        ctl.next(((100.0, 30.0), &dt));
+        assert_eq!(ctl.setpoint(), Some(40.0));
+        ctl.next(((100.0, 40.0), &dt));
+        assert_eq!(ctl.setpoint(), Some(60.0));
+        ctl.next(((100.0, 50.0), &dt));
+        assert_eq!(ctl.setpoint(), Some(80.0));
+        ctl.next(((100.0, 60.0), &dt));
+        assert_eq!(ctl.setpoint(), Some(100.0));
+        // target changes are applied immediately after the soft-start
+        ctl.next(((90.0, 60.0), &dt));
+        assert_eq!(ctl.setpoint(), Some(90.0));
+
+        ctl.disable();
+        assert_eq!(ctl.next(((90.0, 60.0), &dt)), None);
+        ctl.enable();
+        ctl.next(((90.0, 70.0), &dt));
+        assert_eq!(ctl.setpoint(), Some(70.0));
+    }
+
+    #[test]
+    fn absorb_manual_bias() {
+        let mut pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            bias_absorption: 0.5,
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        pid.set_target(10.0);
+        assert_eq!(pid.next((10.0, &dt)), 0.0);
+
+        // immediate shift
+        pid.set_bias(8.0);
+        assert_eq!(pid.next((10.0, &dt)), 8.0);
+        assert_eq!(pid.state.i, -4.0);
+        assert_eq!(pid.next((10.0, &dt)), 4.0);
+        assert_eq!(pid.next((10.0, &dt)), 2.0);
+        for _ in 0..30 {
+            pid.next((10.0, &dt));
+        }
+        // the integral compensates the bias
+        assert!((pid.state.i + 8.0).abs() < 1e-6);
+        assert!(pid.next((10.0, &dt)).abs() < 1e-6);
+
+        // removing the bias shifts in the opposite direction
+        pid.set_bias(0.0);
+        assert!((pid.next((10.0, &dt)) + 8.0).abs() < 1e-6);
+
+        // without absorption the bias is kept
+        let mut pid = Pid::new(PidConfig::default());
+        pid.set_bias(3.0);
+        for _ in 0..10 {
+            assert_eq!(pid.next((0.0, &dt)), 3.0);
+        }
+        pid.reset();
+        assert_eq!(pid.next((0.0, &dt)), 3.0);
+    }
+
+    #[test]
+    fn report_faults() {
+        let mut pid = Pid::new(PidConfig {
+            k_p: 2.0,
+            k_i: 1.0,
+            max: Some(50.0),
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        pid.set_target(10.0);
+        assert_eq!(pid.try_next(5.0, None, &dt), Ok((15.0, None)));
+        let state = pid.state;
+
+        // rejected steps don't change the state
+        assert_eq!(pid.try_next(f64::NAN, None, &dt), Err(Fault::InvalidInput));
+        assert_eq!(
+            pid.try_next(f64::INFINITY, None, &dt),
+            Err(Fault::InvalidInput)
+        );
+        assert_eq!(
+            pid.try_next(5.0, Some(SensorFault::Flatline), &dt),
+            Err(Fault::Sensor(SensorFault::Flatline))
+        );
+        assert_eq!(pid.state, state);
+
+        // a saturated output is not a fault
+        assert_eq!(
+            pid.try_next(-10.0, None, &dt),
+            Ok((50.0, Some(Saturation::High)))
+        );
+        assert_eq!(pid.state.saturation, Some(Saturation::High));
+        assert!(Fault::InvalidInput.to_string().contains("finite"));
+    }
+
+    #[test]
+    fn absorb_outer_bias_while_inner_loop_is_saturated() {
+        let outer = PidConfig {
+            k_p: 1.0,
+            k_i: 1.0,
+            bias_absorption: 0.5,
+            ..Default::default()
+        };
+        let inner = PidConfig {
+            k_p: 1.0,
+            max: Some(10.0),
+            min: Some(0.0),
+            ..Default::default()
+        };
+        let mut cascade = Cascade::new(outer, inner);
+        cascade.outer.set_bias(4.0);
+        cascade.set_target(5.0);
+        let dt = Duration::from_secs(1);
+
+        assert_eq!(cascade.next(((0.0, 0.0), &dt)), 10.0);
+        assert_eq!(cascade.inner_saturation(), Some(Saturation::High));
+        assert_eq!(cascade.outer.state.i, 3.0);
+        assert_eq!(cascade.outer.state.absorbed_bias, 2.0);
+
+        // the error is not integrated but the bias is still absorbed
+        for _ in 0..30 {
+            assert_eq!(cascade.next(((0.0, 0.0), &dt)), 10.0);
+            let state = cascade.outer.state;
+            assert_eq!(state.i + state.absorbed_bias, 5.0);
+        }
+        assert!((cascade.outer.state.absorbed_bias - 4.0).abs() < 1e-6);
+        assert!((cascade.outer.state.i - 1.0).abs() < 1e-6);
+        assert_eq!(cascade.outer.state.saturation, None);
+
+        // leaving the saturation without a bump
+        let output = cascade.next(((6.0, 0.0), &dt));
+        assert!((output - 3.0).abs() < 1e-6);
+        assert_eq!(cascade.inner_saturation(), None);
+        let output = cascade.next(((5.0, 3.0), &dt));
+        assert!((output - 1.0).abs() < 1e-6);
+        assert!((cascade.inner.state.target - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn warm_start_with_absorbed_bias() {
+        let mut pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            k_i: 1.0,
+            bias_absorption: 0.5,
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        pid.set_target(10.0);
+        pid.set_bias(5.0);
+        pid.warm_start(10.0, 40.0);
+        assert_eq!(pid.state.i, 35.0);
+        for _ in 0..5 {
+            assert_eq!(pid.next((10.0, &dt)), 40.0);
+        }
+
+        // a later bias change is absorbed as usual
+        pid.set_bias(7.0);
+        assert_eq!(pid.next((10.0, &dt)), 42.0);
+        assert_eq!(pid.next((10.0, &dt)), 41.0);
+    }
 }
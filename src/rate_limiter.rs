@@ -0,0 +1,332 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{TimeStepController, rate_limiter::*};
+//! use std::time::Duration;
+//!
+//! let mut cfg = RateLimiterConfig::default();
+//! cfg.rate = 2.0;     // units per second
+//! cfg.deadband = 0.5; // changes up to 0.5 pass instantly
+//! let mut limiter = RateLimiter::new(cfg);
+//!
+//! let dt = Duration::from_secs(1);
+//! assert_eq!(limiter.next(10.0, &dt), 10.0);
+//! assert_eq!(limiter.next(10.4, &dt), 10.4); // small correction
+//! assert_eq!(limiter.next(20.0, &dt), 12.4); // large move
+//! ```
+
+use super::{Controller, PureController};
+use crate::util::{cap_delta_t, DurationInSeconds};
+use std::time::Duration;
+
+/// A rate limiter implementation
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    cfg: RateLimiterConfig,
+    /// Current rate limiter state
+    pub state: RateLimiterState,
+}
+
+/// Rate limiter configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RateLimiterConfig {
+    /// Maximum rate of change (per second)
+    pub rate: f64,
+    /// Changes up to this amount are passed through without limitation
+    pub deadband: f64,
+    /// Maximum plausible time step (see [crate::util::cap_delta_t])
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_delta_t: Option<Duration>,
+}
+
+/// Internal rate limiter state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RateLimiterState {
+    /// The current (limited) value
+    pub current: Option<f64>,
+}
+
+impl Default for RateLimiterConfig {
+    fn default() -> Self {
+        RateLimiterConfig {
+            rate: 1.0,
+            deadband: 0.0,
+            max_delta_t: None,
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter instance.
+    pub fn new(cfg: RateLimiterConfig) -> Self {
+        RateLimiter {
+            cfg,
+            state: RateLimiterState::default(),
+        }
+    }
+    /// Reset the internal state.
+    ///
+    /// The next value will pass without limitation.
+    pub fn reset(&mut self) {
+        self.state = RateLimiterState::default();
+    }
+}
+
+impl Controller<(f64, &Duration), f64> for RateLimiter {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (target, duration) = input;
+        self.state = self.cfg.next((self.state, target, duration));
+        self.state.current.unwrap_or(target)
+    }
+}
+
+impl PureController<(RateLimiterState, f64, &Duration), RateLimiterState> for RateLimiterConfig {
+    fn next(&self, input: (RateLimiterState, f64, &Duration)) -> RateLimiterState {
+        let (mut state, target, duration) = input;
+        let delta_t = DurationInSeconds::from(cap_delta_t(duration, self.max_delta_t));
+        debug_assert!(delta_t.is_valid());
+        state.current = match state.current {
+            Some(current) => {
+                let delta = target - current;
+                if delta.abs() <= self.deadband {
+                    Some(target)
+                } else {
+                    let max_delta = self.rate * f64::from(delta_t);
+                    Some(current + delta.max(-max_delta).min(max_delta))
+                }
+            }
+            None => Some(target),
+        };
+        state
+    }
+}
+
+/// A motion profile limiter for positioners
+///
+/// In contrast to the [RateLimiter] it limits both the velocity
+/// and the acceleration of the output, so the output follows a
+/// trapezoidal velocity profile towards the target.
+/// Only the last step, that stops exactly at the target,
+/// may decelerate a bit faster.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, rate_limiter::*};
+/// use std::time::Duration;
+///
+/// let mut limiter = MotionLimiter::new(MotionLimiterConfig {
+///     max_velocity: 10.0,
+///     max_acceleration: 5.0,
+///     ..Default::default()
+/// });
+/// let dt = Duration::from_millis(100);
+/// let position = limiter.next(100.0, &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MotionLimiter {
+    cfg: MotionLimiterConfig,
+    /// Current limiter state
+    pub state: MotionLimiterState,
+}
+
+/// Motion limiter configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MotionLimiterConfig {
+    /// Maximum velocity (per second)
+    pub max_velocity: f64,
+    /// Maximum acceleration (per second²)
+    pub max_acceleration: f64,
+    /// Maximum plausible time step (see [crate::util::cap_delta_t])
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_delta_t: Option<Duration>,
+}
+
+/// Internal motion limiter state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MotionLimiterState {
+    /// The current (limited) position
+    pub position: Option<f64>,
+    /// The current velocity
+    pub velocity: f64,
+}
+
+impl Default for MotionLimiterConfig {
+    fn default() -> Self {
+        MotionLimiterConfig {
+            max_velocity: 1.0,
+            max_acceleration: 1.0,
+            max_delta_t: None,
+        }
+    }
+}
+
+impl MotionLimiter {
+    /// Create a new motion limiter instance.
+    pub fn new(cfg: MotionLimiterConfig) -> Self {
+        MotionLimiter {
+            cfg,
+            state: MotionLimiterState::default(),
+        }
+    }
+    /// Reset the internal state.
+    ///
+    /// The next value will pass without limitation.
+    pub fn reset(&mut self) {
+        self.state = MotionLimiterState::default();
+    }
+}
+
+impl Controller<(f64, &Duration), f64> for MotionLimiter {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (target, duration) = input;
+        self.state = self.cfg.next((self.state, target, duration));
+        self.state.position.unwrap_or(target)
+    }
+}
+
+impl PureController<(MotionLimiterState, f64, &Duration), MotionLimiterState>
+    for MotionLimiterConfig
+{
+    fn next(&self, input: (MotionLimiterState, f64, &Duration)) -> MotionLimiterState {
+        let (mut state, target, duration) = input;
+        let delta_t = f64::from(DurationInSeconds::from(cap_delta_t(
+            duration,
+            self.max_delta_t,
+        )));
+        debug_assert!(delta_t >= 0.0);
+        let position = match state.position {
+            Some(position) => position,
+            None => {
+                state.position = Some(target);
+                state.velocity = 0.0;
+                return state;
+            }
+        };
+        let max_dv = self.max_acceleration * delta_t;
+        let distance = target - position;
+
+        // The highest velocity that still allows to stop at the target
+        // (with a constant deceleration within discrete time steps)
+        let a = self.max_acceleration;
+        let stop_velocity =
+            a * ((delta_t * delta_t / 4.0 + 2.0 * distance.abs() / a).sqrt() - delta_t / 2.0);
+        let desired = distance.signum() * stop_velocity.min(self.max_velocity);
+        let velocity = state.velocity + (desired - state.velocity).max(-max_dv).min(max_dv);
+
+        if velocity.abs() <= max_dv && distance.abs() <= (velocity * delta_t).abs() {
+            // Arrived: The remaining distance is shorter than
+            // a step with the lowest velocity, so stop at the target.
+            state.position = Some(target);
+            state.velocity = 0.0;
+        } else {
+            state.position = Some(position + velocity * delta_t);
+            state.velocity = velocity;
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn default_rate_limiter_config() {
+        let cfg = RateLimiterConfig::default();
+        assert_eq!(cfg.rate, 1.0);
+        assert_eq!(cfg.deadband, 0.0);
+    }
+
+    #[test]
+    fn pass_small_changes_and_limit_large_ones() {
+        let cfg = RateLimiterConfig {
+            rate: 2.0,
+            deadband: 1.0,
+            ..Default::default()
+        };
+        let mut limiter = RateLimiter::new(cfg);
+        let dt = Duration::from_millis(500);
+        assert_eq!(limiter.next((50.0, &dt)), 50.0);
+        assert_eq!(limiter.next((50.8, &dt)), 50.8);
+        assert_eq!(limiter.next((50.0, &dt)), 50.0);
+        assert_eq!(limiter.next((49.0, &dt)), 49.0);
+        assert_eq!(limiter.next((55.0, &dt)), 50.0);
+        assert_eq!(limiter.next((55.0, &dt)), 51.0);
+        assert_eq!(limiter.next((55.0, &dt)), 52.0);
+        assert_eq!(limiter.next((55.0, &dt)), 53.0);
+        assert_eq!(limiter.next((55.0, &dt)), 54.0);
+        // the remaining difference is within the deadband
+        assert_eq!(limiter.next((55.0, &dt)), 55.0);
+        assert_eq!(limiter.next((52.0, &dt)), 54.0);
+    }
+
+    #[test]
+    fn reset() {
+        let mut limiter = RateLimiter::new(RateLimiterConfig::default());
+        let dt = Duration::from_secs(1);
+        assert_eq!(limiter.next((0.0, &dt)), 0.0);
+        assert_eq!(limiter.next((10.0, &dt)), 1.0);
+        limiter.reset();
+        assert_eq!(limiter.next((10.0, &dt)), 10.0);
+    }
+
+    #[test]
+    fn limit_velocity_and_acceleration() {
+        let cfg = MotionLimiterConfig {
+            max_velocity: 10.0,
+            max_acceleration: 5.0,
+            ..Default::default()
+        };
+        let mut limiter = MotionLimiter::new(cfg);
+        let dt = Duration::from_millis(100);
+        assert_eq!(limiter.next((0.0, &dt)), 0.0);
+
+        let mut position = 0.0;
+        let mut velocity = 0.0;
+        let mut max_velocity: f64 = 0.0;
+        for _ in 0..200 {
+            let next = limiter.next((37.3, &dt));
+            let v = (next - position) / 0.1;
+            assert!(v.abs() <= 10.0 + 1e-9);
+            if next != 37.3 {
+                assert!((v - velocity).abs() <= 0.5 + 1e-9);
+            }
+            assert!(next <= 37.3);
+            max_velocity = max_velocity.max(v);
+            position = next;
+            velocity = v;
+        }
+        assert_eq!(position, 37.3);
+        assert_eq!(limiter.state.velocity, 0.0);
+        // the velocity limit was reached
+        assert!(max_velocity > 9.9);
+
+        // it takes 2s to accelerate to 10/s
+        let mut limiter = MotionLimiter::new(MotionLimiterConfig {
+            max_velocity: 10.0,
+            max_acceleration: 5.0,
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        limiter.next((0.0, &dt));
+        assert_eq!(limiter.next((100.0, &dt)), 5.0);
+        assert_eq!(limiter.next((100.0, &dt)), 15.0);
+        assert_eq!(limiter.next((100.0, &dt)), 25.0);
+    }
+
+    #[test]
+    fn cap_large_time_steps() {
+        let mut limiter = RateLimiter::new(RateLimiterConfig {
+            rate: 2.0,
+            max_delta_t: Some(Duration::from_secs(1)),
+            ..Default::default()
+        });
+        let dt = Duration::from_secs(1);
+        assert_eq!(limiter.next((0.0, &dt)), 0.0);
+        // a pause of the loop must not result in a jump
+        let dt = Duration::from_secs(60);
+        assert_eq!(limiter.next((100.0, &dt)), 2.0);
+    }
+}
@@ -1,5 +1,5 @@
 use super::*;
-use crate::fsm::*;
+use crate::{fsm::*, util::DurationInSeconds};
 use std::{collections::HashMap, io, result, time::Duration};
 
 /// A simple synchronous closed-loop runtime.
@@ -13,6 +13,13 @@ pub struct SyncRuntime {
     pub actions: Vec<Action>,
     /// Finite State Machines
     pub state_machines: HashMap<String, StateMachine>,
+    /// Wires that connect loop outputs with loop inputs
+    pub wires: Vec<Wire>,
+    /// Reset conditions of latching rules (by rule ID)
+    ///
+    /// Once such a rule became active, it stays active
+    /// until its reset condition is true.
+    pub resets: HashMap<String, BoolExpr<Comparison>>,
 }
 
 impl Default for SyncRuntime {
@@ -22,6 +29,8 @@ impl Default for SyncRuntime {
             rules: vec![],
             actions: vec![],
             state_machines: HashMap::new(),
+            wires: vec![],
+            resets: HashMap::new(),
         }
     }
 }
@@ -35,28 +44,80 @@ pub struct Error<T> {
 
 type Result<T> = result::Result<T, Error<T>>;
 
-//TODO: tidy up!
+/// A report of a rule evaluation.
+#[derive(Debug, Default)]
+pub struct EvaluationReport {
+    /// The IDs of the rules that fired
+    pub fired: Vec<String>,
+    /// The outputs that were written by the actions of the fired rules
+    /// and of the state machine transitions
+    pub writes: Vec<OutputWrite>,
+    /// The errors of loops and rules (by their IDs)
+    pub errors: Vec<(String, io::Error)>,
+}
+
+/// A report of a [RuleSet] evaluation.
+#[derive(Debug)]
+pub struct RuleSetReport {
+    /// The ID of the rule set
+    pub id: String,
+    /// Whether the set was enabled
+    pub enabled: bool,
+    /// The report of the rules of the set
+    pub report: EvaluationReport,
+}
+
+/// An output that was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputWrite {
+    /// The ID of the output
+    pub id: String,
+    /// The value before the write
+    pub old: Option<Value>,
+    /// The written value
+    pub new: Value,
+}
+
+/// A cycle fails if a loop or rule failed (see [SyncRuntime::run]).
 impl<'a> PureController<(&'a SystemState, &'a Duration), Result<SystemState>> for SyncRuntime {
     fn next(&self, input: (&SystemState, &Duration)) -> Result<SystemState> {
-        let (orig_state, dt) = input;
+        let (state, dt) = input;
+        let (state, report) = self.run(state, dt);
+        if !report.errors.is_empty() {
+            return Err(Error {
+                state,
+                causes: report.errors.into_iter().map(|(_, e)| e).collect(),
+            });
+        }
+        Ok(state)
+    }
+}
+
+//TODO: tidy up!
+impl SyncRuntime {
+    /// Run a single cycle.
+    ///
+    /// A loop or rule that fails doesn't stop the cycle,
+    /// instead its error is recorded in the report.
+    pub fn run(&self, orig_state: &SystemState, dt: &Duration) -> (SystemState, EvaluationReport) {
         let mut state = orig_state.clone();
-        let mut errors = vec![];
+        let mut report = EvaluationReport::default();
 
         for (id, s) in &orig_state.setpoints {
             if self.loops.iter().any(|l| l.id == *id) {
                 if let Some(c) = orig_state.controllers.get(id) {
-                    if let Value::Decimal(v) = s {
+                    if let Ok(v) = s.to_decimal(BitEncoding::Strict) {
                         match c {
                             ControllerState::Pid(pid) => {
                                 let mut pid = *pid;
-                                pid.target = *v;
+                                pid.target = v;
                                 state
                                     .controllers
                                     .insert(id.clone(), ControllerState::Pid(pid));
                             }
                             ControllerState::BangBang(bb) => {
                                 let mut bb = *bb;
-                                bb.threshold = *v;
+                                bb.threshold = v;
                                 state
                                     .controllers
                                     .insert(id.clone(), ControllerState::BangBang(bb));
@@ -67,6 +128,11 @@ impl<'a> PureController<(&'a SystemState, &'a Duration), Result<SystemState>> fo
             }
         }
 
+        // Wires are resolved before the loops run and again after
+        // each loop, so a loop reads the outputs of all loops
+        // that were declared before it within the same cycle.
+        self.apply_wires(None, &mut state);
+
         for l in 0..self.loops.len() {
             let this_loop = &self.loops[l];
             let loop_id = &self.loops[l].id;
@@ -90,9 +156,10 @@ impl<'a> PureController<(&'a SystemState, &'a Duration), Result<SystemState>> fo
                         let (new_controller, new_io) = x;
                     state.io = new_io;
                     state.controllers.insert((*loop_id).clone(), new_controller);
+                        self.apply_wires(Some(&this_loop.outputs), &mut state);
                     }
                     Err(err) => {
-                        errors.push(err);
+                        report.errors.push((loop_id.clone(), err));
                     }
                 }
             }
@@ -112,34 +179,15 @@ impl<'a> PureController<(&'a SystemState, &'a Duration), Result<SystemState>> fo
                 }
             }
         }
-        match self.rules_state(&state) {
-            Ok(rules) => {
-                state.rules = rules;
-            }
-            Err(err) => {
-                state.rules = err.state;
-                for e in err.causes {
-                    errors.push(e);
-                }
-            }
-        }
-
-        let rule_actions = state
-            .rules
-            .iter()
-            .filter(|(_, active)| **active)
-            .filter_map(|(r_id, _)| {
-                if let Some(r) = self.rules.iter().find(|r| r.id == *r_id) {
-                    Some(&r.actions)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-
-        for x in rule_actions {
-            self.apply_actions(&x, orig_state, &mut state);
-        }
+        let rules_state = state.clone();
+        state.rules.clear();
+        self.eval_rules(
+            &self.rules,
+            &rules_state,
+            orig_state,
+            &mut state,
+            &mut report,
+        );
 
         let mut actions = vec![];
 
@@ -155,26 +203,184 @@ impl<'a> PureController<(&'a SystemState, &'a Duration), Result<SystemState>> fo
         }
 
         for x in actions {
-            self.apply_actions(&x, orig_state, &mut state);
+            let writes = self.apply_actions(&x, orig_state, &mut state);
+            report.writes.extend(writes);
         }
 
-        if !errors.is_empty() {
-            return Err(Error {
-                state,
-                causes: errors,
+        (state, report)
+    }
+
+    /// Evaluate all [Rule]s and apply the actions of the active ones.
+    ///
+    /// Rules are evaluated in the order of their declaration.
+    /// A rule that can't be evaluated doesn't stop the evaluation
+    /// of the remaining rules, instead its error is recorded in the report.
+    pub fn run_rules(&self, state: &SystemState) -> (SystemState, EvaluationReport) {
+        let mut next_state = state.clone();
+        let mut report = EvaluationReport::default();
+        self.eval_rules(&self.rules, state, state, &mut next_state, &mut report);
+        (next_state, report)
+    }
+
+    /// Evaluate [RuleSet]s and apply the actions of the active rules.
+    ///
+    /// The rules of a disabled set are not evaluated,
+    /// they are marked as inactive instead.
+    /// The report of each set is returned in the order of the sets.
+    pub fn run_rule_sets(
+        &self,
+        sets: &[RuleSet],
+        state: &SystemState,
+    ) -> (SystemState, Vec<RuleSetReport>) {
+        let mut next_state = state.clone();
+        let mut reports = vec![];
+        for set in sets {
+            let mut report = EvaluationReport::default();
+            if set.enabled {
+                self.eval_rules(&set.rules, state, state, &mut next_state, &mut report);
+            } else {
+                for r in &set.rules {
+                    next_state.rules.insert(r.id.clone(), false);
+                }
+            }
+            reports.push(RuleSetReport {
+                id: set.id.clone(),
+                enabled: set.enabled,
+                report,
             });
         }
-        Ok(state)
+        (next_state, reports)
     }
-}
 
-impl SyncRuntime {
-    /// Check for active [Rule]s.
-    fn rules_state(&self, state: &SystemState) -> Result<HashMap<String, bool>> {
+    /// Evaluate rules against `state` and apply the actions of the active
+    /// ones to `next_state`, reading the action values from `src`.
+    fn eval_rules(
+        &self,
+        rules: &[Rule],
+        state: &SystemState,
+        src: &SystemState,
+        next_state: &mut SystemState,
+        report: &mut EvaluationReport,
+    ) {
+        for r in rules {
+            match self.eval_rule(r, state) {
+                Ok(active) => {
+                    next_state.rules.insert(r.id.clone(), active);
+                    if active {
+                        report.fired.push(r.id.clone());
+                        let writes = self.apply_actions(&r.actions, src, next_state);
+                        report.writes.extend(writes);
+                    }
+                }
+                Err(err) => {
+                    next_state.rules.remove(&r.id);
+                    report.errors.push((r.id.clone(), err));
+                }
+            }
+        }
+    }
+
+    /// Run a single cycle and publish the resulting state changes.
+    ///
+    /// If the cycle fails the changes up to the (partial)
+    /// state of the error are published.
+    // The error carries the state like `next` does.
+    #[allow(clippy::result_large_err)]
+    pub fn next_with_events(
+        &self,
+        state: &SystemState,
+        dt: &Duration,
+        bus: &mut EventBus,
+    ) -> Result<SystemState> {
+        let res = self.next((state, dt));
+        match res {
+            Ok(ref next) => bus.publish_changes(state, next),
+            Err(ref err) => bus.publish_changes(state, &err.state),
+        }
+        res
+    }
+
+    /// Run a single cycle and periodically persist the resulting I/O state.
+    ///
+    /// If the cycle fails the (partial) state of the error is persisted.
+    /// A failure to persist the state is added to the causes of the error.
+    // The error carries the state like `next` does.
+    #[allow(clippy::result_large_err)]
+    pub fn next_with_persistence<S: StateStore>(
+        &self,
+        state: &SystemState,
+        dt: &Duration,
+        persistence: &mut PeriodicPersistence<S>,
+    ) -> Result<SystemState> {
+        let res = self.next((state, dt));
+        let io_state = match res {
+            Ok(ref next) => &next.io,
+            Err(ref err) => &err.state.io,
+        };
+        match (persistence.persist(io_state, dt), res) {
+            (Ok(_), res) => res,
+            (Err(cause), Ok(state)) => Err(Error {
+                state,
+                causes: vec![cause],
+            }),
+            (Err(cause), Err(mut err)) => {
+                err.causes.push(cause);
+                Err(err)
+            }
+        }
+    }
+
+    /// Write the outputs of the fired rules (see [EvaluationReport::writes])
+    /// to an I/O system in the order they were written.
+    ///
+    /// Wrap the I/O system with a [strict](SequencedIo::strict) [SequencedIo]
+    /// to reject rules that violate a declared write order.
+    /// The first failed write stops the remaining ones.
+    pub fn write_outputs<IO>(&self, report: &EvaluationReport, io: &mut IO) -> io::Result<()>
+    where
+        IO: SyncIoSystem,
+    {
+        for w in &report.writes {
+            io.write(&w.id, &w.new)?;
+        }
+        Ok(())
+    }
+
+    /// Add a rule whose condition is wrapped with a [Hysteresis].
+    ///
+    /// The rule becomes active with the `set` comparison and
+    /// stays active until the `reset` comparison is true.
+    pub fn add_rule_with_hysteresis<S: Into<String>>(
+        &mut self,
+        id: S,
+        hysteresis: Hysteresis,
+        actions: Vec<String>,
+    ) {
+        let id = id.into();
+        self.resets
+            .insert(id.clone(), hysteresis.reset().clone().into());
+        self.rules.push(Rule {
+            id,
+            condition: hysteresis.set().clone().into(),
+            actions,
+        });
+    }
+
+    /// Evaluate a single rule with respect to its state of the last cycle.
+    fn eval_rule(&self, rule: &Rule, state: &SystemState) -> io::Result<bool> {
+        let active = state.rules.get(&rule.id).copied().unwrap_or(false);
+        match self.resets.get(&rule.id) {
+            Some(reset) if active => Ok(!reset.eval(state)?),
+            _ => rule.condition.eval(state),
+        }
+    }
+
+    /// Check for active [Rule]s without applying their actions.
+    pub fn rules_state(&self, state: &SystemState) -> Result<HashMap<String, bool>> {
         let mut rules_state = HashMap::new();
         let mut errors = vec![];
         for r in &self.rules {
-            match r.condition.eval(state) {
+            match self.eval_rule(r, state) {
                 Ok(r_state) => {
                     rules_state.insert(r.id.clone(), r_state);
                 }
@@ -192,6 +398,75 @@ impl SyncRuntime {
         Ok(rules_state)
     }
 
+    /// Initialize the controller states from the current process values.
+    ///
+    /// Before the first cycle the measurement and the present output of each
+    /// loop is read so that the controllers resume control from the current
+    /// operating point instead of starting from zero.
+    /// Loops that already have a controller state are skipped.
+    pub fn warm_start<IO>(&self, io: &mut IO, state: &mut SystemState) -> io::Result<()>
+    where
+        IO: SyncIoSystem,
+    {
+        for l in &self.loops {
+            if state.controllers.contains_key(&l.id) {
+                continue;
+            }
+            if l.inputs.len() != 1 || l.outputs.len() != 1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Loop has invalid length of inputs/outputs",
+                ));
+            }
+            let actual = io.read(&l.inputs[0])?;
+            let output = io.read_output(&l.outputs[0])?;
+            self.initialize_controller_state(l, state);
+            let setpoint = state
+                .setpoints
+                .get(&l.id)
+                .and_then(|v| v.to_decimal(BitEncoding::Strict).ok());
+            let controller = match (&l.controller, state.controllers.get(&l.id)) {
+                (ControllerConfig::Pid(cfg), Some(ControllerState::Pid(s))) => {
+                    let mut s = *s;
+                    if let Some(v) = setpoint {
+                        s.target = v;
+                    }
+                    let y = output.and_then(|v| v.to_decimal(BitEncoding::Strict).ok());
+                    match (actual.to_decimal(BitEncoding::Strict), y) {
+                        (Ok(x), Some(y)) => ControllerState::Pid(cfg.warm_start(s, x, y)),
+                        _ => ControllerState::Pid(s),
+                    }
+                }
+                (ControllerConfig::BangBang(_), Some(ControllerState::BangBang(s))) => {
+                    let mut s = *s;
+                    if let Some(v) = setpoint {
+                        s.threshold = v;
+                    }
+                    if let Some(Value::Bit(b)) = output {
+                        s.current = b;
+                    }
+                    ControllerState::BangBang(s)
+                }
+                _ => continue,
+            };
+            state.controllers.insert(l.id.clone(), controller);
+        }
+        Ok(())
+    }
+
+    /// Copy the values of wired outputs to their inputs.
+    ///
+    /// If `outputs` is given, only wires starting at one of them are applied.
+    fn apply_wires(&self, outputs: Option<&[String]>, state: &mut SystemState) {
+        for w in &self.wires {
+            if outputs.map(|o| o.contains(&w.from)).unwrap_or(true) {
+                if let Some(v) = state.io.outputs.get(&w.from) {
+                    state.io.inputs.insert(w.to.clone(), v.clone());
+                }
+            }
+        }
+    }
+
     fn initialize_controller_state(&self, l: &Loop, state: &mut SystemState) {
         match l.controller {
             ControllerConfig::Pid(ref cfg) => {
@@ -211,22 +486,37 @@ impl SyncRuntime {
         }
     }
 
-    fn apply_actions(&self, actions: &[String], orig_state: &SystemState, state: &mut SystemState) {
+    /// Apply actions and return the outputs that were written.
+    ///
+    /// Values of sources that don't exist are skipped.
+    fn apply_actions(
+        &self,
+        actions: &[String],
+        orig_state: &SystemState,
+        state: &mut SystemState,
+    ) -> Vec<OutputWrite> {
+        let mut writes = vec![];
         for a_id in actions {
             if let Some(a) = self.actions.iter().find(|a| a.id == *a_id) {
                 for (k, src) in &a.outputs {
-                    if let Some(v) = orig_state.get(&src) {
-                        state.io.outputs.insert(k.clone(), v.clone());
+                    if let Ok(v) = get_val(src, orig_state) {
+                        let new = v.into_owned();
+                        let old = state.io.outputs.insert(k.clone(), new.clone());
+                        writes.push(OutputWrite {
+                            id: k.clone(),
+                            old,
+                            new,
+                        });
                     }
                 }
                 for (k, src) in &a.setpoints {
-                    if let Some(v) = orig_state.get(&src) {
-                        state.setpoints.insert(k.clone(), v.clone());
+                    if let Ok(v) = get_val(src, orig_state) {
+                        state.setpoints.insert(k.clone(), v.into_owned());
                     }
                 }
                 for (k, src) in &a.memory {
-                    if let Some(v) = orig_state.get(&src) {
-                        state.io.mem.insert(k.clone(), v.clone());
+                    if let Ok(v) = get_val(src, orig_state) {
+                        state.io.mem.insert(k.clone(), v.into_owned());
                     }
                 }
                 for (id, ctl) in &a.controllers {
@@ -260,6 +550,235 @@ impl SyncRuntime {
                 }
             }
         }
+        writes
+    }
+}
+
+/// A timer that accumulates the time steps of a loop.
+///
+/// It's the foundation of delays and one-shots.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{Controller, Timer};
+/// use std::time::Duration;
+///
+/// let mut timer = Timer::new(Duration::from_secs(5));
+/// timer.start();
+/// let dt = Duration::from_millis(100);
+/// let done = timer.next(&dt);
+/// println!("{:?} elapsed", timer.elapsed());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Timer {
+    preset: Duration,
+    /// Current timer state
+    pub state: TimerState,
+}
+
+/// Internal timer state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TimerState {
+    /// Whether the timer is running
+    pub running: bool,
+    /// The accumulated time
+    pub elapsed: Duration,
+}
+
+impl Timer {
+    /// Create a stopped timer that is done after `preset`.
+    pub fn new(preset: Duration) -> Self {
+        Timer {
+            preset,
+            state: TimerState::default(),
+        }
+    }
+    /// The configured preset.
+    pub fn preset(&self) -> Duration {
+        self.preset
+    }
+    /// Start (or continue) accumulating time.
+    pub fn start(&mut self) {
+        self.state.running = true;
+    }
+    /// Stop accumulating time.
+    ///
+    /// The elapsed time is kept.
+    pub fn stop(&mut self) {
+        self.state.running = false;
+    }
+    /// Stop the timer and clear the elapsed time.
+    pub fn reset(&mut self) {
+        self.state = TimerState::default();
+    }
+    /// Check if the timer is running.
+    pub fn is_running(&self) -> bool {
+        self.state.running
+    }
+    /// Check if the preset was reached.
+    pub fn is_done(&self) -> bool {
+        self.state.elapsed >= self.preset
+    }
+    /// The accumulated time (at most the preset).
+    pub fn elapsed(&self) -> Duration {
+        self.state.elapsed
+    }
+    /// The time until the preset is reached.
+    pub fn remaining(&self) -> Duration {
+        self.preset.saturating_sub(self.state.elapsed)
+    }
+}
+
+/// Returns `true` if the preset was reached.
+impl Controller<&Duration, bool> for Timer {
+    fn next(&mut self, duration: &Duration) -> bool {
+        if self.state.running {
+            self.state.elapsed = (self.state.elapsed + *duration).min(self.preset);
+        }
+        self.is_done()
+    }
+}
+
+/// Aggregates runtime statistics for monitoring.
+///
+/// The metrics are updated after each cycle. Raised alarms are
+/// counted by passing the runtime [Event]s to [Metrics::handle].
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+/// use std::time::{Duration, Instant};
+///
+/// let rt = SyncRuntime::default();
+/// let mut metrics = Metrics::default();
+/// let mut state = SystemState::default();
+/// let dt = Duration::from_millis(100);
+///
+/// let start = Instant::now();
+/// state = rt.next((&state, &dt)).unwrap();
+/// metrics.update(&rt, &state, &start.elapsed());
+///
+/// for (name, value) in metrics.snapshot() {
+///     println!("{} = {:?}", name, value);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    cycles: u64,
+    total_cycle_time: Duration,
+    max_cycle_time: Duration,
+    loops: HashMap<String, LoopMetrics>,
+    alarms: HashMap<String, u64>,
+}
+
+/// Statistics of a single [Loop]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LoopMetrics {
+    /// The number of cycles the loop was active
+    pub active_cycles: u64,
+    /// The number of active cycles with a saturated output
+    pub saturated_cycles: u64,
+    /// The last output value
+    pub output: Option<f64>,
+}
+
+impl LoopMetrics {
+    /// The percentage of active cycles with a saturated output.
+    pub fn saturation_percent(&self) -> f64 {
+        if self.active_cycles == 0 {
+            0.0
+        } else {
+            100.0 * self.saturated_cycles as f64 / self.active_cycles as f64
+        }
+    }
+}
+
+impl Metrics {
+    /// Record a finished cycle of the runtime.
+    ///
+    /// The `cycle_time` is the (measured) duration of the cycle.
+    pub fn update(&mut self, rt: &SyncRuntime, state: &SystemState, cycle_time: &Duration) {
+        self.cycles += 1;
+        self.total_cycle_time += *cycle_time;
+        self.max_cycle_time = self.max_cycle_time.max(*cycle_time);
+        for l in &rt.loops {
+            let m = self.loops.entry(l.id.clone()).or_default();
+            if state.inactive_loops.contains(&l.id) {
+                continue;
+            }
+            m.active_cycles += 1;
+            m.output = l
+                .outputs
+                .first()
+                .and_then(|id| state.io.outputs.get(id))
+                .and_then(|v| v.to_decimal(BitEncoding::permissive()).ok());
+            if let (ControllerConfig::Pid(cfg), Some(output)) = (&l.controller, m.output) {
+                let saturated = cfg.min.map(|min| output <= min).unwrap_or(false)
+                    || cfg.max.map(|max| output >= max).unwrap_or(false);
+                if saturated {
+                    m.saturated_cycles += 1;
+                }
+            }
+        }
+    }
+    /// The number of recorded cycles.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+    /// The average cycle time.
+    pub fn avg_cycle_time(&self) -> Option<Duration> {
+        if self.cycles == 0 {
+            return None;
+        }
+        let nanos = self.total_cycle_time.as_nanos() / u128::from(self.cycles);
+        Some(Duration::from_nanos(nanos as u64))
+    }
+    /// The longest cycle time.
+    pub fn max_cycle_time(&self) -> Duration {
+        self.max_cycle_time
+    }
+    /// The statistics of a loop.
+    pub fn loop_metrics(&self, id: &str) -> Option<&LoopMetrics> {
+        self.loops.get(id)
+    }
+    /// The number of times an alarm was raised.
+    pub fn alarm_count(&self, id: &str) -> u64 {
+        self.alarms.get(id).copied().unwrap_or(0)
+    }
+    /// Export all metrics as named values.
+    ///
+    /// Durations are given in seconds and the names are sorted.
+    pub fn snapshot(&self) -> Vec<(String, Value)> {
+        let secs = |d: Duration| Value::Decimal(f64::from(DurationInSeconds::from(d)));
+        let mut values = vec![
+            ("cycles".to_string(), Value::Integer(self.cycles as i64)),
+            ("cycle_time.max".to_string(), secs(self.max_cycle_time)),
+        ];
+        if let Some(avg) = self.avg_cycle_time() {
+            values.push(("cycle_time.avg".into(), secs(avg)));
+        }
+        for (id, m) in &self.loops {
+            values.push((
+                format!("loop.{}.saturation", id),
+                Value::Decimal(m.saturation_percent()),
+            ));
+            if let Some(output) = m.output {
+                values.push((format!("loop.{}.output", id), Value::Decimal(output)));
+            }
+        }
+        for (id, count) in &self.alarms {
+            values.push((format!("alarm.{}.count", id), Value::Integer(*count as i64)));
+        }
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        values
+    }
+}
+
+impl EventSink for Metrics {
+    fn handle(&mut self, event: &Event) {
+        if let Event::AlarmRaised(id) = event {
+            *self.alarms.entry(id.clone()).or_insert(0) += 1;
+        }
     }
 }
 
@@ -536,6 +1055,7 @@ mod tests {
                 p: 40.0,
                 i: 3000.0,
                 d: 0.0,
+                ..Default::default()
             })
         );
         // trigger the rule
@@ -551,6 +1071,7 @@ mod tests {
                 p: 0.0,
                 i: 0.0,
                 d: 0.0,
+                ..Default::default()
             })
         );
     }
@@ -763,6 +1284,7 @@ mod tests {
                 d: 0.0,
                 prev_value: Some(0.0),
                 target: 10.0,
+                ..Default::default()
             })
         );
     }
@@ -950,4 +1472,914 @@ mod tests {
         assert!(err.state.io.outputs.get("actuator_0").is_none());
         assert!(err.state.io.outputs.get("actuator_1").is_some());
     }
+
+    #[test]
+    fn warm_start_controllers() {
+        let pid_cfg = PidConfig {
+            k_p: 2.0,
+            k_i: 1.0,
+            default_target: 10.0,
+            ..Default::default()
+        };
+        let rt = SyncRuntime {
+            loops: vec![
+                Loop {
+                    id: "pid".into(),
+                    inputs: vec!["sensor".into()],
+                    outputs: vec!["actuator".into()],
+                    controller: ControllerConfig::Pid(pid_cfg),
+                },
+                Loop {
+                    id: "bb".into(),
+                    inputs: vec!["sensor".into()],
+                    outputs: vec!["heater".into()],
+                    controller: ControllerConfig::BangBang(BangBangConfig::default()),
+                },
+            ],
+            ..Default::default()
+        };
+        let mut state = SystemState::default();
+        state.io.inputs.insert("sensor".into(), 8.0.into());
+        state.io.outputs.insert("actuator".into(), 50.0.into());
+        state.io.outputs.insert("heater".into(), true.into());
+
+        let mut io = state.io.clone();
+        rt.warm_start(&mut io, &mut state).unwrap();
+        match state.controllers.get("pid").unwrap() {
+            ControllerState::Pid(s) => {
+                assert_eq!(s.i, 46.0);
+                assert_eq!(s.prev_value, Some(8.0));
+            }
+            _ => panic!("invalid controller state"),
+        }
+        match state.controllers.get("bb").unwrap() {
+            ControllerState::BangBang(s) => assert!(s.current),
+            _ => panic!("invalid controller state"),
+        }
+
+        let state = rt.next((&state, &Duration::from_secs(0))).unwrap();
+        assert_eq!(
+            *state.io.outputs.get("actuator").unwrap(),
+            Value::Decimal(50.0)
+        );
+    }
+
+    #[test]
+    fn resolve_wires_within_one_cycle() {
+        let outer = PidConfig {
+            k_p: 2.0,
+            default_target: 10.0,
+            ..Default::default()
+        };
+        let inner = PidConfig {
+            k_p: 0.5,
+            ..Default::default()
+        };
+        let rt = SyncRuntime {
+            loops: vec![
+                Loop {
+                    id: "outer".into(),
+                    inputs: vec!["level".into()],
+                    outputs: vec!["flow-setpoint".into()],
+                    controller: ControllerConfig::Pid(outer),
+                },
+                Loop {
+                    id: "inner".into(),
+                    inputs: vec!["inner-input".into()],
+                    outputs: vec!["valve".into()],
+                    controller: ControllerConfig::Pid(inner),
+                },
+            ],
+            wires: vec![Wire {
+                from: "flow-setpoint".into(),
+                to: "inner-input".into(),
+            }],
+            ..Default::default()
+        };
+        let dt = Duration::from_secs(1);
+        let mut state = SystemState::default();
+        state.io.inputs.insert("level".into(), 8.0.into());
+        let state = rt.next((&state, &dt)).unwrap();
+        assert_eq!(
+            *state.io.outputs.get("flow-setpoint").unwrap(),
+            Value::Decimal(4.0)
+        );
+        assert_eq!(
+            *state.io.inputs.get("inner-input").unwrap(),
+            Value::Decimal(4.0)
+        );
+        assert_eq!(
+            *state.io.outputs.get("valve").unwrap(),
+            Value::Decimal(-2.0)
+        );
+    }
+
+    #[test]
+    fn resolve_wires_of_later_loops_in_the_next_cycle() {
+        let rt = SyncRuntime {
+            loops: vec![
+                Loop {
+                    id: "a".into(),
+                    inputs: vec!["b-out".into()],
+                    outputs: vec!["a-out".into()],
+                    controller: ControllerConfig::Pid(PidConfig::default()),
+                },
+                Loop {
+                    id: "b".into(),
+                    inputs: vec!["x".into()],
+                    outputs: vec!["b-out-raw".into()],
+                    controller: ControllerConfig::Pid(PidConfig::default()),
+                },
+            ],
+            wires: vec![Wire {
+                from: "b-out-raw".into(),
+                to: "b-out".into(),
+            }],
+            ..Default::default()
+        };
+        let dt = Duration::from_secs(1);
+        let mut state = SystemState::default();
+        state.io.inputs.insert("x".into(), 1.0.into());
+        state.io.inputs.insert("b-out".into(), 0.0.into());
+        let state = rt.next((&state, &dt)).unwrap();
+        assert_eq!(*state.io.outputs.get("a-out").unwrap(), Value::Decimal(0.0));
+        assert_eq!(*state.io.inputs.get("b-out").unwrap(), Value::Decimal(-1.0));
+        let state = rt.next((&state, &dt)).unwrap();
+        assert_eq!(*state.io.outputs.get("a-out").unwrap(), Value::Decimal(1.0));
+    }
+
+    #[test]
+    fn report_rule_evaluation() {
+        let mut outputs = HashMap::new();
+        outputs.insert("y".into(), Source::Const(1.0.into()));
+        let actions = vec![Action {
+            id: "set-y".into(),
+            outputs,
+            memory: HashMap::new(),
+            setpoints: HashMap::new(),
+            controllers: HashMap::new(),
+            timeouts: HashMap::new(),
+        }];
+        let rules = vec![
+            Rule {
+                id: "broken".into(),
+                condition: BoolExpr::Eval(Source::In("missing".into()).cmp_gt(0.0.into())),
+                actions: vec!["set-y".into()],
+            },
+            Rule {
+                id: "x-high".into(),
+                condition: BoolExpr::Eval(Source::In("x".into()).cmp_gt(5.0.into())),
+                actions: vec!["set-y".into()],
+            },
+            Rule {
+                id: "x-low".into(),
+                condition: BoolExpr::Eval(Source::In("x".into()).cmp_lt(5.0.into())),
+                actions: vec![],
+            },
+        ];
+        let rt = SyncRuntime {
+            actions,
+            rules,
+            ..Default::default()
+        };
+        let mut state = SystemState::default();
+        state.io.inputs.insert("x".into(), 6.0.into());
+        state.io.outputs.insert("y".into(), 0.0.into());
+
+        let (state, report) = rt.run_rules(&state);
+        assert_eq!(report.fired, vec!["x-high".to_string()]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, "broken");
+        assert_eq!(report.errors[0].1.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            report.writes,
+            vec![OutputWrite {
+                id: "y".into(),
+                old: Some(0.0.into()),
+                new: 1.0.into(),
+            }]
+        );
+        assert_eq!(*state.io.outputs.get("y").unwrap(), Value::Decimal(1.0));
+        assert_eq!(state.rules.get("x-high"), Some(&true));
+        assert_eq!(state.rules.get("x-low"), Some(&false));
+        assert_eq!(state.rules.get("broken"), None);
+    }
+
+    #[test]
+    fn toggle_rule_sets() {
+        let mut outputs = HashMap::new();
+        outputs.insert("pump".into(), Source::Const(true.into()));
+        let rt = SyncRuntime {
+            actions: vec![Action {
+                id: "start-pump".into(),
+                outputs,
+                memory: HashMap::new(),
+                setpoints: HashMap::new(),
+                controllers: HashMap::new(),
+                timeouts: HashMap::new(),
+            }],
+            ..Default::default()
+        };
+        let mut sets = vec![
+            RuleSet {
+                id: "filling".into(),
+                enabled: true,
+                rules: vec![
+                    Rule {
+                        id: "level-low".into(),
+                        condition: BoolExpr::Eval(Source::In("level".into()).cmp_lt(2.0.into())),
+                        actions: vec!["start-pump".into()],
+                    },
+                    Rule {
+                        id: "always".into(),
+                        condition: BoolExpr::True,
+                        actions: vec![],
+                    },
+                ],
+            },
+            RuleSet {
+                id: "other".into(),
+                enabled: true,
+                rules: vec![Rule {
+                    id: "other-always".into(),
+                    condition: BoolExpr::True,
+                    actions: vec![],
+                }],
+            },
+        ];
+        let mut state = SystemState::default();
+        state.io.inputs.insert("level".into(), 1.0.into());
+
+        let (next, reports) = rt.run_rule_sets(&sets, &state);
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].id, "filling");
+        assert!(reports[0].enabled);
+        assert_eq!(reports[0].report.fired, vec!["level-low", "always"]);
+        assert_eq!(reports[0].report.writes.len(), 1);
+        assert_eq!(reports[1].report.fired, vec!["other-always"]);
+        assert_eq!(*next.io.outputs.get("pump").unwrap(), Value::Bit(true));
+        assert_eq!(next.rules.get("level-low"), Some(&true));
+
+        sets[0].enabled = false;
+        let (next, reports) = rt.run_rule_sets(&sets, &state);
+        assert!(!reports[0].enabled);
+        assert!(reports[0].report.fired.is_empty());
+        assert!(reports[0].report.writes.is_empty());
+        assert!(reports[0].report.errors.is_empty());
+        assert_eq!(next.io.outputs.get("pump"), None);
+        assert_eq!(next.rules.get("level-low"), Some(&false));
+        assert_eq!(next.rules.get("always"), Some(&false));
+        // other sets are not affected
+        assert_eq!(reports[1].report.fired, vec!["other-always"]);
+        assert_eq!(next.rules.get("other-always"), Some(&true));
+    }
+
+    #[test]
+    fn publish_events_of_a_cycle() {
+        use std::{cell::RefCell, rc::Rc};
+
+        struct Recorder(Rc<RefCell<Vec<Event>>>);
+
+        impl EventSink for Recorder {
+            fn handle(&mut self, event: &Event) {
+                self.0.borrow_mut().push(event.clone());
+            }
+        }
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut bus = EventBus::default()
+            .with_sink(Recorder(events.clone()))
+            .with_alarm("too_hot");
+
+        let mut outputs = HashMap::new();
+        outputs.insert("horn".to_string(), Source::Const(true.into()));
+        let rt = SyncRuntime {
+            loops: vec![Loop {
+                id: "foo".into(),
+                inputs: vec!["sensor".into()],
+                outputs: vec!["actuator".into()],
+                controller: ControllerConfig::Pid(PidConfig {
+                    k_p: 2.0,
+                    default_target: 10.0,
+                    ..Default::default()
+                }),
+            }],
+            rules: vec![Rule {
+                id: "too_hot".into(),
+                condition: BoolExpr::Eval(Source::In("sensor".into()).cmp_gt(50.0.into())),
+                actions: vec!["alarm".into()],
+            }],
+            actions: vec![Action {
+                id: "alarm".into(),
+                outputs,
+                setpoints: HashMap::new(),
+                memory: HashMap::new(),
+                timeouts: HashMap::new(),
+                controllers: HashMap::new(),
+            }],
+            ..Default::default()
+        };
+        let dt = Duration::from_secs(1);
+        let mut state = SystemState::default();
+        state.io.inputs.insert("sensor".into(), 0.0.into());
+        let mut state = rt.next_with_events(&state, &dt, &mut bus).unwrap();
+        assert_eq!(
+            *events.borrow(),
+            vec![Event::OutputWritten(OutputWrite {
+                id: "actuator".into(),
+                old: None,
+                new: Value::Decimal(20.0),
+            })]
+        );
+
+        events.borrow_mut().clear();
+        state.io.inputs.insert("sensor".into(), 60.0.into());
+        let mut state = rt.next_with_events(&state, &dt, &mut bus).unwrap();
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                Event::AlarmRaised("too_hot".into()),
+                Event::OutputWritten(OutputWrite {
+                    id: "actuator".into(),
+                    old: Some(Value::Decimal(20.0)),
+                    new: Value::Decimal(-100.0),
+                }),
+                Event::OutputWritten(OutputWrite {
+                    id: "horn".into(),
+                    old: None,
+                    new: Value::Bit(true),
+                }),
+            ]
+        );
+
+        events.borrow_mut().clear();
+        state.io.inputs.insert("sensor".into(), 10.0.into());
+        rt.next_with_events(&state, &dt, &mut bus).unwrap();
+        assert_eq!(events.borrow()[0], Event::AlarmCleared("too_hot".into()));
+    }
+
+    #[test]
+    fn accumulate_time_until_preset() {
+        let mut timer = Timer::new(Duration::from_millis(500));
+        let dt = Duration::from_millis(200);
+        // a stopped timer doesn't accumulate time
+        assert!(!timer.next(&dt));
+        assert_eq!(timer.elapsed(), Duration::new(0, 0));
+
+        timer.start();
+        assert!(timer.is_running());
+        assert!(!timer.next(&dt));
+        assert_eq!(timer.elapsed(), Duration::from_millis(200));
+        assert!(!timer.next(&dt));
+        assert_eq!(timer.elapsed(), Duration::from_millis(400));
+        assert_eq!(timer.remaining(), Duration::from_millis(100));
+
+        timer.stop();
+        assert!(!timer.next(&dt));
+        assert_eq!(timer.elapsed(), Duration::from_millis(400));
+
+        timer.start();
+        assert!(timer.next(&dt));
+        assert!(timer.is_done());
+        // the elapsed time doesn't exceed the preset
+        assert_eq!(timer.elapsed(), Duration::from_millis(500));
+        assert!(timer.next(&dt));
+        assert_eq!(timer.elapsed(), timer.preset());
+
+        timer.reset();
+        assert!(!timer.is_running());
+        assert!(!timer.is_done());
+        assert_eq!(timer.elapsed(), Duration::new(0, 0));
+
+        // a restored state may exceed the preset
+        timer.state.elapsed = Duration::from_secs(1);
+        assert!(timer.is_done());
+        assert_eq!(timer.remaining(), Duration::new(0, 0));
+    }
+
+    #[test]
+    fn aggregate_metrics() {
+        let rt = SyncRuntime {
+            loops: vec![Loop {
+                id: "heating".into(),
+                inputs: vec!["sensor".into()],
+                outputs: vec!["heater".into()],
+                controller: ControllerConfig::Pid(PidConfig {
+                    k_p: 10.0,
+                    default_target: 20.0,
+                    min: Some(0.0),
+                    max: Some(100.0),
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        };
+        let mut metrics = Metrics::default();
+        assert_eq!(metrics.avg_cycle_time(), None);
+
+        let dt = Duration::from_secs(1);
+        let mut state = SystemState::default();
+        let cycles = [(5.0, 10), (15.0, 20), (18.0, 30), (25.0, 20)];
+        for (sensor, ms) in cycles {
+            state.io.inputs.insert("sensor".into(), sensor.into());
+            state = rt.next((&state, &dt)).unwrap();
+            metrics.update(&rt, &state, &Duration::from_millis(ms));
+        }
+        metrics.handle(&Event::AlarmRaised("too_hot".into()));
+        metrics.handle(&Event::AlarmCleared("too_hot".into()));
+        metrics.handle(&Event::AlarmRaised("too_hot".into()));
+
+        assert_eq!(metrics.cycles(), 4);
+        assert_eq!(metrics.avg_cycle_time(), Some(Duration::from_millis(20)));
+        assert_eq!(metrics.max_cycle_time(), Duration::from_millis(30));
+        let heating = metrics.loop_metrics("heating").unwrap();
+        assert_eq!(heating.active_cycles, 4);
+        // 100.0 (max) and 0.0 (min)
+        assert_eq!(heating.saturated_cycles, 2);
+        assert_eq!(heating.saturation_percent(), 50.0);
+        assert_eq!(heating.output, Some(0.0));
+        assert_eq!(metrics.alarm_count("too_hot"), 2);
+        assert_eq!(metrics.alarm_count("other"), 0);
+
+        let snapshot = metrics.snapshot();
+        let names: Vec<_> = snapshot.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "alarm.too_hot.count",
+                "cycle_time.avg",
+                "cycle_time.max",
+                "cycles",
+                "loop.heating.output",
+                "loop.heating.saturation",
+            ]
+        );
+        assert_eq!(snapshot[1].1, Value::Decimal(0.02));
+        assert_eq!(snapshot[3].1, Value::Integer(4));
+
+        // inactive loops are not counted
+        state.inactive_loops.push("heating".into());
+        metrics.update(&rt, &state, &Duration::from_millis(20));
+        assert_eq!(metrics.cycles(), 5);
+        assert_eq!(metrics.loop_metrics("heating").unwrap().active_cycles, 4);
+    }
+
+    #[test]
+    fn evaluate_controller_states_in_rules() {
+        let rt = SyncRuntime {
+            loops: vec![Loop {
+                id: "heating".into(),
+                inputs: vec!["sensor".into()],
+                outputs: vec!["heater".into()],
+                controller: ControllerConfig::Pid(PidConfig {
+                    k_p: 10.0,
+                    default_target: 20.0,
+                    max: Some(100.0),
+                    ..Default::default()
+                }),
+            }],
+            rules: vec![Rule {
+                id: "heating-saturated".into(),
+                condition: BoolExpr::Eval(
+                    Source::ControllerVar("heating".into(), "saturated".into()).cmp_eq(true.into()),
+                ),
+                actions: vec![],
+            }],
+            ..Default::default()
+        };
+        let dt = Duration::from_secs(1);
+        let mut state = SystemState::default();
+        state.io.inputs.insert("sensor".into(), 15.0.into());
+        let state = rt.next((&state, &dt)).unwrap();
+        assert_eq!(
+            state.get_controller_var("heating", "p"),
+            Some(Value::Decimal(50.0))
+        );
+        let (_, report) = rt.run_rules(&state);
+        assert!(report.fired.is_empty());
+        assert!(report.errors.is_empty());
+
+        let mut state = state;
+        state.io.inputs.insert("sensor".into(), 5.0.into());
+        let state = rt.next((&state, &dt)).unwrap();
+        let (_, report) = rt.run_rules(&state);
+        assert_eq!(report.fired, vec!["heating-saturated"]);
+
+        // unknown controllers or variables
+        let cmp = Source::ControllerVar("cooling".into(), "saturated".into()).cmp_eq(true.into());
+        assert_eq!(
+            cmp.eval(&state).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        let cmp = Source::ControllerVar("heating".into(), "foo".into()).cmp_eq(true.into());
+        assert!(cmp.eval(&state).is_err());
+    }
+
+    #[test]
+    fn latch_rules_with_hysteresis() {
+        let mut rt = SyncRuntime::default();
+        let too_hot = Source::In("temp".into())
+            .cmp_gt(80.0.into())
+            .with_hysteresis(75.0.into())
+            .unwrap();
+        rt.add_rule_with_hysteresis("too_hot", too_hot, vec!["cool".into()]);
+        let mut outputs = HashMap::new();
+        outputs.insert("fan".into(), Source::Const(true.into()));
+        rt.actions = vec![Action {
+            id: "cool".into(),
+            outputs,
+            setpoints: HashMap::new(),
+            memory: HashMap::new(),
+            timeouts: HashMap::new(),
+            controllers: HashMap::new(),
+        }];
+        let dt = Duration::from_millis(100);
+        let mut state = SystemState::default();
+        for (temp, active) in [
+            (70.0, false),
+            (80.0, false),
+            (81.0, true),
+            (78.0, true),
+            (75.0, true),
+            (74.9, false),
+            (78.0, false),
+            (80.5, true),
+        ] {
+            state.io.inputs.insert("temp".into(), temp.into());
+            state = rt.next((&state, &dt)).unwrap();
+            assert_eq!(state.rules["too_hot"], active);
+        }
+        assert_eq!(state.io.outputs["fan"], Value::Bit(true));
+
+        // rule sets are evaluated the same way
+        let set = RuleSet {
+            id: "safety".into(),
+            enabled: true,
+            rules: rt.rules.clone(),
+        };
+        state.io.inputs.insert("temp".into(), 76.0.into());
+        let (state, reports) = rt.run_rule_sets(&[set], &state);
+        assert!(state.rules["too_hot"]);
+        assert_eq!(reports[0].report.fired, vec!["too_hot".to_string()]);
+    }
+
+    #[test]
+    fn report_cycle_results() {
+        let mut a = HashMap::new();
+        a.insert("y".into(), Source::In("missing".into()));
+        let mut b = HashMap::new();
+        b.insert("z".into(), Source::Const(2.0.into()));
+        let action = |id: &str, outputs| Action {
+            id: id.into(),
+            outputs,
+            memory: HashMap::new(),
+            setpoints: HashMap::new(),
+            controllers: HashMap::new(),
+            timeouts: HashMap::new(),
+        };
+        let rt = SyncRuntime {
+            loops: vec![Loop {
+                id: "broken-loop".into(),
+                inputs: vec!["sensor".into()],
+                outputs: vec!["actuator".into()],
+                controller: ControllerConfig::Pid(PidConfig::default()),
+            }],
+            actions: vec![action("a", a), action("b", b)],
+            rules: vec![
+                Rule {
+                    id: "no-source".into(),
+                    condition: BoolExpr::True,
+                    actions: vec!["a".into()],
+                },
+                Rule {
+                    id: "writes-z".into(),
+                    condition: BoolExpr::True,
+                    actions: vec!["b".into()],
+                },
+                Rule {
+                    id: "broken-rule".into(),
+                    condition: Source::In("missing".into()).cmp_gt(0.0.into()).into(),
+                    actions: vec!["b".into()],
+                },
+            ],
+            ..Default::default()
+        };
+        let mut state = SystemState::default();
+        state.io.outputs.insert("y".into(), 1.0.into());
+        let dt = Duration::from_millis(100);
+
+        let (next, report) = rt.run(&state, &dt);
+        assert_eq!(report.fired, vec!["no-source", "writes-z"]);
+        // the action of "no-source" had nothing to write
+        assert_eq!(
+            report.writes,
+            vec![OutputWrite {
+                id: "z".into(),
+                old: None,
+                new: 2.0.into(),
+            }]
+        );
+        let ids: Vec<_> = report.errors.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["broken-loop", "broken-rule"]);
+        assert_eq!(next.io.outputs["y"], Value::Decimal(1.0));
+        assert_eq!(next.io.outputs["z"], Value::Decimal(2.0));
+
+        let err = rt.next((&state, &dt)).unwrap_err();
+        assert_eq!(err.causes.len(), 2);
+        assert_eq!(err.state.io.outputs["z"], Value::Decimal(2.0));
+    }
+
+    #[test]
+    fn persist_state_periodically() {
+        struct MockStore {
+            saved: Vec<IoState>,
+            fail: bool,
+        }
+        impl StateStore for MockStore {
+            fn save(&mut self, state: &IoState) -> io::Result<()> {
+                if self.fail {
+                    return Err(io::Error::other("disk full"));
+                }
+                self.saved.push(state.clone());
+                Ok(())
+            }
+            fn load(&self) -> io::Result<IoState> {
+                self.saved
+                    .last()
+                    .cloned()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "nothing saved"))
+            }
+        }
+        let rt = SyncRuntime {
+            loops: vec![Loop {
+                id: "foo".into(),
+                inputs: vec!["sensor".into()],
+                outputs: vec!["actuator".into()],
+                controller: ControllerConfig::Pid(PidConfig {
+                    k_p: 2.0,
+                    default_target: 10.0,
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        };
+        let store = MockStore {
+            saved: vec![],
+            fail: false,
+        };
+        let mut persistence = PeriodicPersistence::new(store, Duration::from_secs(2));
+        let dt = Duration::from_secs(1);
+        let mut state = SystemState::default();
+        state.io.inputs.insert("sensor".into(), 8.0.into());
+
+        let state = rt
+            .next_with_persistence(&state, &dt, &mut persistence)
+            .unwrap();
+        assert!(persistence.store.saved.is_empty());
+        let state = rt
+            .next_with_persistence(&state, &dt, &mut persistence)
+            .unwrap();
+        assert_eq!(persistence.store.saved.len(), 1);
+        assert_eq!(persistence.store.load().unwrap(), state.io);
+        assert_eq!(state.io.outputs["actuator"], Value::Decimal(4.0));
+
+        persistence.store.fail = true;
+        rt.next_with_persistence(&state, &dt, &mut persistence)
+            .unwrap();
+        let err = rt
+            .next_with_persistence(&state, &dt, &mut persistence)
+            .unwrap_err();
+        assert_eq!(err.causes.len(), 1);
+        assert_eq!(err.state.io.outputs["actuator"], Value::Decimal(4.0));
+    }
+
+    #[test]
+    fn enforce_write_order_of_rules() {
+        let action = |id: &str, output: &str| {
+            let mut outputs = HashMap::new();
+            outputs.insert(output.to_string(), Source::Const(true.into()));
+            Action {
+                id: id.into(),
+                outputs,
+                setpoints: HashMap::new(),
+                memory: HashMap::new(),
+                timeouts: HashMap::new(),
+                controllers: HashMap::new(),
+            }
+        };
+        let rule = |id: &str, action: &str| Rule {
+            id: id.into(),
+            condition: BoolExpr::True,
+            actions: vec![action.into()],
+        };
+        let rt = SyncRuntime {
+            rules: vec![rule("drain", "open-drain"), rule("fill", "open-inlet")],
+            actions: vec![action("open-drain", "drain"), action("open-inlet", "inlet")],
+            ..Default::default()
+        };
+        let (_, report) = rt.run(&SystemState::default(), &Duration::from_secs(1));
+        let ids: Vec<_> = report.writes.iter().map(|w| w.id.as_str()).collect();
+        assert_eq!(ids, vec!["drain", "inlet"]);
+
+        let mut io = SequencedIo::new(IoState::default())
+            .with_order("inlet", "drain")
+            .unwrap();
+        rt.write_outputs(&report, &mut io).unwrap();
+        assert_eq!(io.sequence(), vec!["inlet", "drain"]);
+        io.flush().unwrap();
+        assert_eq!(io.io.outputs["drain"], Value::Bit(true));
+
+        let mut io = SequencedIo::new(IoState::default())
+            .with_order("inlet", "drain")
+            .unwrap()
+            .strict();
+        let err = rt.write_outputs(&report, &mut io).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(io.sequence(), vec!["drain"]);
+    }
+
+    #[test]
+    fn cascade_loops_with_unit_tagged_outputs() {
+        let dt = Duration::from_secs(1);
+        let primary = Pid::new(PidConfig {
+            k_p: 2.0,
+            default_target: 30.0,
+            ..Default::default()
+        });
+        let mut primary = OutputWriter::new(primary, "primary").with_unit("°C");
+
+        let rt = SyncRuntime {
+            loops: vec![Loop {
+                id: "secondary".into(),
+                inputs: vec!["temp".into()],
+                outputs: vec!["valve".into()],
+                controller: ControllerConfig::Pid(PidConfig {
+                    k_p: 1.0,
+                    ..Default::default()
+                }),
+            }],
+            wires: vec![Wire {
+                from: "primary".into(),
+                to: "temp".into(),
+            }],
+            ..Default::default()
+        };
+        let mut state = SystemState::default();
+        state
+            .setpoints
+            .insert("secondary".into(), Value::Quantity(25.0, "°C".into()));
+        state.io.outputs.insert("valve".into(), 10.0.into());
+        crate::Controller::next(&mut primary, ((20.0, &dt), &mut state.io)).unwrap();
+        assert_eq!(
+            state.io.outputs["primary"],
+            Value::Quantity(20.0, "°C".into())
+        );
+
+        let mut io = state.io.clone();
+        io.inputs
+            .insert("temp".into(), state.io.outputs["primary"].clone());
+        rt.warm_start(&mut io, &mut state).unwrap();
+        match state.controllers["secondary"] {
+            ControllerState::Pid(s) => {
+                assert_eq!(s.target, 25.0);
+                assert_eq!(s.i, 5.0);
+            }
+            _ => unreachable!(),
+        }
+
+        // bumpless
+        let mut state = rt.next((&state, &dt)).unwrap();
+        assert_eq!(state.io.outputs["valve"], Value::Decimal(10.0));
+
+        crate::Controller::next(&mut primary, ((21.0, &dt), &mut state.io)).unwrap();
+        assert_eq!(
+            state.io.outputs["primary"],
+            Value::Quantity(18.0, "°C".into())
+        );
+        let state = rt.next((&state, &dt)).unwrap();
+        assert_eq!(state.io.outputs["valve"], Value::Decimal(12.0));
+    }
+
+    #[test]
+    fn report_output_writes_of_fsm_transitions() {
+        let sm = StateMachine {
+            initial: "start".into(),
+            transitions: vec![Transition {
+                condition: BoolExpr::Eval(
+                    Source::In("x".into()).cmp_eq(Source::Const(true.into())),
+                ),
+                from: "start".into(),
+                to: "step-one".into(),
+                actions: vec!["foo".into()],
+            }],
+        };
+        let mut outputs = HashMap::new();
+        outputs.insert("y".to_string(), Value::from(99.9).into());
+        let rt = SyncRuntime {
+            actions: vec![Action {
+                id: "foo".into(),
+                outputs,
+                setpoints: HashMap::new(),
+                memory: HashMap::new(),
+                timeouts: HashMap::new(),
+                controllers: HashMap::new(),
+            }],
+            state_machines: vec![("fsm".to_string(), sm)].into_iter().collect(),
+            ..Default::default()
+        };
+        let mut state = SystemState::default();
+        state.state_machines.insert("fsm".into(), "start".into());
+        state.io.inputs.insert("x".into(), true.into());
+        state.io.outputs.insert("y".into(), 1.0.into());
+        let (_, report) = rt.run(&state, &Duration::from_secs(1));
+        assert_eq!(
+            report.writes,
+            vec![OutputWrite {
+                id: "y".into(),
+                old: Some(1.0.into()),
+                new: 99.9.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn fire_rules_on_historical_anomalies() {
+        let mut anomaly = HistoricalCondition::new(
+            HistoricalComparison::new(HistoricalComparisonConfig {
+                source: Source::In("pressure".into()),
+                window: Duration::from_secs(60),
+                deviation: Deviation::Absolute(1.0),
+                min_samples: 3,
+            }),
+            "pressure.anomaly",
+        );
+        let rt = SyncRuntime {
+            rules: vec![Rule {
+                id: "alarm".into(),
+                condition: BoolExpr::Eval(
+                    Source::Mem("pressure.anomaly".into()).cmp_eq(true.into()),
+                ),
+                actions: vec![],
+            }],
+            ..Default::default()
+        };
+        let dt = Duration::from_secs(1);
+        let mut state = SystemState::default();
+        for (pressure, fired) in [(5.0, false), (5.1, false), (4.9, false), (7.0, true)] {
+            state.io.inputs.insert("pressure".into(), pressure.into());
+            TimeStepController::next(&mut anomaly, &mut state, &dt).unwrap();
+            let (next, report) = rt.run(&state, &dt);
+            assert_eq!(report.fired.contains(&"alarm".to_string()), fired);
+            state = next;
+        }
+    }
+
+    #[test]
+    fn apply_actions_with_controller_variables() {
+        let var = |v: &str| Source::ControllerVar("pid".into(), v.into());
+        let mut outputs = HashMap::new();
+        outputs.insert("y".to_string(), var("i"));
+        outputs.insert("z".to_string(), var("foo"));
+        let mut setpoints = HashMap::new();
+        setpoints.insert("sp".to_string(), var("target"));
+        let mut memory = HashMap::new();
+        memory.insert("m".to_string(), var("saturated"));
+        let rt = SyncRuntime {
+            rules: vec![Rule {
+                id: "copy".into(),
+                condition: BoolExpr::True,
+                actions: vec!["a".into()],
+            }],
+            actions: vec![Action {
+                id: "a".into(),
+                outputs,
+                setpoints,
+                memory,
+                timeouts: HashMap::new(),
+                controllers: HashMap::new(),
+            }],
+            ..Default::default()
+        };
+        let mut state = SystemState::default();
+        state.controllers.insert(
+            "pid".into(),
+            ControllerState::Pid(pid::PidState {
+                target: 7.0,
+                i: 2.5,
+                ..Default::default()
+            }),
+        );
+        let (state, report) = rt.run(&state, &Duration::from_secs(1));
+        assert_eq!(
+            report.writes,
+            vec![OutputWrite {
+                id: "y".into(),
+                old: None,
+                new: 2.5.into(),
+            }]
+        );
+        assert_eq!(state.io.outputs.get("z"), None);
+        assert_eq!(state.setpoints["sp"], Value::Decimal(7.0));
+        assert_eq!(state.io.mem["m"], Value::Bit(false));
+    }
 }
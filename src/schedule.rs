@@ -0,0 +1,307 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::schedule::*;
+//! use std::time::Duration;
+//!
+//! let hours = |h: u64| Duration::from_secs(h * 3600);
+//!
+//! // night setback
+//! let schedule = TimeSchedule::new(TimeScheduleConfig {
+//!     breakpoints: vec![
+//!         (hours(6), 21.0),
+//!         (hours(22), 21.0),
+//!         (hours(23), 17.0),
+//!         (hours(5), 17.0),
+//!     ],
+//! });
+//! let target = schedule.setpoint();
+//! ```
+
+use super::{Controller, PureController};
+use crate::util::DurationInSeconds;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A source of the current time of day
+pub trait Clock {
+    /// The time elapsed since midnight.
+    fn time_of_day(&self) -> Duration;
+}
+
+/// The system clock (UTC)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn time_of_day(&self) -> Duration {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Duration::from_nanos((since_epoch.as_nanos() % DAY.as_nanos()) as u64)
+    }
+}
+
+/// A setpoint schedule by time of day
+#[derive(Debug, Clone)]
+pub struct TimeSchedule<C = SystemClock> {
+    cfg: TimeScheduleConfig,
+    clock: C,
+}
+
+/// Schedule configuration
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeScheduleConfig {
+    /// Setpoints at specific times of day
+    ///
+    /// Setpoints between two breakpoints are linearly interpolated.
+    /// The last breakpoint of a day is interpolated with the first
+    /// one of the next day. Times beyond 24h are wrapped.
+    pub breakpoints: Vec<(Duration, f64)>,
+}
+
+impl TimeSchedule {
+    /// Create a new schedule using the system clock.
+    pub fn new(cfg: TimeScheduleConfig) -> Self {
+        TimeSchedule::with_clock(cfg, SystemClock)
+    }
+}
+
+impl<C: Clock> TimeSchedule<C> {
+    /// Create a new schedule with a custom clock.
+    pub fn with_clock(cfg: TimeScheduleConfig, clock: C) -> Self {
+        TimeSchedule { cfg, clock }
+    }
+    /// The current setpoint.
+    ///
+    /// Returns `None` if there are no breakpoints.
+    pub fn setpoint(&self) -> Option<f64> {
+        self.cfg.next(&self.clock.time_of_day())
+    }
+}
+
+fn time_of_day(t: Duration) -> Duration {
+    Duration::from_nanos((t.as_nanos() % DAY.as_nanos()) as u64)
+}
+
+fn seconds(t: Duration) -> f64 {
+    f64::from(DurationInSeconds::from(t))
+}
+
+impl PureController<&Duration, Option<f64>> for TimeScheduleConfig {
+    fn next(&self, time: &Duration) -> Option<f64> {
+        let mut points: Vec<_> = self
+            .breakpoints
+            .iter()
+            .map(|(t, v)| (time_of_day(*t), *v))
+            .collect();
+        points.sort_by_key(|(t, _)| *t);
+        let (first, last) = (*points.first()?, *points.last()?);
+        let t = time_of_day(*time);
+
+        let (prev, next) = match points.iter().position(|(p, _)| *p > t) {
+            Some(0) | None => {
+                // between the last breakpoint and the first one of the next day
+                (last, (first.0 + DAY, first.1))
+            }
+            Some(i) => (points[i - 1], points[i]),
+        };
+        let t = if t < prev.0 { t + DAY } else { t };
+        let span = seconds(next.0 - prev.0);
+        if span <= 0.0 {
+            return Some(prev.1);
+        }
+        let ratio = seconds(t - prev.0) / span;
+        Some(prev.1 + (next.1 - prev.1) * ratio)
+    }
+}
+
+/// A supervisory function that adjusts a setpoint,
+/// e.g. a model-predictive or economic optimization
+/// layered on top of the base loops.
+pub trait SetpointOptimizer {
+    /// Propose the next setpoint based on the current
+    /// setpoint and the actual value of the process.
+    fn optimize(&mut self, setpoint: f64, actual: f64, duration: &Duration) -> f64;
+}
+
+/// A setpoint that is adjusted by a [SetpointOptimizer]
+/// within configured bounds.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, pid::*, schedule::*};
+/// use std::time::Duration;
+///
+/// struct Economizer;
+///
+/// impl SetpointOptimizer for Economizer {
+///     fn optimize(&mut self, setpoint: f64, _actual: f64, _: &Duration) -> f64 {
+///         // e.g. lower the setpoint while energy is expensive
+///         setpoint - 0.1
+///     }
+/// }
+///
+/// let mut setpoint = OptimizedSetpoint::new(
+///     Economizer,
+///     OptimizedSetpointConfig { min: 18.0, max: 22.0 },
+///     21.0,
+/// );
+/// let mut pid = Pid::new(PidConfig::default());
+///
+/// let dt = Duration::from_secs(1);
+/// let actual = 20.3;
+/// pid.set_target(setpoint.next(actual, &dt));
+/// let output = pid.next(actual, &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct OptimizedSetpoint<O> {
+    /// The optimization hook
+    pub optimizer: O,
+    cfg: OptimizedSetpointConfig,
+    setpoint: f64,
+}
+
+/// Bounds of an optimized setpoint
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OptimizedSetpointConfig {
+    /// The lowest allowed setpoint
+    pub min: f64,
+    /// The highest allowed setpoint
+    pub max: f64,
+}
+
+impl PureController<f64, f64> for OptimizedSetpointConfig {
+    fn next(&self, setpoint: f64) -> f64 {
+        setpoint.max(self.min).min(self.max)
+    }
+}
+
+impl<O> OptimizedSetpoint<O> {
+    /// Create a new instance with an initial setpoint.
+    pub fn new(optimizer: O, cfg: OptimizedSetpointConfig, setpoint: f64) -> Self {
+        let setpoint = cfg.next(setpoint);
+        OptimizedSetpoint {
+            optimizer,
+            cfg,
+            setpoint,
+        }
+    }
+    /// The current setpoint.
+    pub fn setpoint(&self) -> f64 {
+        self.setpoint
+    }
+}
+
+impl<O: SetpointOptimizer> Controller<(f64, &Duration), f64> for OptimizedSetpoint<O> {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (actual, duration) = input;
+        let proposed = self.optimizer.optimize(self.setpoint, actual, duration);
+        if !proposed.is_nan() {
+            self.setpoint = self.cfg.next(proposed);
+        }
+        self.setpoint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    struct FixedClock(Duration);
+
+    impl Clock for FixedClock {
+        fn time_of_day(&self) -> Duration {
+            self.0
+        }
+    }
+
+    fn hours(h: f64) -> Duration {
+        Duration::from_secs_f64(h * 3600.0)
+    }
+
+    fn cfg() -> TimeScheduleConfig {
+        TimeScheduleConfig {
+            breakpoints: vec![
+                (hours(22.0), 20.0),
+                (hours(6.0), 20.0),
+                (hours(8.0), 22.0),
+                (hours(2.0), 16.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn interpolate_between_breakpoints() {
+        let cfg = cfg();
+        let at = |h| cfg.next(&hours(h)).unwrap();
+        assert_eq!(at(6.0), 20.0);
+        assert_eq!(at(7.0), 21.0);
+        assert_eq!(at(8.0), 22.0);
+        assert_eq!(at(15.0), 21.0);
+        assert_eq!(at(4.0), 18.0);
+    }
+
+    #[test]
+    fn wrap_around_midnight() {
+        let cfg = cfg();
+        let at = |h| cfg.next(&hours(h)).unwrap();
+        assert_eq!(at(22.0), 20.0);
+        assert_eq!(at(23.0), 19.0);
+        assert_eq!(at(0.0), 18.0);
+        assert_eq!(at(24.0), 18.0);
+        assert_eq!(at(1.0), 17.0);
+        assert_eq!(at(2.0), 16.0);
+    }
+
+    #[test]
+    fn query_with_injected_clock() {
+        let schedule = TimeSchedule::with_clock(cfg(), FixedClock(hours(23.0)));
+        assert_eq!(schedule.setpoint(), Some(19.0));
+
+        let single = TimeScheduleConfig {
+            breakpoints: vec![(hours(12.0), 5.0)],
+        };
+        let schedule = TimeSchedule::with_clock(single, FixedClock(hours(3.0)));
+        assert_eq!(schedule.setpoint(), Some(5.0));
+
+        let empty = TimeSchedule::with_clock(TimeScheduleConfig::default(), FixedClock(hours(3.0)));
+        assert_eq!(empty.setpoint(), None);
+    }
+
+    #[test]
+    fn clamp_optimized_setpoint() {
+        struct Nudge(f64);
+
+        impl SetpointOptimizer for Nudge {
+            fn optimize(&mut self, setpoint: f64, _: f64, _: &Duration) -> f64 {
+                setpoint + self.0
+            }
+        }
+
+        let cfg = OptimizedSetpointConfig {
+            min: 18.0,
+            max: 22.0,
+        };
+        let dt = Duration::from_secs(1);
+        let mut sp = OptimizedSetpoint::new(Nudge(1.5), cfg.clone(), 19.0);
+        assert_eq!(sp.setpoint(), 19.0);
+        assert_eq!(sp.next((20.0, &dt)), 20.5);
+        assert_eq!(sp.next((20.0, &dt)), 22.0);
+        assert_eq!(sp.next((20.0, &dt)), 22.0);
+
+        sp.optimizer.0 = -3.0;
+        assert_eq!(sp.next((20.0, &dt)), 19.0);
+        assert_eq!(sp.next((20.0, &dt)), 18.0);
+
+        // invalid proposals are ignored
+        sp.optimizer.0 = f64::NAN;
+        assert_eq!(sp.next((20.0, &dt)), 18.0);
+
+        let sp = OptimizedSetpoint::new(Nudge(0.0), cfg, 30.0);
+        assert_eq!(sp.setpoint(), 22.0);
+    }
+}
@@ -0,0 +1,131 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{PureController, select::*};
+//!
+//! // select the highest of three redundant temperature sensors
+//! let selector = Selector {
+//!     selection: Selection::Max,
+//!     tie_break: TieBreak::Priority(vec![2, 0, 1]),
+//! };
+//! let (index, value) = selector.next(&[71.2, 72.5, 72.5]).unwrap();
+//! assert_eq!(index, 2);
+//! ```
+
+use super::PureController;
+
+/// Selects one of several inputs
+///
+/// The selector returns the index and the value of the selected input.
+/// Invalid inputs (`NaN`) are ignored.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Selector {
+    /// Which input to select
+    pub selection: Selection,
+    /// How to select between equal inputs
+    pub tie_break: TieBreak,
+}
+
+/// The selection criterion of a [Selector]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Selection {
+    /// Select the highest input
+    #[default]
+    Max,
+    /// Select the lowest input
+    Min,
+}
+
+/// Deterministic selection between inputs with exactly the same value
+///
+/// The default is [TieBreak::FirstIndex].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TieBreak {
+    /// Select the input with the lowest index
+    #[default]
+    FirstIndex,
+    /// Select the input with the highest index
+    LastIndex,
+    /// Select the input that comes first in the list of indices
+    ///
+    /// Inputs that are not listed have the lowest priority
+    /// and are selected by their index.
+    Priority(Vec<usize>),
+}
+
+impl TieBreak {
+    /// Check if the input `a` is preferred over the input `b`.
+    fn prefers(&self, a: usize, b: usize) -> bool {
+        match self {
+            TieBreak::FirstIndex => a < b,
+            TieBreak::LastIndex => a > b,
+            TieBreak::Priority(list) => {
+                let rank = |i| list.iter().position(|x| *x == i).unwrap_or(list.len());
+                (rank(a), a) < (rank(b), b)
+            }
+        }
+    }
+}
+
+impl PureController<&[f64], Option<(usize, f64)>> for Selector {
+    fn next(&self, inputs: &[f64]) -> Option<(usize, f64)> {
+        let mut selected: Option<(usize, f64)> = None;
+        for (i, x) in inputs.iter().copied().enumerate() {
+            if x.is_nan() {
+                continue;
+            }
+            let better = match selected {
+                None => true,
+                Some((j, y)) if x == y => self.tie_break.prefers(i, j),
+                Some((_, y)) => match self.selection {
+                    Selection::Max => x > y,
+                    Selection::Min => x < y,
+                },
+            };
+            if better {
+                selected = Some((i, x));
+            }
+        }
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn select_extremum() {
+        let max = Selector::default();
+        assert_eq!(max.next(&[1.0, 3.0, 2.0]), Some((1, 3.0)));
+        let min = Selector {
+            selection: Selection::Min,
+            ..Default::default()
+        };
+        assert_eq!(min.next(&[1.0, f64::NAN, -2.0]), Some((2, -2.0)));
+        assert_eq!(min.next(&[f64::NAN]), None);
+        assert_eq!(min.next(&[]), None);
+    }
+
+    #[test]
+    fn break_ties_deterministically() {
+        let inputs = [5.0, 7.0, 1.0, 7.0, 7.0];
+        let select = |tie_break| {
+            Selector {
+                selection: Selection::Max,
+                tie_break,
+            }
+            .next(&inputs)
+            .map(|(i, _)| i)
+        };
+        assert_eq!(select(TieBreak::FirstIndex), Some(1));
+        assert_eq!(select(TieBreak::LastIndex), Some(4));
+        assert_eq!(select(TieBreak::Priority(vec![3, 1])), Some(3));
+        assert_eq!(select(TieBreak::Priority(vec![0, 4])), Some(4));
+        // unlisted inputs are selected by index
+        assert_eq!(select(TieBreak::Priority(vec![0, 2])), Some(1));
+    }
+}
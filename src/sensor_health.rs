@@ -0,0 +1,358 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{TimeStepController, sensor_health::*};
+//! use std::time::Duration;
+//!
+//! let cfg = SensorHealthConfig {
+//!     flatline: Some(FlatlineConfig {
+//!         window: Duration::from_secs(60),
+//!         tolerance: 0.01,
+//!     }),
+//!     rails: Some(RailsConfig {
+//!         low: 4.0,
+//!         high: 20.0,
+//!         margin: 0.1,
+//!     }),
+//! };
+//! let mut health = SensorHealth::new(cfg);
+//!
+//! let dt = Duration::from_secs(1);
+//! assert_eq!(health.next(12.3, &dt), None);
+//! assert_eq!(health.next(20.0, &dt), Some(SensorFault::RailedHigh));
+//! ```
+
+use super::{Controller, PureController};
+use std::time::Duration;
+
+/// Sensor health monitor
+#[derive(Debug, Clone)]
+pub struct SensorHealth {
+    cfg: SensorHealthConfig,
+    /// Current monitor state
+    pub state: SensorHealthState,
+}
+
+/// Sensor health configuration
+///
+/// Each criterion is optional and can be configured per sensor.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SensorHealthConfig {
+    /// Detect signals that don't change anymore
+    pub flatline: Option<FlatlineConfig>,
+    /// Detect signals that stick to the extremes of their range
+    pub rails: Option<RailsConfig>,
+}
+
+/// Flatline detection
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FlatlineConfig {
+    /// The time a signal is allowed to stay unchanged
+    pub window: Duration,
+    /// Changes up to this amount are considered as no change
+    pub tolerance: f64,
+}
+
+/// Railed signal detection
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RailsConfig {
+    /// The lower end of the sensor range
+    pub low: f64,
+    /// The upper end of the sensor range
+    pub high: f64,
+    /// Values within this distance to an end are considered as railed
+    pub margin: f64,
+}
+
+/// A sensor fault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SensorFault {
+    /// The signal didn't change within the configured window
+    Flatline,
+    /// The signal sticks to the lower end of its range
+    RailedLow,
+    /// The signal sticks to the upper end of its range
+    RailedHigh,
+}
+
+/// Internal sensor health state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SensorHealthState {
+    /// The value the signal is compared with to detect changes
+    pub reference: Option<f64>,
+    /// The time since the last change
+    pub unchanged: Duration,
+    /// The current fault
+    pub fault: Option<SensorFault>,
+}
+
+impl SensorHealth {
+    /// Create a new monitor instance.
+    pub fn new(cfg: SensorHealthConfig) -> Self {
+        SensorHealth {
+            cfg,
+            state: SensorHealthState::default(),
+        }
+    }
+    /// The current fault
+    pub fn fault(&self) -> Option<SensorFault> {
+        self.state.fault
+    }
+    /// Reset the internal state.
+    pub fn reset(&mut self) {
+        self.state = SensorHealthState::default();
+    }
+}
+
+impl Controller<(f64, &Duration), Option<SensorFault>> for SensorHealth {
+    fn next(&mut self, input: (f64, &Duration)) -> Option<SensorFault> {
+        let (value, duration) = input;
+        self.state = self.cfg.next((self.state, value, duration));
+        self.state.fault
+    }
+}
+
+impl PureController<(SensorHealthState, f64, &Duration), SensorHealthState> for SensorHealthConfig {
+    fn next(&self, input: (SensorHealthState, f64, &Duration)) -> SensorHealthState {
+        let (mut state, value, duration) = input;
+
+        let mut flatline = false;
+        if let Some(ref cfg) = self.flatline {
+            match state.reference {
+                Some(reference) if (value - reference).abs() <= cfg.tolerance => {
+                    state.unchanged += *duration;
+                }
+                _ => {
+                    state.reference = Some(value);
+                    state.unchanged = Duration::new(0, 0);
+                }
+            }
+            flatline = state.unchanged >= cfg.window;
+        }
+
+        state.fault = match self.rails {
+            Some(ref rails) if value <= rails.low + rails.margin => Some(SensorFault::RailedLow),
+            Some(ref rails) if value >= rails.high - rails.margin => Some(SensorFault::RailedHigh),
+            _ if flatline => Some(SensorFault::Flatline),
+            _ => None,
+        };
+        state
+    }
+}
+
+/// Holds the last good output of a controller during a sensor fault.
+///
+/// The quality of the measurement is passed along with its value,
+/// e.g. as reported by a [SensorHealth] monitor.
+/// While the measurement is flagged as bad the wrapped controller isn't
+/// executed, so its state stays frozen and the last good output is held.
+/// If the fault lasts longer than the configured number of cycles,
+/// the safe output is issued instead.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, pid::*, sensor_health::*};
+/// use std::time::Duration;
+///
+/// let pid = Pid::new(PidConfig::default());
+/// let mut ctl = HoldLastGood::new(pid, HoldLastGoodConfig {
+///     max_hold_cycles: Some(10),
+///     safe_output: 0.0,
+/// });
+/// let dt = Duration::from_millis(100);
+/// let measurement = 2.1;
+/// let good = true;
+/// let output = ctl.next((measurement, good), &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HoldLastGood<C> {
+    /// The wrapped controller
+    pub controller: C,
+    cfg: HoldLastGoodConfig,
+    /// Current hold state
+    pub state: HoldLastGoodState,
+}
+
+/// Hold-last-good configuration
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HoldLastGoodConfig {
+    /// The number of bad cycles the last good output is held
+    ///
+    /// `None` holds the output forever.
+    pub max_hold_cycles: Option<u32>,
+    /// The output if there is no good output to hold
+    pub safe_output: f64,
+}
+
+/// Internal hold-last-good state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HoldLastGoodState {
+    /// The last output based on a good measurement
+    pub last_good: Option<f64>,
+    /// The number of consecutive bad cycles
+    pub bad_cycles: u32,
+}
+
+impl<C> HoldLastGood<C> {
+    /// Wrap a controller.
+    pub fn new(controller: C, cfg: HoldLastGoodConfig) -> Self {
+        HoldLastGood {
+            controller,
+            cfg,
+            state: HoldLastGoodState::default(),
+        }
+    }
+    /// Check if the output is held (or forced to the safe output).
+    pub fn is_holding(&self) -> bool {
+        self.state.bad_cycles > 0
+    }
+    /// Check if the safe output is issued.
+    pub fn is_safe(&self) -> bool {
+        self.state.bad_cycles > 0
+            && (self.state.last_good.is_none()
+                || self
+                    .cfg
+                    .max_hold_cycles
+                    .map(|max| self.state.bad_cycles > max)
+                    .unwrap_or(false))
+    }
+}
+
+impl<C> Controller<((f64, bool), &Duration), f64> for HoldLastGood<C>
+where
+    C: for<'a> Controller<(f64, &'a Duration), f64>,
+{
+    fn next(&mut self, input: ((f64, bool), &Duration)) -> f64 {
+        let ((value, good), duration) = input;
+        if good {
+            let output = self.controller.next((value, duration));
+            self.state.last_good = Some(output);
+            self.state.bad_cycles = 0;
+            return output;
+        }
+        self.state.bad_cycles = self.state.bad_cycles.saturating_add(1);
+        if self.is_safe() {
+            self.cfg.safe_output
+        } else {
+            self.state.last_good.unwrap_or(self.cfg.safe_output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn default_sensor_health_config() {
+        let cfg = SensorHealthConfig::default();
+        assert_eq!(cfg.flatline, None);
+        assert_eq!(cfg.rails, None);
+        let mut health = SensorHealth::new(cfg);
+        let dt = Duration::from_secs(3600);
+        assert_eq!(health.next((1.0, &dt)), None);
+        assert_eq!(health.next((1.0, &dt)), None);
+    }
+
+    #[test]
+    fn detect_flatline() {
+        let cfg = SensorHealthConfig {
+            flatline: Some(FlatlineConfig {
+                window: Duration::from_secs(5),
+                tolerance: 0.1,
+            }),
+            rails: None,
+        };
+        let mut health = SensorHealth::new(cfg);
+        let dt = Duration::from_secs(1);
+        for x in &[10.0, 10.05, 9.95, 10.0, 10.1] {
+            assert_eq!(health.next((*x, &dt)), None);
+        }
+        assert_eq!(health.next((10.0, &dt)), Some(SensorFault::Flatline));
+        assert_eq!(health.fault(), Some(SensorFault::Flatline));
+        // the signal is alive again
+        assert_eq!(health.next((10.2, &dt)), None);
+    }
+
+    #[test]
+    fn detect_railed_signals() {
+        let cfg = SensorHealthConfig {
+            flatline: None,
+            rails: Some(RailsConfig {
+                low: 4.0,
+                high: 20.0,
+                margin: 0.2,
+            }),
+        };
+        let mut health = SensorHealth::new(cfg);
+        let dt = Duration::from_secs(1);
+        assert_eq!(health.next((12.0, &dt)), None);
+        assert_eq!(health.next((4.2, &dt)), Some(SensorFault::RailedLow));
+        assert_eq!(health.next((3.0, &dt)), Some(SensorFault::RailedLow));
+        assert_eq!(health.next((4.3, &dt)), None);
+        assert_eq!(health.next((19.7, &dt)), None);
+        assert_eq!(health.next((19.8, &dt)), Some(SensorFault::RailedHigh));
+        assert_eq!(health.next((25.0, &dt)), Some(SensorFault::RailedHigh));
+    }
+
+    #[test]
+    fn hold_output_during_bad_quality() {
+        use crate::pid::*;
+
+        let mut pid = Pid::new(PidConfig {
+            k_p: 1.0,
+            k_i: 1.0,
+            ..Default::default()
+        });
+        pid.set_target(10.0);
+        let mut ctl = HoldLastGood::new(
+            pid,
+            HoldLastGoodConfig {
+                max_hold_cycles: Some(3),
+                safe_output: -1.0,
+            },
+        );
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next(((8.0, true), &dt)), 4.0);
+        assert_eq!(ctl.next(((9.0, true), &dt)), 4.0);
+        let frozen = ctl.controller.state;
+
+        for _ in 0..3 {
+            assert_eq!(ctl.next(((100.0, false), &dt)), 4.0);
+            assert!(ctl.is_holding());
+            assert!(!ctl.is_safe());
+        }
+        assert_eq!(ctl.controller.state, frozen);
+
+        // the fault lasts too long
+        assert_eq!(ctl.next(((100.0, false), &dt)), -1.0);
+        assert!(ctl.is_safe());
+
+        // quality returns
+        assert_eq!(ctl.next(((9.0, true), &dt)), 5.0);
+        assert!(!ctl.is_holding());
+    }
+
+    #[test]
+    fn issue_safe_output_without_good_value() {
+        let mut ctl = HoldLastGood::new(
+            crate::rate_limiter::RateLimiter::new(Default::default()),
+            HoldLastGoodConfig {
+                max_hold_cycles: None,
+                safe_output: 7.0,
+            },
+        );
+        let dt = Duration::from_secs(1);
+        assert_eq!(ctl.next(((3.0, false), &dt)), 7.0);
+        assert!(ctl.is_safe());
+        assert_eq!(ctl.next(((3.0, true), &dt)), 3.0);
+        for _ in 0..100 {
+            assert_eq!(ctl.next(((-5.0, false), &dt)), 3.0);
+        }
+    }
+}
@@ -0,0 +1,589 @@
+//! # Example
+//!
+//! ```rust,no_run
+//! use msr::{TimeStepController, sim::*};
+//! use std::time::Duration;
+//!
+//! let mut cfg = SimulatorConfig::default();
+//! cfg.gain = 2.0;
+//! cfg.time_constant = Duration::from_secs(10);
+//! cfg.noise = Some(NoiseConfig {
+//!     seed: 42,
+//!     amplitude: 0.1,
+//! });
+//! let mut sim = Simulator::new(cfg);
+//!
+//! let dt = Duration::from_millis(100);
+//! let actuator_value = 5.0;
+//! let sensor_value = sim.next(actuator_value, &dt);
+//! ```
+
+use super::{Controller, PureController, Value};
+use crate::util::{cap_delta_t, DurationInSeconds};
+use std::{f64::consts::PI, time::Duration};
+
+/// A simulated first-order process
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    cfg: SimulatorConfig,
+    /// Current simulator state
+    pub state: SimulatorState,
+}
+
+/// Simulator configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SimulatorConfig {
+    /// Steady-state gain of the process
+    pub gain: f64,
+    /// Time constant of the process
+    pub time_constant: Duration,
+    /// Initial process value
+    pub initial_value: f64,
+    /// Optional measurement noise
+    pub noise: Option<NoiseConfig>,
+    /// Maximum plausible time step (see [crate::util::cap_delta_t])
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_delta_t: Option<Duration>,
+}
+
+/// Internal simulator state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulatorState {
+    /// The (noise free) process value
+    pub value: f64,
+    /// The noise generator
+    pub noise: Option<Noise>,
+}
+
+/// Configuration of a pseudo-random noise source
+///
+/// The same seed always reproduces the same sequence.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NoiseConfig {
+    /// The seed of the random number generator
+    pub seed: u64,
+    /// The maximum (absolute) noise value
+    pub amplitude: f64,
+}
+
+/// A deterministic pseudo-random noise generator (xorshift64*)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Noise {
+    rng: u64,
+    amplitude: f64,
+}
+
+impl Noise {
+    /// Create a new generator.
+    pub fn new(cfg: &NoiseConfig) -> Self {
+        // Scramble the seed (splitmix64) to avoid the invalid state `0`
+        // and poorly mixed sequences for small seeds.
+        let mut z = cfg.seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Noise {
+            rng: if z == 0 { 1 } else { z },
+            amplitude: cfg.amplitude,
+        }
+    }
+
+    /// A uniformly distributed number within `[0, 1)`.
+    pub fn next_unit(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let x = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        // Use the upper 53 bits for the mantissa.
+        (x >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// A uniformly distributed noise value within `[-amplitude, amplitude)`.
+    pub fn next_value(&mut self) -> f64 {
+        (self.next_unit() * 2.0 - 1.0) * self.amplitude
+    }
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        SimulatorConfig {
+            gain: 1.0,
+            time_constant: Duration::from_secs(1),
+            initial_value: 0.0,
+            noise: None,
+            max_delta_t: None,
+        }
+    }
+}
+
+impl Simulator {
+    /// Create a new simulator instance.
+    pub fn new(cfg: SimulatorConfig) -> Self {
+        let state = SimulatorState {
+            value: cfg.initial_value,
+            noise: cfg.noise.as_ref().map(Noise::new),
+        };
+        Simulator { cfg, state }
+    }
+    /// Reset the internal simulator state.
+    pub fn reset(&mut self) {
+        *self = Simulator::new(self.cfg.clone());
+    }
+}
+
+impl Controller<(f64, &Duration), f64> for Simulator {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (actuator, duration) = input;
+        let (state, measurement) = self.cfg.next((self.state, actuator, duration));
+        self.state = state;
+        measurement
+    }
+}
+
+impl PureController<(SimulatorState, f64, &Duration), (SimulatorState, f64)> for SimulatorConfig {
+    fn next(&self, input: (SimulatorState, f64, &Duration)) -> (SimulatorState, f64) {
+        let (mut state, actuator, duration) = input;
+        let delta_t = f64::from(DurationInSeconds::from(cap_delta_t(
+            duration,
+            self.max_delta_t,
+        )));
+        let tau = f64::from(DurationInSeconds::from(self.time_constant));
+        let target = self.gain * actuator;
+        state.value = if tau > 0.0 {
+            // exact discretization of the first-order lag
+            target + (state.value - target) * (-delta_t / tau).exp()
+        } else {
+            target
+        };
+        let noise = state.noise.as_mut().map(Noise::next_value).unwrap_or(0.0);
+        (state, state.value + noise)
+    }
+}
+
+/// Replays a recorded series of values.
+///
+/// Each call returns the value of the current offset
+/// (the value of the latest sample that was reached)
+/// and advances the time by `delta_t` afterwards.
+/// Before the first sample there is no value.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{Controller, Value, sim::*};
+/// use std::time::Duration;
+///
+/// let mut playback = Playback::new(PlaybackConfig {
+///     samples: vec![
+///         (Duration::from_secs(0), Value::Decimal(1.0)),
+///         (Duration::from_secs(5), Value::Decimal(3.0)),
+///     ],
+///     repeat: None,
+/// });
+/// let dt = Duration::from_secs(1);
+/// assert_eq!(playback.next(&dt), Some(Value::Decimal(1.0)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Playback {
+    cfg: PlaybackConfig,
+    /// Current playback state
+    pub state: PlaybackState,
+}
+
+/// Playback configuration
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PlaybackConfig {
+    /// The recorded samples `(offset, value)`
+    pub samples: Vec<(Duration, Value)>,
+    /// Repeat the series with the given period
+    ///
+    /// If `None` the playback stops at the end
+    /// and the last value is held.
+    pub repeat: Option<Duration>,
+}
+
+/// Internal playback state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlaybackState {
+    /// The current offset
+    pub offset: Duration,
+}
+
+impl Playback {
+    /// Create a new instance.
+    pub fn new(mut cfg: PlaybackConfig) -> Self {
+        cfg.samples.sort_by_key(|(offset, _)| *offset);
+        Playback {
+            cfg,
+            state: PlaybackState::default(),
+        }
+    }
+    /// Check if the end of a non-repeating series was reached.
+    pub fn is_finished(&self) -> bool {
+        self.cfg.repeat.is_none()
+            && self
+                .cfg
+                .samples
+                .last()
+                .map(|(offset, _)| self.state.offset >= *offset)
+                .unwrap_or(true)
+    }
+    /// Restart from the beginning.
+    pub fn reset(&mut self) {
+        self.state = PlaybackState::default();
+    }
+}
+
+impl Controller<&Duration, Option<Value>> for Playback {
+    fn next(&mut self, duration: &Duration) -> Option<Value> {
+        let (state, value) = self.cfg.next((self.state, duration));
+        self.state = state;
+        value
+    }
+}
+
+impl PureController<(PlaybackState, &Duration), (PlaybackState, Option<Value>)> for PlaybackConfig {
+    fn next(&self, input: (PlaybackState, &Duration)) -> (PlaybackState, Option<Value>) {
+        let (mut state, duration) = input;
+        let offset = match self.repeat {
+            Some(period) if period > Duration::new(0, 0) => {
+                Duration::from_nanos((state.offset.as_nanos() % period.as_nanos()) as u64)
+            }
+            _ => state.offset,
+        };
+        let value = self
+            .samples
+            .iter()
+            .take_while(|(o, _)| *o <= offset)
+            .last()
+            .map(|(_, v)| v.clone());
+        state.offset += *duration;
+        if let Some(period) = self.repeat {
+            if period > Duration::new(0, 0) && state.offset >= period {
+                state.offset -= period;
+            }
+        }
+        (state, value)
+    }
+}
+
+/// A generator of test signals, e.g. to excite a loop during commissioning
+///
+/// Each call advances the time by `delta_t` and
+/// returns the value of the signal at the new time.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::{TimeStepController, sim::*};
+/// use std::time::Duration;
+///
+/// let mut gen = SignalGen::new(SignalGenConfig {
+///     waveform: Waveform::Sine,
+///     amplitude: 5.0,
+///     frequency: 0.01,
+///     offset: 50.0,
+/// });
+/// let dt = Duration::from_millis(100);
+/// let setpoint = gen.next((), &dt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SignalGen {
+    cfg: SignalGenConfig,
+    /// Current generator state
+    pub state: SignalGenState,
+}
+
+/// Signal generator configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SignalGenConfig {
+    /// The shape of the signal
+    pub waveform: Waveform,
+    /// The amplitude of the signal
+    pub amplitude: f64,
+    /// The frequency of the signal in Hz
+    pub frequency: f64,
+    /// The value the signal is added to
+    pub offset: f64,
+}
+
+/// The waveform of a [SignalGen]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Waveform {
+    /// A step by `amplitude` at the start
+    #[default]
+    Step,
+    /// A ramp rising by `amplitude` per period
+    Ramp,
+    /// A sine wave
+    Sine,
+    /// A square wave, starting with `+amplitude`
+    Square,
+    /// A triangle wave that is in phase with a sine wave
+    Triangle,
+    /// Uniformly distributed white noise within `[-amplitude, amplitude)`
+    Noise {
+        /// The seed of the random number generator
+        seed: u64,
+    },
+}
+
+/// Internal signal generator state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalGenState {
+    /// The elapsed time
+    pub elapsed: Duration,
+    /// The noise generator
+    pub noise: Option<Noise>,
+}
+
+impl Default for SignalGenConfig {
+    fn default() -> Self {
+        SignalGenConfig {
+            waveform: Waveform::default(),
+            amplitude: 1.0,
+            frequency: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl SignalGenConfig {
+    fn initial_state(&self) -> SignalGenState {
+        let noise = match self.waveform {
+            Waveform::Noise { seed } => Some(Noise::new(&NoiseConfig {
+                seed,
+                amplitude: self.amplitude,
+            })),
+            _ => None,
+        };
+        SignalGenState {
+            elapsed: Duration::new(0, 0),
+            noise,
+        }
+    }
+}
+
+impl SignalGen {
+    /// Create a new generator instance.
+    pub fn new(cfg: SignalGenConfig) -> Self {
+        let state = cfg.initial_state();
+        SignalGen { cfg, state }
+    }
+    /// Restart the signal.
+    pub fn reset(&mut self) {
+        self.state = self.cfg.initial_state();
+    }
+}
+
+impl Controller<((), &Duration), f64> for SignalGen {
+    fn next(&mut self, input: ((), &Duration)) -> f64 {
+        let (_, duration) = input;
+        let (state, value) = self.cfg.next((self.state, duration));
+        self.state = state;
+        value
+    }
+}
+
+impl PureController<(SignalGenState, &Duration), (SignalGenState, f64)> for SignalGenConfig {
+    fn next(&self, input: (SignalGenState, &Duration)) -> (SignalGenState, f64) {
+        let (mut state, duration) = input;
+        state.elapsed += *duration;
+        let t = f64::from(DurationInSeconds::from(state.elapsed));
+        let phase = (t * self.frequency).fract();
+        let signal = match self.waveform {
+            Waveform::Step => 1.0,
+            Waveform::Ramp => t * self.frequency,
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => {
+                if phase < 0.25 {
+                    4.0 * phase
+                } else if phase < 0.75 {
+                    2.0 - 4.0 * phase
+                } else {
+                    4.0 * phase - 4.0
+                }
+            }
+            Waveform::Noise { .. } => {
+                let noise = state.noise.as_mut().map(Noise::next_value).unwrap_or(0.0);
+                return (state, self.offset + noise);
+            }
+        };
+        (state, self.offset + self.amplitude * signal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn default_simulator_config() {
+        let cfg = SimulatorConfig::default();
+        assert_eq!(cfg.gain, 1.0);
+        assert_eq!(cfg.time_constant, Duration::from_secs(1));
+        assert_eq!(cfg.initial_value, 0.0);
+        assert_eq!(cfg.noise, None);
+    }
+
+    #[test]
+    fn simulate_first_order_lag() {
+        let cfg = SimulatorConfig {
+            gain: 2.0,
+            time_constant: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let mut sim = Simulator::new(cfg);
+        let y = sim.next((5.0, &Duration::from_secs(1)));
+        assert!((y - 10.0 * (1.0 - (-1.0_f64).exp())).abs() < 1e-9);
+        for _ in 0..50 {
+            sim.next((5.0, &Duration::from_secs(1)));
+        }
+        assert!((sim.state.value - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reproduce_noise_with_the_same_seed() {
+        let cfg = SimulatorConfig {
+            noise: Some(NoiseConfig {
+                seed: 7,
+                amplitude: 0.5,
+            }),
+            ..Default::default()
+        };
+        let dt = Duration::from_millis(100);
+        let run = |cfg: SimulatorConfig| {
+            let mut sim = Simulator::new(cfg);
+            (0..100).map(|_| sim.next((1.0, &dt))).collect::<Vec<_>>()
+        };
+        let first = run(cfg.clone());
+        let second = run(cfg.clone());
+        assert_eq!(first, second);
+
+        let mut sim = Simulator::new(cfg.clone());
+        for (expected, value) in first.iter().zip(run(cfg.clone())) {
+            assert_eq!(*expected, value);
+            let noisy = sim.next((1.0, &dt));
+            assert!((noisy - sim.state.value).abs() <= 0.5);
+        }
+        sim.reset();
+        assert_eq!(sim.next((1.0, &dt)), first[0]);
+
+        let mut other = cfg;
+        other.noise = Some(NoiseConfig {
+            seed: 8,
+            amplitude: 0.5,
+        });
+        assert_ne!(first, run(other));
+    }
+
+    fn samples() -> Vec<(Duration, Value)> {
+        vec![
+            (Duration::from_secs(3), Value::Bit(true)),
+            (Duration::from_secs(1), Value::Decimal(1.0)),
+            (Duration::from_secs(2), Value::Decimal(2.0)),
+        ]
+    }
+
+    #[test]
+    fn play_back_series_and_stop() {
+        let mut playback = Playback::new(PlaybackConfig {
+            samples: samples(),
+            repeat: None,
+        });
+        let dt = Duration::from_millis(500);
+        let values: Vec<_> = (0..9).map(|_| playback.next(&dt)).collect();
+        assert_eq!(
+            values,
+            vec![
+                None,
+                None,
+                Some(Value::Decimal(1.0)),
+                Some(Value::Decimal(1.0)),
+                Some(Value::Decimal(2.0)),
+                Some(Value::Decimal(2.0)),
+                Some(Value::Bit(true)),
+                Some(Value::Bit(true)),
+                Some(Value::Bit(true)),
+            ]
+        );
+        assert!(playback.is_finished());
+        playback.reset();
+        assert!(!playback.is_finished());
+        assert_eq!(playback.next(&dt), None);
+    }
+
+    #[test]
+    fn repeat_series() {
+        let mut playback = Playback::new(PlaybackConfig {
+            samples: samples(),
+            repeat: Some(Duration::from_secs(4)),
+        });
+        let dt = Duration::from_secs(1);
+        let values: Vec<_> = (0..9).map(|_| playback.next(&dt)).collect();
+        assert_eq!(
+            values,
+            vec![
+                None,
+                Some(Value::Decimal(1.0)),
+                Some(Value::Decimal(2.0)),
+                Some(Value::Bit(true)),
+                None,
+                Some(Value::Decimal(1.0)),
+                Some(Value::Decimal(2.0)),
+                Some(Value::Bit(true)),
+                None,
+            ]
+        );
+        assert!(!playback.is_finished());
+    }
+
+    #[test]
+    fn generate_sine() {
+        let mut gen = SignalGen::new(SignalGenConfig {
+            waveform: Waveform::Sine,
+            amplitude: 2.0,
+            frequency: 0.25,
+            offset: 10.0,
+        });
+        let dt = Duration::from_secs(1);
+        // a quarter period
+        assert!((gen.next(((), &dt)) - 12.0).abs() < 1e-9);
+        assert!((gen.next(((), &dt)) - 10.0).abs() < 1e-9);
+        assert!((gen.next(((), &dt)) - 8.0).abs() < 1e-9);
+        assert!((gen.next(((), &dt)) - 10.0).abs() < 1e-9);
+        gen.reset();
+        assert!((gen.next(((), &dt)) - 12.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn generate_other_waveforms() {
+        let dt = Duration::from_millis(250);
+        let values = |waveform| {
+            let mut gen = SignalGen::new(SignalGenConfig {
+                waveform,
+                ..Default::default()
+            });
+            (0..4).map(|_| gen.next(((), &dt))).collect::<Vec<_>>()
+        };
+        assert_eq!(values(Waveform::Step), vec![1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(values(Waveform::Ramp), vec![0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(values(Waveform::Square), vec![1.0, -1.0, -1.0, 1.0]);
+        assert_eq!(values(Waveform::Triangle), vec![1.0, 0.0, -1.0, 0.0]);
+
+        let noise = values(Waveform::Noise { seed: 7 });
+        assert!(noise.iter().all(|v| (-1.0..1.0).contains(v)));
+        assert_eq!(noise, values(Waveform::Noise { seed: 7 }));
+    }
+}
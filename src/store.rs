@@ -0,0 +1,202 @@
+use super::*;
+use std::{fs, path::PathBuf};
+
+/// A persistent storage of an [IoState].
+///
+/// It allows to recover the last known state after a restart or crash.
+pub trait StateStore {
+    /// Persist the state.
+    fn save(&mut self, state: &IoState) -> Result<()>;
+    /// Load the last persisted state.
+    fn load(&self) -> Result<IoState>;
+}
+
+/// Encodes an [IoState] for a [FileStore].
+pub type Encode = fn(&IoState) -> Result<Vec<u8>>;
+
+/// Decodes an [IoState] of a [FileStore].
+pub type Decode = fn(&[u8]) -> Result<IoState>;
+
+/// A [StateStore] that keeps the state in a file.
+///
+/// The file format is up to the application,
+/// e.g. JSON by using the `serde` feature with `serde_json`.
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+///
+/// let mut store = FileStore::new(
+///     "/var/lib/msr/state.json",
+///     |state| Ok(serde_json::to_vec(state)?),
+///     |data| Ok(serde_json::from_slice(data)?),
+/// );
+/// let state = store.load().unwrap_or_default();
+/// store.save(&state).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    path: PathBuf,
+    encode: Encode,
+    decode: Decode,
+}
+
+impl FileStore {
+    /// Create a store for the given file.
+    pub fn new<P: Into<PathBuf>>(path: P, encode: Encode, decode: Decode) -> Self {
+        FileStore {
+            path: path.into(),
+            encode,
+            decode,
+        }
+    }
+}
+
+impl StateStore for FileStore {
+    fn save(&mut self, state: &IoState) -> Result<()> {
+        let data = (self.encode)(state)?;
+        // Write to a temporary file first, so a crash while
+        // writing doesn't destroy the previous state.
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    fn load(&self) -> Result<IoState> {
+        let data = fs::read(&self.path)?;
+        (self.decode)(&data)
+    }
+}
+
+/// Persists the state periodically.
+///
+/// See [SyncRuntime::next_with_persistence].
+///
+/// # Example
+/// ```rust,no_run
+/// use msr::*;
+/// use std::time::Duration;
+///
+/// let store = FileStore::new(
+///     "state.json",
+///     |state| Ok(serde_json::to_vec(state)?),
+///     |data| Ok(serde_json::from_slice(data)?),
+/// );
+/// let mut persistence = PeriodicPersistence::new(store, Duration::from_secs(60));
+///
+/// let state = IoState::default();
+/// let dt = Duration::from_millis(100);
+/// persistence.persist(&state, &dt).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct PeriodicPersistence<S> {
+    /// The underlying store
+    pub store: S,
+    interval: Duration,
+    elapsed: Duration,
+}
+
+impl<S> PeriodicPersistence<S> {
+    /// Create a new instance that saves the state
+    /// each time the `interval` elapsed.
+    pub fn new(store: S, interval: Duration) -> Self {
+        PeriodicPersistence {
+            store,
+            interval,
+            elapsed: Duration::new(0, 0),
+        }
+    }
+}
+
+impl<S: StateStore> PeriodicPersistence<S> {
+    /// Save the state if the interval elapsed.
+    ///
+    /// Returns `true` if the state was saved.
+    pub fn persist(&mut self, state: &IoState, duration: &Duration) -> Result<bool> {
+        self.elapsed += *duration;
+        if self.elapsed < self.interval {
+            return Ok(false);
+        }
+        self.store.save(state)?;
+        self.elapsed = Duration::new(0, 0);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MockStore {
+        state: Option<IoState>,
+        saved: usize,
+    }
+
+    impl StateStore for MockStore {
+        fn save(&mut self, state: &IoState) -> Result<()> {
+            self.state = Some(state.clone());
+            self.saved += 1;
+            Ok(())
+        }
+        fn load(&self) -> Result<IoState> {
+            self.state
+                .clone()
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "nothing saved"))
+        }
+    }
+
+    fn io_state() -> IoState {
+        let mut state = IoState::default();
+        state.inputs.insert("x".into(), Value::Decimal(1.5));
+        state.inputs.insert("n".into(), Value::Integer(42));
+        state.outputs.insert("y".into(), Value::Bit(true));
+        state.mem.insert("t".into(), Value::Text("foo".into()));
+        state
+    }
+
+    #[test]
+    fn round_trip_through_mock_store() {
+        let mut store = MockStore::default();
+        assert!(store.load().is_err());
+        let state = io_state();
+        store.save(&state).unwrap();
+        assert_eq!(store.load().unwrap(), state);
+    }
+
+    #[test]
+    fn persist_periodically() {
+        let mut persistence =
+            PeriodicPersistence::new(MockStore::default(), Duration::from_secs(2));
+        let state = io_state();
+        let dt = Duration::from_secs(1);
+        assert!(!persistence.persist(&state, &dt).unwrap());
+        assert!(persistence.persist(&state, &dt).unwrap());
+        assert!(!persistence.persist(&state, &dt).unwrap());
+        assert!(persistence.persist(&state, &dt).unwrap());
+        assert_eq!(persistence.store.saved, 2);
+        assert_eq!(persistence.store.load().unwrap(), state);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trip_through_file_store() {
+        let path = std::env::temp_dir().join(format!("msr-state-{}.json", std::process::id()));
+        let mut store = FileStore::new(
+            &path,
+            |state| Ok(serde_json::to_vec(state)?),
+            |data| Ok(serde_json::from_slice(data)?),
+        );
+        let mut state = io_state();
+        state.outputs.insert("b".into(), Value::Bin(vec![1, 2]));
+        state
+            .mem
+            .insert("d".into(), Value::Timeout(Duration::from_millis(1500)));
+        store.save(&state).unwrap();
+        assert_eq!(store.load().unwrap(), state);
+        fs::remove_file(&path).unwrap();
+        assert!(store.load().is_err());
+    }
+}
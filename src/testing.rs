@@ -0,0 +1,197 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{pid::*, testing::*};
+//! use std::time::Duration;
+//!
+//! let mut pid = Pid::new(PidConfig { k_p: 2.0, ..Default::default() });
+//! pid.set_target(10.0);
+//!
+//! let csv = "\
+//! input,expected
+//! 8.0,4.0
+//! 9.0,2.0
+//! ";
+//! let replay = Replay::new(Duration::from_secs(1), 1e-6);
+//! if let Err(err) = replay.run(&mut pid, csv) {
+//!     panic!("{}", err);
+//! }
+//! ```
+
+use super::Controller;
+use std::{error, fmt, time::Duration};
+
+/// Replays recorded inputs and compares the outputs
+/// of a controller with the expected ones.
+///
+/// The data is given as CSV with two columns per row:
+/// the input and the expected output.
+/// Empty lines, lines starting with `#` and a
+/// header line are ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /// The time step between two rows
+    pub delta_t: Duration,
+    /// The maximum (absolute) deviation of an output
+    pub tolerance: f64,
+}
+
+/// The reason why a replay failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    /// A line of the dataset could not be parsed
+    Parse {
+        /// The line number (starting at `1`)
+        line: usize,
+        /// A description of the problem
+        msg: String,
+    },
+    /// An output didn't match the expected value
+    Mismatch {
+        /// The line number (starting at `1`)
+        line: usize,
+        /// The input of the controller
+        input: f64,
+        /// The expected output
+        expected: f64,
+        /// The actual output
+        actual: f64,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+            ReplayError::Mismatch {
+                line,
+                input,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "line {}: input {} resulted in {} instead of {}",
+                line, input, actual, expected
+            ),
+        }
+    }
+}
+
+impl error::Error for ReplayError {}
+
+impl Replay {
+    /// Create a new replay.
+    pub fn new(delta_t: Duration, tolerance: f64) -> Self {
+        Replay { delta_t, tolerance }
+    }
+
+    /// Feed the inputs to the controller and
+    /// return the first mismatch.
+    pub fn run<C>(&self, controller: &mut C, csv: &str) -> Result<(), ReplayError>
+    where
+        C: for<'a> Controller<(f64, &'a Duration), f64>,
+    {
+        for (line, input, expected) in parse(csv)? {
+            let actual = controller.next((input, &self.delta_t));
+            let deviation = (actual - expected).abs();
+            if deviation.is_nan() || deviation > self.tolerance {
+                return Err(ReplayError::Mismatch {
+                    line,
+                    input,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse(csv: &str) -> Result<Vec<(usize, f64, f64)>, ReplayError> {
+    let mut rows = vec![];
+    for (i, l) in csv.lines().enumerate() {
+        let line = i + 1;
+        let l = l.trim();
+        if l.is_empty() || l.starts_with('#') {
+            continue;
+        }
+        let cols: Vec<_> = l.split(',').map(str::trim).collect();
+        if cols.len() != 2 {
+            return Err(ReplayError::Parse {
+                line,
+                msg: format!("expected 2 columns instead of {}", cols.len()),
+            });
+        }
+        match (cols[0].parse(), cols[1].parse()) {
+            (Ok(input), Ok(expected)) => rows.push((line, input, expected)),
+            // header
+            _ if rows.is_empty() && cols[0].parse::<f64>().is_err() => {}
+            _ => {
+                return Err(ReplayError::Parse {
+                    line,
+                    msg: format!("invalid number in '{}'", l),
+                });
+            }
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::pid::*;
+
+    const DATA: &str = "
+# P controller, k_p = 2, target = 10
+input, expected
+8.0, 4.0
+9.0, 2.0
+10.0, 0.0
+12.5, -5.0
+";
+
+    fn pid() -> Pid {
+        let mut pid = Pid::new(PidConfig {
+            k_p: 2.0,
+            ..Default::default()
+        });
+        pid.set_target(10.0);
+        pid
+    }
+
+    #[test]
+    fn replay_matching_dataset() {
+        let replay = Replay::new(Duration::from_secs(1), 1e-9);
+        assert_eq!(replay.run(&mut pid(), DATA), Ok(()));
+    }
+
+    #[test]
+    fn report_first_mismatch() {
+        let replay = Replay::new(Duration::from_secs(1), 0.1);
+        let data = DATA.replace("10.0, 0.0", "10.0, 0.5");
+        let err = replay.run(&mut pid(), &data).unwrap_err();
+        assert_eq!(
+            err,
+            ReplayError::Mismatch {
+                line: 6,
+                input: 10.0,
+                expected: 0.5,
+                actual: 0.0,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 6: input 10 resulted in 0 instead of 0.5"
+        );
+    }
+
+    #[test]
+    fn report_invalid_rows() {
+        let replay = Replay::new(Duration::from_secs(1), 0.1);
+        let err = replay.run(&mut pid(), "1.0, 2.0\nfoo, 3").unwrap_err();
+        assert!(matches!(err, ReplayError::Parse { line: 2, .. }));
+        let err = replay.run(&mut pid(), "1.0").unwrap_err();
+        assert!(matches!(err, ReplayError::Parse { line: 1, .. }));
+    }
+}
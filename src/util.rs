@@ -1,11 +1,24 @@
 //! Little helpers
 
+use std::time::Duration;
+
 /// Re-maps a number from one range to another.
 pub fn map_value(x: f64, x_min: f64, x_max: f64, y_min: f64, y_max: f64) -> f64 {
     let s = (y_max - y_min) / (x_max - x_min);
     (x - x_min) * s + y_min
 }
 
+/// Cap a time step to a maximum plausible step.
+///
+/// Larger steps (e.g. after a pause of the loop) would otherwise
+/// result in huge jumps of integrating calculations.
+pub fn cap_delta_t(delta_t: &Duration, max: Option<Duration>) -> Duration {
+    match max {
+        Some(max) if max < *delta_t => max,
+        _ => *delta_t,
+    }
+}
+
 /// Limit a value by minimum and maximum values.
 pub fn limit(min: Option<f64>, max: Option<f64>, mut value: f64) -> f64 {
     if let Some(max) = max {
@@ -21,6 +34,40 @@ pub fn limit(min: Option<f64>, max: Option<f64>, mut value: f64) -> f64 {
     value
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub(crate) struct DurationInSeconds(f64);
+
+impl DurationInSeconds {
+    // Number of nanoseconds in a second.
+    const NANOS_PER_SEC: f64 = 1e9;
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0.0
+    }
+
+    pub fn is_valid(self) -> bool {
+        self.0 >= 0.0
+    }
+
+    pub fn seconds(self) -> f64 {
+        self.0
+    }
+}
+
+impl From<Duration> for DurationInSeconds {
+    fn from(from: Duration) -> Self {
+        DurationInSeconds(
+            from.as_secs() as f64 + f64::from(from.subsec_nanos()) / Self::NANOS_PER_SEC,
+        )
+    }
+}
+
+impl From<DurationInSeconds> for f64 {
+    fn from(from: DurationInSeconds) -> Self {
+        from.seconds()
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
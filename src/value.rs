@@ -1,8 +1,11 @@
+use crate::util;
 #[cfg(feature = "serde")]
 use serde::{
     de::{Error, MapAccess, SeqAccess, Visitor},
+    ser::SerializeMap,
     Deserialize, Deserializer, Serialize, Serializer,
 };
+use std::io;
 #[cfg(feature = "serde")]
 use std::{fmt, time::Duration};
 
@@ -21,6 +24,122 @@ pub enum Value {
     Bin(Vec<u8>),
     /// Timeout
     Timeout(Duration),
+    /// A decimal value with its engineering unit (e.g. `"°C"`)
+    Quantity(f64, String),
+}
+
+/// The numeric encoding of bits.
+///
+/// By default bits are not treated as numbers (strict).
+/// If a numeric encoding is chosen, bits can participate
+/// in arithmetic operations.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BitEncoding {
+    /// Bits can't be converted to numbers.
+    #[default]
+    Strict,
+    /// Bits are encoded with the given numbers.
+    Numeric {
+        /// The number that represents `true`
+        on: f64,
+        /// The number that represents `false`
+        off: f64,
+    },
+}
+
+impl BitEncoding {
+    /// Encode `true` as `1.0` and `false` as `0.0`.
+    pub fn permissive() -> Self {
+        BitEncoding::Numeric { on: 1.0, off: 0.0 }
+    }
+}
+
+impl Value {
+    /// Create a decimal value with an engineering unit.
+    pub fn quantity<S: Into<String>>(value: f64, unit: S) -> Self {
+        Value::Quantity(value, unit.into())
+    }
+    /// The engineering unit of a [Value::Quantity].
+    pub fn unit(&self) -> Option<&str> {
+        match self {
+            Value::Quantity(_, unit) => Some(unit),
+            _ => None,
+        }
+    }
+    /// Get the numeric representation of the value.
+    ///
+    /// Decimals, quantities and integers are always numeric,
+    /// bits only if a numeric [BitEncoding] is given.
+    pub fn to_decimal(&self, encoding: BitEncoding) -> io::Result<f64> {
+        match (self, encoding) {
+            (Value::Decimal(v), _) | (Value::Quantity(v, _), _) => Ok(*v),
+            (Value::Integer(v), _) => Ok(*v as f64),
+            (Value::Bit(b), BitEncoding::Numeric { on, off }) => Ok(if *b { on } else { off }),
+            (Value::Bit(_), BitEncoding::Strict) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Bits can't be used as numbers with a strict encoding",
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The value is not numeric",
+            )),
+        }
+    }
+}
+
+/// The handling of values outside of a range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OutOfRange {
+    /// Limit the result to the target range.
+    #[default]
+    Clamp,
+    /// Map the value linearly beyond the range.
+    Extrapolate,
+}
+
+/// Map a numeric value from `in_min..in_max` to `0.0..1.0`.
+///
+/// Returns an error if the value is not numeric
+/// or if the range is empty.
+pub fn normalize(
+    value: &Value,
+    in_min: f64,
+    in_max: f64,
+    out_of_range: OutOfRange,
+) -> io::Result<Value> {
+    let v = value.to_decimal(BitEncoding::Strict)?;
+    let span = in_max - in_min;
+    if span == 0.0 || !span.is_finite() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Invalid input range",
+        ));
+    }
+    let norm = util::map_value(v, in_min, in_max, 0.0, 1.0);
+    Ok(Value::Decimal(match out_of_range {
+        OutOfRange::Clamp => norm.clamp(0.0, 1.0),
+        OutOfRange::Extrapolate => norm,
+    }))
+}
+
+/// Map a normalized numeric value from `0.0..1.0` to `out_min..out_max`.
+///
+/// Returns an error if the value is not numeric.
+pub fn denormalize(
+    norm: &Value,
+    out_min: f64,
+    out_max: f64,
+    out_of_range: OutOfRange,
+) -> io::Result<Value> {
+    let mut n = norm.to_decimal(BitEncoding::Strict)?;
+    if out_of_range == OutOfRange::Clamp {
+        n = n.clamp(0.0, 1.0);
+    }
+    Ok(Value::Decimal(util::map_value(
+        n, 0.0, 1.0, out_min, out_max,
+    )))
 }
 
 impl From<bool> for Value {
@@ -84,6 +203,12 @@ impl Serialize for Value {
             Value::Text(t) => serializer.serialize_str(t),
             Value::Bin(b) => serializer.serialize_bytes(b),
             Value::Timeout(t) => t.serialize(serializer),
+            Value::Quantity(v, unit) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("value", v)?;
+                map.serialize_entry("unit", unit)?;
+                map.end()
+            }
         }
     }
 }
@@ -96,7 +221,8 @@ impl<'de> Visitor<'de> for ValueVisitor {
     type Value = Value;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("a boolean, a number, a string, an array of bytes or an timeout.")
+        formatter
+            .write_str("a boolean, a number, a string, an array of bytes, a timeout or a quantity.")
     }
     fn visit_bool<E>(self, value: bool) -> Result<Value, E> {
         Ok(Value::Bit(value))
@@ -107,6 +233,15 @@ impl<'de> Visitor<'de> for ValueVisitor {
     fn visit_i64<E>(self, value: i64) -> Result<Value, E> {
         Ok(Value::Integer(value))
     }
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E>
+    where
+        E: ::serde::de::Error,
+    {
+        if value > i64::MAX as u64 {
+            return Err(E::custom(format!("Integer out of range: {}", value)));
+        }
+        Ok(Value::Integer(value as i64))
+    }
     fn visit_str<E>(self, value: &str) -> Result<Value, E>
     where
         E: ::serde::de::Error,
@@ -131,22 +266,31 @@ impl<'de> Visitor<'de> for ValueVisitor {
     {
         let mut secs: Option<u64> = None;
         let mut nanos: Option<u32> = None;
+        let mut value: Option<f64> = None;
+        let mut unit: Option<String> = None;
 
-        while let Some((key, value)) = access.next_entry()? {
-            match key {
+        while let Some(key) = access.next_key::<String>()? {
+            match key.as_str() {
                 "secs" => {
-                    secs = Some(value);
+                    secs = Some(access.next_value()?);
                 }
                 "nanos" => {
-                    nanos = Some(value as u32);
+                    nanos = Some(access.next_value()?);
+                }
+                "value" => {
+                    value = Some(access.next_value()?);
+                }
+                "unit" => {
+                    unit = Some(access.next_value()?);
                 }
                 k => return Err(A::Error::custom(format!("Unknown key: {}", k))),
             }
         }
-        if let Some(secs) = secs {
-            if let Some(nanos) = nanos {
-                return Ok(Value::Timeout(Duration::new(secs, nanos)));
-            }
+        if let (Some(secs), Some(nanos)) = (secs, nanos) {
+            return Ok(Value::Timeout(Duration::new(secs, nanos)));
+        }
+        if let (Some(value), Some(unit)) = (value, unit) {
+            return Ok(Value::Quantity(value, unit));
         }
         Err(A::Error::custom("Unknown map"))
     }
@@ -183,6 +327,70 @@ mod tests {
         assert_eq!(Value::from(vec![0x07]), Value::Bin(vec![0x07]));
     }
 
+    #[test]
+    fn encode_bits_as_numbers() {
+        let a = Value::Bit(true);
+        let b = Value::Bit(true);
+        let strict = BitEncoding::default();
+        assert_eq!(strict, BitEncoding::Strict);
+        assert!(a.to_decimal(strict).is_err());
+        let permissive = BitEncoding::permissive();
+        let sum = a.to_decimal(permissive).unwrap() + b.to_decimal(permissive).unwrap();
+        assert_eq!(sum, 2.0);
+        assert_eq!(Value::Bit(false).to_decimal(permissive).unwrap(), 0.0);
+        let custom = BitEncoding::Numeric {
+            on: 100.0,
+            off: -1.0,
+        };
+        assert_eq!(Value::Bit(false).to_decimal(custom).unwrap(), -1.0);
+        assert_eq!(Value::Integer(3).to_decimal(strict).unwrap(), 3.0);
+        assert_eq!(Value::Decimal(3.5).to_decimal(strict).unwrap(), 3.5);
+        assert!(Value::Text("1".into()).to_decimal(permissive).is_err());
+        assert_eq!(Value::quantity(3.5, "bar").to_decimal(strict).unwrap(), 3.5);
+    }
+
+    #[test]
+    fn normalize_and_denormalize_ranges() {
+        let clamp = OutOfRange::Clamp;
+        let n = normalize(&Value::Decimal(2.5), 0.0, 10.0, clamp).unwrap();
+        assert_eq!(n, Value::Decimal(0.25));
+        assert_eq!(
+            denormalize(&n, 0.0, 10.0, clamp).unwrap(),
+            Value::Decimal(2.5)
+        );
+        assert_eq!(
+            normalize(&Value::Integer(10), 0.0, 10.0, clamp).unwrap(),
+            Value::Decimal(1.0)
+        );
+        // reversed ranges
+        assert_eq!(
+            normalize(&Value::Decimal(2.5), 10.0, 0.0, clamp).unwrap(),
+            Value::Decimal(0.75)
+        );
+        // out of range
+        assert_eq!(
+            normalize(&Value::Decimal(12.0), 0.0, 10.0, clamp).unwrap(),
+            Value::Decimal(1.0)
+        );
+        assert_eq!(
+            normalize(&Value::Decimal(-5.0), 0.0, 10.0, OutOfRange::Extrapolate).unwrap(),
+            Value::Decimal(-0.5)
+        );
+        assert_eq!(
+            denormalize(&Value::Decimal(1.5), 0.0, 10.0, clamp).unwrap(),
+            Value::Decimal(10.0)
+        );
+        assert_eq!(
+            denormalize(&Value::Decimal(1.5), 0.0, 10.0, OutOfRange::Extrapolate).unwrap(),
+            Value::Decimal(15.0)
+        );
+        // errors
+        assert!(normalize(&Value::Bit(true), 0.0, 10.0, clamp).is_err());
+        assert!(normalize(&Value::Text("5".into()), 0.0, 10.0, clamp).is_err());
+        assert!(normalize(&Value::Decimal(5.0), 1.0, 1.0, clamp).is_err());
+        assert!(denormalize(&Value::Bit(false), 0.0, 10.0, clamp).is_err());
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn value_serialization() {
@@ -206,6 +414,12 @@ mod tests {
             serde_json::to_string(&t).unwrap(),
             "{\"secs\":1,\"nanos\":500000000}"
         );
+
+        let q = Value::quantity(21.5, "°C");
+        assert_eq!(
+            serde_json::to_string(&q).unwrap(),
+            "{\"value\":21.5,\"unit\":\"°C\"}"
+        );
     }
 
     #[cfg(feature = "serde")]
@@ -231,5 +445,22 @@ mod tests {
 
         assert!(serde_json::from_str::<Value>("{\"secs\":1,\"nanooos\":500}").is_err());
         assert!(serde_json::from_str::<Value>("{\"secs\":1}").is_err());
+
+        let v: Value = serde_json::from_str("{\"value\":21.5,\"unit\":\"°C\"}").unwrap();
+        assert_eq!(v, Value::Quantity(21.5, "°C".into()));
+        assert!(serde_json::from_str::<Value>("{\"value\":21.5}").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn positive_integer_deserialization() {
+        let v: Value = serde_json::from_str("8").unwrap();
+        assert_eq!(v, Value::Integer(8));
+
+        let v: Value = serde_json::from_str(&i64::MAX.to_string()).unwrap();
+        assert_eq!(v, Value::Integer(i64::MAX));
+
+        let max = (i64::MAX as u64 + 1).to_string();
+        assert!(serde_json::from_str::<Value>(&max).is_err());
     }
 }
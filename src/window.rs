@@ -0,0 +1,171 @@
+//! # Example
+//! ```rust,no_run
+//! use msr::{TimeStepController, window::*};
+//! use std::time::Duration;
+//!
+//! let mut peak = WindowExtremum::new(WindowExtremumConfig {
+//!     window: Duration::from_secs(60),
+//!     extremum: Extremum::Max,
+//! });
+//! let dt = Duration::from_secs(1);
+//! assert_eq!(peak.next(3.0, &dt), 3.0);
+//! assert_eq!(peak.next(1.0, &dt), 3.0);
+//! ```
+
+use super::Controller;
+use std::{collections::VecDeque, time::Duration};
+
+/// The maximum or minimum of a signal within a sliding time window
+///
+/// Old extrema expire as the window slides.
+#[derive(Debug, Clone)]
+pub struct WindowExtremum {
+    cfg: WindowExtremumConfig,
+    /// Current window state
+    pub state: WindowExtremumState,
+}
+
+/// Window configuration
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WindowExtremumConfig {
+    /// The length of the window
+    pub window: Duration,
+    /// The tracked extremum
+    pub extremum: Extremum,
+}
+
+/// Kind of extremum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Extremum {
+    /// Track the maximum
+    #[default]
+    Max,
+    /// Track the minimum
+    Min,
+}
+
+/// Internal window state
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WindowExtremumState {
+    /// The time since the start
+    pub elapsed: Duration,
+    /// Monotonic queue of candidate samples `(timestamp, value)`
+    ///
+    /// The current extremum is always at the front.
+    pub candidates: VecDeque<(Duration, f64)>,
+}
+
+impl Default for WindowExtremumConfig {
+    fn default() -> Self {
+        WindowExtremumConfig {
+            window: Duration::from_secs(1),
+            extremum: Extremum::default(),
+        }
+    }
+}
+
+impl Extremum {
+    /// Check if `a` dominates `b`, i.e. `b` can never be the extremum
+    /// as long as `a` is within the window.
+    fn dominates(self, a: f64, b: f64) -> bool {
+        match self {
+            Extremum::Max => a >= b,
+            Extremum::Min => a <= b,
+        }
+    }
+}
+
+impl WindowExtremum {
+    /// Create a new instance.
+    pub fn new(cfg: WindowExtremumConfig) -> Self {
+        WindowExtremum {
+            cfg,
+            state: WindowExtremumState::default(),
+        }
+    }
+    /// The current extremum
+    pub fn value(&self) -> Option<f64> {
+        self.state.candidates.front().map(|(_, v)| *v)
+    }
+    /// Reset the internal state.
+    pub fn reset(&mut self) {
+        self.state = WindowExtremumState::default();
+    }
+}
+
+impl Controller<(f64, &Duration), f64> for WindowExtremum {
+    fn next(&mut self, input: (f64, &Duration)) -> f64 {
+        let (value, duration) = input;
+        let state = &mut self.state;
+        state.elapsed += *duration;
+        while let Some((_, v)) = state.candidates.back() {
+            if self.cfg.extremum.dominates(value, *v) {
+                state.candidates.pop_back();
+            } else {
+                break;
+            }
+        }
+        state.candidates.push_back((state.elapsed, value));
+        while state.candidates.len() > 1 {
+            let (t, _) = state.candidates[0];
+            if state.elapsed - t >= self.cfg.window {
+                state.candidates.pop_front();
+            } else {
+                break;
+            }
+        }
+        state.candidates[0].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn expire_old_maximum() {
+        let mut peak = WindowExtremum::new(WindowExtremumConfig {
+            window: Duration::from_secs(3),
+            extremum: Extremum::Max,
+        });
+        let dt = Duration::from_secs(1);
+        assert_eq!(peak.value(), None);
+        assert_eq!(peak.next((5.0, &dt)), 5.0);
+        assert_eq!(peak.next((2.0, &dt)), 5.0);
+        assert_eq!(peak.next((3.0, &dt)), 5.0);
+        // the peak is 3s old now
+        assert_eq!(peak.next((1.0, &dt)), 3.0);
+        assert_eq!(peak.next((1.0, &dt)), 3.0);
+        assert_eq!(peak.next((0.5, &dt)), 1.0);
+        assert_eq!(peak.next((4.0, &dt)), 4.0);
+        assert_eq!(peak.state.candidates.len(), 1);
+    }
+
+    #[test]
+    fn track_minimum() {
+        let mut low = WindowExtremum::new(WindowExtremumConfig {
+            window: Duration::from_secs(2),
+            extremum: Extremum::Min,
+        });
+        let dt = Duration::from_secs(1);
+        assert_eq!(low.next((-1.0, &dt)), -1.0);
+        assert_eq!(low.next((4.0, &dt)), -1.0);
+        assert_eq!(low.next((2.0, &dt)), 2.0);
+        low.reset();
+        assert_eq!(low.next((7.0, &dt)), 7.0);
+    }
+
+    #[test]
+    fn empty_window_passes_current_value() {
+        let mut peak = WindowExtremum::new(WindowExtremumConfig {
+            window: Duration::new(0, 0),
+            extremum: Extremum::Max,
+        });
+        let dt = Duration::from_secs(1);
+        assert_eq!(peak.next((5.0, &dt)), 5.0);
+        assert_eq!(peak.next((2.0, &dt)), 2.0);
+    }
+}